@@ -0,0 +1,90 @@
+use std::cmp::min;
+use std::collections::VecDeque;
+use std::time::Instant;
+
+const SECONDS: usize = 8;
+const BARS: [char; 8] = ['▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+
+pub(crate) struct Activity {
+    started: Instant,
+    seconds: VecDeque<usize>,
+    second: u64,
+    total: usize,
+}
+impl Activity {
+    pub(crate) fn new() -> Self {
+        Self {
+            started: Instant::now(),
+            seconds: VecDeque::from(vec![0; SECONDS]),
+            second: 0,
+            total: 0,
+        }
+    }
+
+    pub(crate) fn record(&mut self, total: usize) {
+        let second = self.started.elapsed().as_secs();
+        let passed = min(second - self.second, SECONDS as u64);
+        for _ in 0..passed {
+            self.seconds.pop_front();
+            self.seconds.push_back(0);
+        }
+        self.second = second;
+        // The totals start over from nothing on a new connection, so then all of it is new.
+        let added = match total.checked_sub(self.total) {
+            Some(added) => added,
+            None => total,
+        };
+        self.total = total;
+        if let Some(current) = self.seconds.back_mut() {
+            *current += added;
+        }
+    }
+
+    pub(crate) fn sparkline(&self) -> Vec<char> {
+        let busiest = self.seconds.iter().copied().max().unwrap_or(0);
+        self.seconds
+            .iter()
+            .map(|bytes| match *bytes {
+                0 => ' ',
+                bytes => BARS[(bytes * BARS.len()).div_ceil(busiest) - 1],
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn activity_counts_a_new_connection_from_nothing() {
+        let mut activity = Activity::new();
+        activity.record(100);
+        activity.record(10);
+        assert_eq!(activity.seconds.back(), Some(&110));
+    }
+
+    #[test]
+    fn activity_moves_on_a_second_at_a_time() {
+        let mut activity = Activity::new();
+        activity.record(10);
+        activity.started -= Duration::from_secs(2);
+        activity.record(15);
+        assert_eq!(activity.seconds, [0, 0, 0, 0, 0, 10, 0, 5]);
+        activity.started -= Duration::from_secs(60);
+        activity.record(18);
+        assert_eq!(activity.seconds, [0, 0, 0, 0, 0, 0, 0, 3]);
+    }
+
+    #[test]
+    fn sparkline_scales_against_the_busiest_second() {
+        let mut activity = Activity::new();
+        assert_eq!(activity.sparkline(), [' '; SECONDS]);
+        activity.seconds = VecDeque::from(vec![0, 1, 2, 4, 8, 3, 0, 8]);
+        assert_eq!(
+            activity.sparkline().into_iter().collect::<String>(),
+            " ▁▂▄█▃ █"
+        );
+    }
+}
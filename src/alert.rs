@@ -0,0 +1,35 @@
+use crate::error::InitError;
+use crate::radix::parse_hex;
+use error_stack::{IntoReport, Result, ResultExt};
+
+// Reads can split the pattern anywhere, so the end of the previous one is searched along with the next.
+#[derive(Clone)]
+pub(crate) struct Alert {
+    pattern: Vec<u8>,
+    tail: Vec<u8>,
+}
+impl Alert {
+    pub(crate) fn parse(value: &str) -> Result<Self, InitError> {
+        match parse_hex(&value.chars().collect::<Vec<_>>()) {
+            Some(pattern) if !pattern.is_empty() => Ok(Self {
+                pattern,
+                tail: Vec::new(),
+            }),
+            _ => Err(InitError::InvalidArguments)
+                .into_report()
+                .attach_printable(format!("Invalid hex alert pattern \"{value}\".")),
+        }
+    }
+
+    pub(crate) fn matches(&mut self, message: &[u8]) -> bool {
+        let mut haystack = std::mem::take(&mut self.tail);
+        haystack.extend_from_slice(message);
+        let found = haystack
+            .windows(self.pattern.len())
+            .any(|window| window == self.pattern.as_slice());
+
+        let keep = self.pattern.len() - 1;
+        self.tail = haystack[haystack.len().saturating_sub(keep)..].to_vec();
+        found
+    }
+}
@@ -0,0 +1,35 @@
+use crate::error::InitError;
+use error_stack::{IntoReport, Result, ResultExt};
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Checksum {
+    Xor,
+    Sum8,
+}
+impl Checksum {
+    pub fn parse(value: &str) -> Result<Self, InitError> {
+        match value {
+            "xor" => Ok(Self::Xor),
+            "sum8" => Ok(Self::Sum8),
+            _ => Err(InitError::InvalidArguments)
+                .into_report()
+                .attach_printable(format!(
+                    "Invalid checksum \"{value}\" (use \"xor\" or \"sum8\")."
+                )),
+        }
+    }
+
+    pub fn width(&self) -> usize {
+        1
+    }
+
+    pub fn compute(&self, bytes: &[u8]) -> Vec<u8> {
+        let checksum = match self {
+            Self::Xor => bytes.iter().fold(0u8, |checksum, byte| checksum ^ byte),
+            Self::Sum8 => bytes
+                .iter()
+                .fold(0u8, |checksum, byte| checksum.wrapping_add(*byte)),
+        };
+        vec![checksum]
+    }
+}
@@ -0,0 +1,19 @@
+use std::time::{Instant, SystemTime};
+
+// Everything timed reads the clock through here, so tests can move time along themselves instead of waiting.
+pub trait Clock {
+    fn now(&self) -> Instant;
+
+    fn time(&self) -> SystemTime;
+}
+
+pub struct SystemClock;
+impl Clock for SystemClock {
+    fn now(&self) -> Instant {
+        Instant::now()
+    }
+
+    fn time(&self) -> SystemTime {
+        SystemTime::now()
+    }
+}
@@ -0,0 +1,96 @@
+use crate::connection;
+use std::net::SocketAddr;
+use std::path::PathBuf;
+
+// A colon can never be part of a hex payload (a literal `:` is typed as `3a`), so there is no ambiguity.
+pub(crate) const COMMAND_PREFIX: char = ':';
+
+pub(crate) enum LogFormat {
+    Plain,
+    Ansi,
+    Html,
+}
+
+pub(crate) enum Command {
+    Quit,
+    Clear,
+    Save(PathBuf, LogFormat),
+    Load(PathBuf),
+    Connect {
+        target: Option<SocketAddr>,
+        clear: bool,
+    },
+    Record(String),
+    StopRecording,
+    Run(String),
+}
+impl Command {
+    pub(crate) fn parse(line: &str) -> Result<Self, String> {
+        let line = line.strip_prefix(COMMAND_PREFIX).unwrap_or(line);
+        let mut words = line.split_whitespace();
+        let name = words.next().unwrap_or_default();
+        let argument = words.collect::<Vec<_>>().join(" ");
+
+        let path = || {
+            if argument.is_empty() {
+                Err(format!(":{name} requires a file path."))
+            } else {
+                Ok(PathBuf::from(&argument))
+            }
+        };
+
+        match name {
+            "q" | "quit" => Ok(Self::Quit),
+            "clear" => Ok(Self::Clear),
+            "save" => Self::parse_save(&argument),
+            "load" => Ok(Self::Load(path()?)),
+            "connect" => Self::parse_connect(&argument),
+            "macro" => Self::parse_macro(&argument),
+            _ => Err(format!("Unknown command \":{name}\".")),
+        }
+    }
+
+    fn parse_save(argument: &str) -> Result<Self, String> {
+        let (format, path) = match argument.split_once(' ') {
+            Some(("--ansi", path)) => (LogFormat::Ansi, path),
+            Some(("--html", path)) => (LogFormat::Html, path),
+            _ => (LogFormat::Plain, argument),
+        };
+        if path.is_empty() {
+            return Err(":save requires a file path.".to_string());
+        }
+        Ok(Self::Save(PathBuf::from(path), format))
+    }
+
+    fn parse_macro(argument: &str) -> Result<Self, String> {
+        match argument.split_whitespace().collect::<Vec<_>>().as_slice() {
+            ["record", name] if !name.starts_with('#') => Ok(Self::Record(name.to_string())),
+            ["stop"] => Ok(Self::StopRecording),
+            ["run", name] => Ok(Self::Run(name.to_string())),
+            _ => Err("Usage: :macro record <name> | :macro stop | :macro run <name>".to_string()),
+        }
+    }
+
+    fn parse_connect(argument: &str) -> Result<Self, String> {
+        let mut clear = false;
+        let mut words = Vec::new();
+        for word in argument.split_whitespace() {
+            match word {
+                "--clear" => clear = true,
+                _ => words.push(word),
+            }
+        }
+
+        let target = match words.as_slice() {
+            [] => None,
+            [addr, port] => {
+                let port = port
+                    .parse()
+                    .map_err(|_| format!("Invalid port number \"{port}\"."))?;
+                Some(connection::parse_addr(addr, port)?)
+            }
+            _ => return Err("Usage: :connect [<address> <port>] [--clear]".to_string()),
+        };
+        Ok(Self::Connect { target, clear })
+    }
+}
@@ -0,0 +1,754 @@
+use crate::alert::Alert;
+use crate::checksum::Checksum;
+use crate::connection;
+use crate::error::InitError;
+use crate::filter::Filter;
+use crate::framing::{Encoding, Framing, LengthPrefix, Padding};
+use crate::hosts;
+use crate::paint::{Color, Theme};
+use crate::radix::{self, Guess, Radix};
+use crate::sections::{
+    Controls, DEFAULT_SEPARATOR, DEFAULT_UNPRINTABLE, DEFAULT_WRAP_MARKER, FIXED_COLUMNS,
+};
+use crate::socks::Socks5;
+use crate::window::{INPUT_HEIGHT, TITLE_HEIGHT};
+use crate::TcpMessage;
+use error_stack::{IntoReport, Report, Result, ResultExt};
+use std::env;
+use std::net::{Ipv4Addr, SocketAddr};
+use std::os::fd::RawFd;
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::time::Duration;
+
+const DEFAULT_MAX_FRAME: usize = 16 * 1_024 * 1_024;
+const DEFAULT_HIGHLIGHT: Duration = Duration::from_millis(1_000);
+const DEFAULT_LARGE_INPUT: usize = 64 * 1_024;
+const DEFAULT_ONELINE_INTERVAL: Duration = Duration::from_millis(1_000);
+const DEFAULT_TIMEOUT: Duration = Duration::from_millis(10_000);
+
+pub(crate) struct Config {
+    pub(crate) version: bool,
+    pub(crate) hosts: Option<PathBuf>,
+    pub(crate) macros: Option<PathBuf>,
+    pub(crate) args: Vec<String>,
+    pub(crate) target: SocketAddr,
+    pub(crate) fd: Option<RawFd>,
+    pub(crate) unix: bool,
+    pub(crate) family: Option<u8>,
+    pub(crate) proxy: Option<Socks5>,
+    pub(crate) theme: Theme,
+    pub(crate) dump: Option<DumpFormat>,
+    pub(crate) dump_local: bool,
+    pub(crate) send_file: Option<PathBuf>,
+    pub(crate) send_lines: Option<PathBuf>,
+    pub(crate) line_terminator: TcpMessage,
+    pub(crate) line_delay: Duration,
+    pub(crate) skip_comments: bool,
+    pub(crate) send_env: Option<String>,
+    pub(crate) send_args: Option<String>,
+    pub(crate) open: Option<PathBuf>,
+    pub(crate) validate: Option<String>,
+    pub(crate) send_framing: Framing,
+    pub(crate) encoding: Encoding,
+    pub(crate) recv_checksum: Option<Checksum>,
+    pub(crate) verify_checksum: bool,
+    pub(crate) recv_framing: Framing,
+    pub(crate) max_frame: usize,
+    pub(crate) strip_header: usize,
+    pub(crate) strip_footer: usize,
+    pub(crate) flush: bool,
+    pub(crate) strict: bool,
+    pub(crate) alert: Option<Alert>,
+    pub(crate) filter_out: Option<Filter>,
+    pub(crate) filter_in: Option<Filter>,
+    pub(crate) radix: Radix,
+    pub(crate) detect_input: Option<Vec<Guess>>,
+    pub(crate) keep_trailing_space: bool,
+    pub(crate) space_delimits: bool,
+    pub(crate) group_input: bool,
+    pub(crate) explicit_send: bool,
+    pub(crate) large_input: usize,
+    pub(crate) confirm_large: bool,
+    pub(crate) context: usize,
+    pub(crate) fixed_width: bool,
+    pub(crate) local_columns: usize,
+    pub(crate) remote_columns: usize,
+    pub(crate) columns: Option<usize>,
+    pub(crate) newest_first: bool,
+    pub(crate) title_height: usize,
+    pub(crate) input_height: usize,
+    pub(crate) wrap_marker: String,
+    pub(crate) separator: char,
+    pub(crate) compact_labels: bool,
+    pub(crate) unprintable: char,
+    pub(crate) controls: Controls,
+    pub(crate) hide_empty: bool,
+    pub(crate) diff: bool,
+    pub(crate) placeholder: bool,
+    pub(crate) highlight: Duration,
+    pub(crate) throttle: Option<Duration>,
+    pub(crate) sparkline: bool,
+    pub(crate) pause_unfocused: bool,
+    pub(crate) mouse: bool,
+    pub(crate) pipe: bool,
+    pub(crate) echo_server: bool,
+    pub(crate) json: bool,
+    pub(crate) json_input: JsonInput,
+    pub(crate) half_close_on_eof: bool,
+    pub(crate) expect_bytes: Option<usize>,
+    pub(crate) oneline: Option<Duration>,
+    pub(crate) timeout: Duration,
+    pub(crate) exit_on_close: bool,
+    pub(crate) monitor: bool,
+    pub(crate) keep_without_input: bool,
+    pub(crate) idle_timeout: Option<Duration>,
+}
+
+#[derive(Clone, Copy)]
+pub(crate) enum DumpFormat {
+    Hex,
+    Raw,
+}
+impl DumpFormat {
+    fn parse(value: &str) -> Result<Self, InitError> {
+        match value {
+            "hex" => Ok(Self::Hex),
+            "raw" => Ok(Self::Raw),
+            _ => Err(InitError::InvalidArguments)
+                .into_report()
+                .attach_printable(format!(
+                    "Invalid dump format \"{value}\" (use \"hex\" or \"raw\")."
+                )),
+        }
+    }
+}
+#[derive(Clone, Copy)]
+pub(crate) enum JsonInput {
+    Hex,
+    Json,
+}
+impl JsonInput {
+    fn parse(value: &str) -> Result<Self, InitError> {
+        match value {
+            "hex" => Ok(Self::Hex),
+            "json" => Ok(Self::Json),
+            _ => Err(InitError::InvalidArguments)
+                .into_report()
+                .attach_printable(format!(
+                    "Invalid JSON input format \"{value}\" (use \"hex\" or \"json\")."
+                )),
+        }
+    }
+}
+impl Config {
+    pub(crate) fn from_env() -> Result<Self, InitError> {
+        let args = env::args().skip(1).collect::<Vec<_>>();
+        // Only the target has to be known to look the saved options up.
+        let config = Self::from_args(args.clone())?;
+        Self::with_saved(config, args, None)
+    }
+
+    pub(crate) fn for_target(args: &[String], target: SocketAddr) -> Result<Self, InitError> {
+        let config = Self::parse(args.to_vec(), Some(target))?;
+        Self::with_saved(config, args.to_vec(), Some(target))
+    }
+
+    // The saved options go first, so that anything given on the command line still overrides them.
+    fn with_saved(
+        config: Self,
+        args: Vec<String>,
+        connecting: Option<SocketAddr>,
+    ) -> Result<Self, InitError> {
+        let mut config = config;
+        // Nothing is connected to for these, so there are no host settings to use (or to be stopped by).
+        let connects = !config.version && config.validate.is_none() && config.open.is_none();
+        if let Some(path) = config
+            .hosts
+            .clone()
+            .or_else(hosts::default_path)
+            .filter(|_| connects)
+        {
+            let saved = hosts::options_for(&path, config.target)?;
+            if !saved.is_empty() {
+                let target = config.target;
+                config = Self::parse(saved.into_iter().chain(args.iter().cloned()), connecting)
+                    .attach_printable(format!("Using host settings from {}.", path.display()))?;
+                if config.target != target {
+                    Err(InitError::InvalidArguments)
+                        .into_report()
+                        .attach_printable(format!(
+                            "Host settings in {} can only be options, not where to connect to.",
+                            path.display()
+                        ))?;
+                }
+            }
+        }
+        // See https://no-color.org/
+        if env::var_os("NO_COLOR").is_some_and(|value| !value.is_empty()) {
+            config.theme = Theme::monochrome();
+        }
+        config.args = args;
+        Ok(config)
+    }
+
+    pub(crate) fn from_args(args: impl IntoIterator<Item = String>) -> Result<Self, InitError> {
+        Self::parse(args, None)
+    }
+
+    fn parse(
+        args: impl IntoIterator<Item = String>,
+        connecting: Option<SocketAddr>,
+    ) -> Result<Self, InitError> {
+        let mut args = args.into_iter();
+        let mut positional: Vec<String> = Vec::new();
+        let mut send_args = None;
+        let mut version = false;
+        let mut hosts = None;
+        let mut macros = None;
+        let mut fd = None;
+        let mut family = None;
+        let mut proxy_addr = None;
+        let mut proxy_username = None;
+        let mut proxy_password = None;
+        let mut theme = Theme::default();
+        let mut dump = None;
+        let mut dump_local = false;
+        let mut send_file = None;
+        let mut send_lines = None;
+        let mut line_terminator = b"\n".to_vec();
+        let mut line_delay = Duration::ZERO;
+        let mut skip_comments = false;
+        let mut send_env = None;
+        let mut open = None;
+        let mut validate = None;
+        let mut send_framing = Framing::default();
+        let mut encoding = Encoding::default();
+        let mut recv_checksum = None;
+        let mut verify_checksum = false;
+        let mut recv_framing = Framing::default();
+        let mut max_frame = DEFAULT_MAX_FRAME;
+        let mut strip_header = 0;
+        let mut strip_footer = 0;
+        let mut flush = true;
+        let mut strict = false;
+        let mut alert = None;
+        let mut filter_out = None;
+        let mut filter_in = None;
+        let mut radix = Radix::default();
+        let mut detect_input = None;
+        let mut keep_trailing_space = false;
+        let mut space_delimits = false;
+        let mut group_input = false;
+        let mut explicit_send = false;
+        let mut large_input = DEFAULT_LARGE_INPUT;
+        let mut confirm_large = false;
+        let mut context = 0;
+        let mut fixed_width = false;
+        let mut row_bytes = FIXED_COLUMNS;
+        let mut local_columns = None;
+        let mut remote_columns = None;
+        let mut columns = None;
+        let mut newest_first = false;
+        let mut wrap_marker = DEFAULT_WRAP_MARKER.to_string();
+        let mut separator = DEFAULT_SEPARATOR;
+        let mut compact_labels = false;
+        let mut unprintable = DEFAULT_UNPRINTABLE;
+        let mut hide_empty = false;
+        let mut diff = false;
+        let mut controls = Controls::default();
+        let mut title_height = TITLE_HEIGHT;
+        let mut input_height = INPUT_HEIGHT;
+        let mut placeholder = true;
+        let mut highlight = DEFAULT_HIGHLIGHT;
+        let mut throttle = None;
+        let mut sparkline = false;
+        let mut pause_unfocused = false;
+        let mut mouse = false;
+        let mut pipe = false;
+        let mut echo_server = false;
+        let mut json = false;
+        let mut json_input = JsonInput::Hex;
+        let mut half_close_on_eof = false;
+        let mut expect_bytes = None;
+        let mut oneline = false;
+        let mut oneline_interval = DEFAULT_ONELINE_INTERVAL;
+        let mut timeout = DEFAULT_TIMEOUT;
+        let mut exit_on_close = false;
+        let mut monitor = false;
+        let mut keep_without_input = false;
+        let mut idle_timeout = None;
+
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--version" => version = true,
+                "--fd" => fd = Some(number(&arg, args.next())?),
+                "-4" => family = Some(4),
+                "-6" => family = Some(6),
+                "--hosts" => hosts = Some(PathBuf::from(value(&arg, args.next())?)),
+                "--macros" => macros = Some(PathBuf::from(value(&arg, args.next())?)),
+                "--no-color" => theme = Theme::monochrome(),
+                "--socks5" => proxy_addr = Some(value(&arg, args.next())?),
+                "--socks5-username" => proxy_username = Some(value(&arg, args.next())?),
+                "--socks5-password-env" => {
+                    proxy_password = Some(password_env(&value(&arg, args.next())?)?)
+                }
+                "--color-local" => theme.local = Some(Color::parse(&value(&arg, args.next())?)?),
+                "--color-remote" => theme.remote = Some(Color::parse(&value(&arg, args.next())?)?),
+                "--color-warning" => {
+                    theme.warning = Some(Color::parse(&value(&arg, args.next())?)?)
+                }
+                "--dump" => dump = Some(DumpFormat::parse(&value(&arg, args.next())?)?),
+                "--dump-local" => dump_local = true,
+                "--send-file" => send_file = Some(PathBuf::from(value(&arg, args.next())?)),
+                "--send-lines" => send_lines = Some(PathBuf::from(value(&arg, args.next())?)),
+                "--line-terminator" => line_terminator = terminator(&value(&arg, args.next())?)?,
+                "--line-delay" => line_delay = Duration::from_millis(number(&arg, args.next())?),
+                "--skip-comments" => skip_comments = true,
+                "--send-env" => send_env = Some(value(&arg, args.next())?),
+                "--validate" => validate = Some(value(&arg, args.next())?),
+                "--open" => open = Some(PathBuf::from(value(&arg, args.next())?)),
+                "--send-framing" => send_framing = Framing::parse(&value(&arg, args.next())?)?,
+                "--no-flush" => flush = false,
+                "--strict" => strict = true,
+                "--alert" => alert = Some(Alert::parse(&value(&arg, args.next())?)?),
+                "--filter-out" => filter_out = Some(Filter::parse(&value(&arg, args.next())?)?),
+                "--filter-in" => filter_in = Some(Filter::parse(&value(&arg, args.next())?)?),
+                "--recv-framing" => recv_framing = Framing::parse(&value(&arg, args.next())?)?,
+                "--max-frame" => max_frame = number(&arg, args.next())?,
+                "--strip-header" => strip_header = number(&arg, args.next())?,
+                "--strip-footer" => strip_footer = number(&arg, args.next())?,
+                "--fixed-width" => fixed_width = true,
+                "--row-bytes" => row_bytes = at_least(1, &arg, args.next())?,
+                "--row-bytes-local" => local_columns = Some(at_least(1, &arg, args.next())?),
+                "--row-bytes-remote" => remote_columns = Some(at_least(1, &arg, args.next())?),
+                "--columns" => columns = Some(number(&arg, args.next())?),
+                "--newest-first" => newest_first = true,
+                "--title-height" => title_height = at_least(TITLE_HEIGHT, &arg, args.next())?,
+                "--input-height" => input_height = at_least(INPUT_HEIGHT, &arg, args.next())?,
+                "--no-placeholder" => placeholder = false,
+                "--separator" => separator = glyph(&arg, args.next())?,
+                "--compact-labels" => compact_labels = true,
+                "--unprintable" => unprintable = glyph(&arg, args.next())?,
+                "--hide-empty" => hide_empty = true,
+                "--diff" => diff = true,
+                "--controls" => controls = Controls::parse(&value(&arg, args.next())?)?,
+                "--wrap-marker" => wrap_marker = value(&arg, args.next())?,
+                "--pipe" => pipe = true,
+                "--echo-server" => echo_server = true,
+                "--json" => json = true,
+                "--json-input" => json_input = JsonInput::parse(&value(&arg, args.next())?)?,
+                "--half-close-on-eof" => half_close_on_eof = true,
+                "--expect-bytes" => expect_bytes = Some(number(&arg, args.next())?),
+                "--oneline" => oneline = true,
+                "--oneline-interval" => {
+                    oneline_interval = Duration::from_millis(number(&arg, args.next())?)
+                }
+                "--timeout" => timeout = Duration::from_millis(number(&arg, args.next())?),
+                "--exit-on-close" => exit_on_close = true,
+                "--monitor" => monitor = true,
+                "--keep-without-input" => keep_without_input = true,
+                "--idle-timeout" => {
+                    idle_timeout = Some(Duration::from_secs(number(&arg, args.next())?))
+                }
+                "--pause-unfocused" => pause_unfocused = true,
+                "--mouse" => mouse = true,
+                "--sparkline" => sparkline = true,
+                "--highlight" => highlight = Duration::from_millis(number(&arg, args.next())?),
+                "--throttle" => throttle = Some(Duration::from_millis(number(&arg, args.next())?)),
+                "--context" => context = number(&arg, args.next())?,
+                "--radix" => radix = Radix::parse(&value(&arg, args.next())?)?,
+                "--detect-input" => {
+                    detect_input = Some(Guess::parse_order(&value(&arg, args.next())?)?)
+                }
+                "--keep-trailing-space" => keep_trailing_space = true,
+                "--space-delimits" => space_delimits = true,
+                "--group-input" => group_input = true,
+                "--explicit-send" => explicit_send = true,
+                "--large-input" => large_input = at_least(1, &arg, args.next())?,
+                "--confirm-large" => confirm_large = true,
+                "--checksum" => {
+                    encoding.checksum = Some(Checksum::parse(&value(&arg, args.next())?)?)
+                }
+                "--recv-checksum" => {
+                    recv_checksum = Some(Checksum::parse(&value(&arg, args.next())?)?);
+                    verify_checksum = false;
+                }
+                "--verify-checksum" => {
+                    recv_checksum = Some(Checksum::parse(&value(&arg, args.next())?)?);
+                    verify_checksum = true;
+                }
+                "--length-prefix" => {
+                    encoding.length_prefix = Some(LengthPrefix::parse(&value(&arg, args.next())?)?)
+                }
+                "--pad" => encoding.padding = Some(Padding::parse(&value(&arg, args.next())?)?),
+                "--nul-terminate" => encoding.nul_terminate = true,
+                // Everything after it is one message, however many arguments it's been split into.
+                "--" => send_args = Some(args.by_ref().collect::<Vec<_>>().join(" ")),
+                flag if flag.starts_with("--") => Err(InitError::InvalidArguments)
+                    .into_report()
+                    .attach_printable(format!("Unknown option \"{flag}\"."))?,
+                _ => positional.push(arg),
+            }
+        }
+
+        // Neither reviewing a saved session, validating input nor asking for the version needs anywhere to connect to.
+        let nowhere = (version || open.is_some() || validate.is_some())
+            && positional.is_empty()
+            && fd.is_none()
+            && connecting.is_none();
+        if nowhere {
+            positional = vec![Ipv4Addr::UNSPECIFIED.to_string(), "0".to_string()];
+        }
+        let mut unix = false;
+        let target = match (connecting, fd) {
+            (Some(target), _) => target,
+            // Reconnecting goes back to wherever the passed down connection went.
+            (None, Some(fd)) if positional.is_empty() => match connection::inherited_peer(fd)? {
+                Some(peer) => peer,
+                None => {
+                    unix = true;
+                    SocketAddr::from((Ipv4Addr::UNSPECIFIED, 0))
+                }
+            },
+            (None, Some(_)) => Err(InitError::InvalidArguments)
+                .into_report()
+                .attach_printable(
+                    "A connection passed down with \"--fd\" already has somewhere it goes.",
+                )?,
+            (None, None) => target(&positional)?,
+        };
+        if let (Some(family), true) = (family, unix) {
+            Err(InitError::InvalidConnectionSettings)
+                .into_report()
+                .attach_printable(format!(
+                "Option \"-{family}\" was given, but the connection passed down is a Unix socket."
+            ))?;
+        }
+
+        if monitor
+            && (send_file.is_some()
+                || send_lines.is_some()
+                || send_env.is_some()
+                || send_args.is_some())
+        {
+            Err(InitError::InvalidArguments)
+                .into_report()
+                .attach_printable("Nothing can be sent while monitoring (\"--monitor\").")?;
+        }
+
+        let proxy = match proxy_addr {
+            Some(_) if fd.is_some() => Err(InitError::InvalidArguments)
+                .into_report()
+                .attach_printable(
+                    "A connection passed down with \"--fd\" is already made, so it can't go through a SOCKS5 proxy \
+                     (\"--socks5\").",
+                )?,
+            Some(addr) => Some(Socks5 {
+                addr: proxy(&addr)?,
+                credentials: credentials(proxy_username, proxy_password)?,
+            }),
+            None if proxy_username.is_some() || proxy_password.is_some() => {
+                Err(InitError::InvalidArguments)
+                    .into_report()
+                    .attach_printable(
+                        "A SOCKS5 username or password needs a proxy (\"--socks5\").",
+                    )?
+            }
+            None => None,
+        };
+
+        let config = Self {
+            version,
+            hosts,
+            macros,
+            args: Vec::new(),
+            target,
+            fd,
+            unix,
+            family,
+            proxy,
+            theme,
+            dump,
+            dump_local,
+            send_file,
+            send_lines,
+            line_terminator,
+            line_delay,
+            skip_comments,
+            send_env,
+            send_args,
+            open,
+            validate,
+            send_framing,
+            encoding,
+            recv_checksum,
+            verify_checksum,
+            recv_framing,
+            max_frame,
+            strip_header,
+            strip_footer,
+            flush,
+            strict,
+            alert,
+            filter_out,
+            filter_in,
+            radix,
+            detect_input,
+            keep_trailing_space,
+            space_delimits,
+            group_input,
+            explicit_send,
+            large_input,
+            confirm_large,
+            context,
+            fixed_width,
+            local_columns: local_columns.unwrap_or(row_bytes),
+            remote_columns: remote_columns.unwrap_or(row_bytes),
+            columns,
+            newest_first,
+            title_height,
+            input_height,
+            wrap_marker,
+            separator,
+            compact_labels,
+            unprintable,
+            controls,
+            hide_empty,
+            diff,
+            placeholder,
+            highlight,
+            throttle,
+            sparkline,
+            pause_unfocused,
+            mouse,
+            pipe,
+            echo_server,
+            json,
+            json_input,
+            half_close_on_eof,
+            expect_bytes,
+            oneline: oneline.then_some(oneline_interval),
+            timeout,
+            exit_on_close,
+            monitor,
+            keep_without_input,
+            idle_timeout,
+        };
+        if !nowhere && !unix {
+            config.check_family(target)?;
+        }
+        Ok(config)
+    }
+
+    pub(crate) fn check_family(&self, target: SocketAddr) -> Result<(), InitError> {
+        match self.family {
+            Some(family) if (family == 4) != target.is_ipv4() => {
+                Err(InitError::InvalidConnectionSettings)
+                    .into_report()
+                    .attach_printable(format!(
+                        "Option \"-{family}\" was given, but {} isn't an IPv{family} address.",
+                        target.ip()
+                    ))
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+fn value(flag: &str, value: Option<String>) -> Result<String, InitError> {
+    value
+        .ok_or(InitError::InvalidArguments)
+        .into_report()
+        .attach_printable(format!("Option \"{flag}\" requires a value."))
+}
+
+fn number<T: FromStr>(flag: &str, value_arg: Option<String>) -> Result<T, InitError>
+where
+    T::Err: std::error::Error + Send + Sync + 'static,
+{
+    let raw = value(flag, value_arg)?;
+    raw.parse::<T>()
+        .into_report()
+        .attach_printable(format!(
+            "Option \"{flag}\" expects a number, got \"{raw}\"."
+        ))
+        .change_context(InitError::InvalidArguments)
+}
+
+fn at_least(minimum: usize, flag: &str, value_arg: Option<String>) -> Result<usize, InitError> {
+    let number = number(flag, value_arg)?;
+    if number < minimum {
+        Err(InitError::InvalidArguments)
+            .into_report()
+            .attach_printable(format!(
+                "Option \"{flag}\" can't be less than {minimum}, got {number}."
+            ))?;
+    }
+    Ok(number)
+}
+
+fn target(positional: &[String]) -> Result<SocketAddr, InitError> {
+    if positional.len() < 2 {
+        Err(InitError::NotEnoughArguments)
+            .into_report()
+            .attach_printable("You must supply at least 2 arguments (IP Address and Port).")?;
+    }
+    let port: u16 = positional[1]
+        .parse()
+        .into_report()
+        .attach_printable("Invalid port number.")
+        .change_context(InitError::InvalidConnectionSettings)?;
+    connection::parse_addr(&positional[0], port).map_err(|reason| {
+        Report::new(InitError::InvalidConnectionSettings).attach_printable(reason)
+    })
+}
+
+fn terminator(value: &str) -> Result<TcpMessage, InitError> {
+    radix::parse_hex(&value.chars().collect::<Vec<_>>())
+        .ok_or(InitError::InvalidArguments)
+        .into_report()
+        .attach_printable(format!("Invalid hex line terminator \"{value}\"."))
+}
+
+fn proxy(value: &str) -> Result<SocketAddr, InitError> {
+    let invalid =
+        |reason: String| Report::new(InitError::InvalidConnectionSettings).attach_printable(reason);
+    let (addr, port) = value.rsplit_once(':').ok_or_else(|| {
+        invalid(format!(
+            "Invalid SOCKS5 proxy \"{value}\" (use \"address:port\")."
+        ))
+    })?;
+    let port = port
+        .parse::<u16>()
+        .map_err(|_| invalid(format!("Invalid SOCKS5 proxy port \"{port}\".")))?;
+    let addr = addr
+        .strip_prefix('[')
+        .and_then(|addr| addr.strip_suffix(']'))
+        .unwrap_or(addr);
+    connection::parse_addr(addr, port).map_err(invalid)
+}
+
+// Anyone can see a process's arguments (in `ps`, say), so the password is read from the environment instead.
+fn password_env(name: &str) -> Result<String, InitError> {
+    env::var(name).map_err(|error| {
+        Report::new(InitError::InvalidArguments).attach_printable(match error {
+            env::VarError::NotPresent => {
+                format!("Environment variable \"{name}\" (the SOCKS5 password) is not set.")
+            }
+            env::VarError::NotUnicode(_) => {
+                format!("Environment variable \"{name}\" (the SOCKS5 password) isn't valid text.")
+            }
+        })
+    })
+}
+
+fn credentials(
+    username: Option<String>,
+    password: Option<String>,
+) -> Result<Option<(String, String)>, InitError> {
+    match (username, password) {
+        (Some(username), password) => {
+            let password = password.unwrap_or_default();
+            if username.len() > 255 || password.len() > 255 {
+                Err(InitError::InvalidArguments)
+                    .into_report()
+                    .attach_printable(
+                        "A SOCKS5 username or password can't be longer than 255 bytes.",
+                    )?;
+            }
+            Ok(Some((username, password)))
+        }
+        (None, Some(_)) => Err(InitError::InvalidArguments)
+            .into_report()
+            .attach_printable("A SOCKS5 password needs a username (\"--socks5-username\")."),
+        (None, None) => Ok(None),
+    }
+}
+
+// Everything lines up by counting characters, so anything but a single printable one would throw the layout off.
+fn glyph(flag: &str, value_arg: Option<String>) -> Result<char, InitError> {
+    let raw = value(flag, value_arg)?;
+    let mut chars = raw.chars();
+    match (chars.next(), chars.next()) {
+        (Some(c), None) if !c.is_control() => Ok(c),
+        _ => Err(InitError::InvalidArguments)
+            .into_report()
+            .attach_printable(format!(
+                "Option \"{flag}\" expects a single character, got \"{raw}\"."
+            )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn connecting_elsewhere_uses_its_host_settings() {
+        let dir = env::temp_dir().join(format!("hexcat-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let hosts = dir.join("connecting");
+        fs::write(&hosts, "192.0.2.7 --radix bin --newest-first\n").unwrap();
+        let args = [
+            "--hosts",
+            hosts.to_str().unwrap(),
+            "--fixed-width",
+            "192.0.2.1",
+            "80",
+        ]
+        .map(str::to_string);
+
+        let config = Config::for_target(&args, "192.0.2.7:81".parse().unwrap()).unwrap();
+        assert_eq!(config.target, "192.0.2.7:81".parse().unwrap());
+        assert!(config.radix == Radix::Binary && config.newest_first && config.fixed_width);
+        // Going back takes them away again, but never what was on the command line.
+        let config = Config::for_target(&args, "192.0.2.1:80".parse().unwrap()).unwrap();
+        assert!(config.radix == Radix::Hex && !config.newest_first && config.fixed_width);
+    }
+
+    #[test]
+    fn host_settings_are_only_read_to_connect() {
+        let dir = env::temp_dir().join(format!("hexcat-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let hosts = dir.join("unread");
+        fs::write(&hosts, "not-a-host --radix bin\n").unwrap();
+        let with_saved = |rest: &[&str]| {
+            let args = ["--hosts", hosts.to_str().unwrap()]
+                .iter()
+                .chain(rest)
+                .map(|arg| arg.to_string())
+                .collect::<Vec<_>>();
+            Config::with_saved(Config::from_args(args.clone()).unwrap(), args, None)
+        };
+        assert!(with_saved(&["--version"]).is_ok());
+        assert!(with_saved(&["--validate", "00 ff"]).is_ok());
+        assert!(with_saved(&["--open", "capture.log"]).is_ok());
+        assert!(with_saved(&["192.0.2.1", "80"]).is_err());
+    }
+
+    #[test]
+    fn connecting_elsewhere_keeps_to_the_family() {
+        let args = ["-6", "::1", "80"].map(str::to_string);
+        let config = Config::for_target(&args, "[::2]:81".parse().unwrap()).unwrap();
+        assert_eq!(config.family, Some(6));
+        assert!(config.check_family("[::3]:80".parse().unwrap()).is_ok());
+        assert!(config
+            .check_family("192.0.2.1:80".parse().unwrap())
+            .is_err());
+        assert!(Config::for_target(&args, "192.0.2.1:80".parse().unwrap()).is_err());
+    }
+
+    #[test]
+    fn detection_order_has_no_empty_entries() {
+        let detect = |order: &str| {
+            Config::from_args(["--detect-input", order, "192.0.2.1", "80"].map(str::to_string))
+                .map(|config| config.detect_input.map(|order| order.len()))
+        };
+        assert_eq!(detect("hex,dec,text").unwrap(), Some(3));
+        for order in ["", "hex,", ",dec", "hex,,text"] {
+            assert!(detect(order).is_err(), "{order:?}");
+        }
+    }
+}
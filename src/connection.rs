@@ -0,0 +1,529 @@
+use crate::error::InitError;
+use crate::socks::Socks5;
+use crate::{TcpMessage, BUFFER_SIZE};
+use error_stack::{IntoReport, Report, Result, ResultExt};
+use std::ffi::CString;
+use std::io::{self, ErrorKind, Read, Write};
+use std::mem::{self, ManuallyDrop};
+use std::net::{IpAddr, Ipv6Addr, Shutdown, SocketAddr, SocketAddrV6, TcpListener, TcpStream};
+use std::os::fd::{FromRawFd, RawFd};
+use std::os::unix::net::UnixStream;
+use std::thread;
+use std::time::Duration;
+
+// How long a write waits for a connection that isn't taking any more before giving up for now. Whatever didn't go out
+// stays queued (see `Session::retry`), so a peer that stops reading never holds everything else up for longer.
+const WRITE_TIMEOUT: Duration = Duration::from_millis(10);
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+
+// Kept behind a trait so the message buffer doesn't need a real socket to work with.
+pub trait Transport: Write {
+    fn shutdown(&self);
+
+    fn shutdown_write(&self) {}
+}
+impl Transport for TcpStream {
+    fn shutdown(&self) {
+        _ = TcpStream::shutdown(self, Shutdown::Both);
+    }
+
+    fn shutdown_write(&self) {
+        _ = TcpStream::shutdown(self, Shutdown::Write);
+    }
+}
+
+pub enum Stream {
+    Tcp(TcpStream),
+    Unix(UnixStream),
+}
+impl Stream {
+    pub fn try_clone(&self) -> io::Result<Self> {
+        match self {
+            Self::Tcp(stream) => stream.try_clone().map(Self::Tcp),
+            Self::Unix(stream) => stream.try_clone().map(Self::Unix),
+        }
+    }
+}
+impl From<TcpStream> for Stream {
+    fn from(stream: TcpStream) -> Self {
+        Self::Tcp(stream)
+    }
+}
+impl Read for Stream {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match self {
+            Self::Tcp(stream) => stream.read(buf),
+            Self::Unix(stream) => stream.read(buf),
+        }
+    }
+}
+impl Write for Stream {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            Self::Tcp(stream) => stream.write(buf),
+            Self::Unix(stream) => stream.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            Self::Tcp(stream) => stream.flush(),
+            Self::Unix(stream) => stream.flush(),
+        }
+    }
+}
+impl Transport for Stream {
+    fn shutdown(&self) {
+        _ = match self {
+            Self::Tcp(stream) => stream.shutdown(Shutdown::Both),
+            Self::Unix(stream) => stream.shutdown(Shutdown::Both),
+        };
+    }
+
+    fn shutdown_write(&self) {
+        _ = match self {
+            Self::Tcp(stream) => stream.shutdown(Shutdown::Write),
+            Self::Unix(stream) => stream.shutdown(Shutdown::Write),
+        };
+    }
+}
+
+pub struct Offline;
+impl Write for Offline {
+    fn write(&mut self, _: &[u8]) -> io::Result<usize> {
+        Err(io::Error::new(ErrorKind::NotConnected, "not connected"))
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+impl Transport for Offline {
+    fn shutdown(&self) {}
+}
+
+// `IpAddr` has no room for a zone (like "fe80::1%eth0"), so it's resolved to the interface's scope id here instead.
+pub fn parse_addr(addr: &str, port: u16) -> std::result::Result<SocketAddr, String> {
+    let Some((ip, zone)) = addr.split_once('%') else {
+        return addr
+            .parse::<IpAddr>()
+            .map(|ip| SocketAddr::new(ip, port))
+            .map_err(|_| format!("Invalid IP address \"{addr}\"."));
+    };
+    let ip = ip
+        .parse::<Ipv6Addr>()
+        .map_err(|_| format!("Invalid IPv6 address \"{ip}\" (only IPv6 addresses have zones)."))?;
+    let scope_id = match zone.parse::<u32>() {
+        Ok(index) => index,
+        Err(_) => interface_index(zone)
+            .ok_or_else(|| format!("Unknown network interface \"{zone}\" in \"{addr}\"."))?,
+    };
+    Ok(SocketAddr::V6(SocketAddrV6::new(ip, port, 0, scope_id)))
+}
+
+fn interface_index(name: &str) -> Option<u32> {
+    let name = CString::new(name).ok()?;
+    // SAFETY: `name` is a valid, NUL-terminated string that outlives the call.
+    let index = unsafe { libc::if_nametoindex(name.as_ptr()) };
+    (index != 0).then_some(index)
+}
+
+pub fn connect(
+    target: SocketAddr,
+    nodelay: bool,
+    proxy: Option<&Socks5>,
+) -> Result<TcpStream, InitError> {
+    let stream = match proxy {
+        Some(proxy) => {
+            let mut stream =
+                open(proxy.addr).attach_printable("Could not reach the SOCKS5 proxy.")?;
+            set_timeouts(&stream, Some(HANDSHAKE_TIMEOUT))?;
+            proxy
+                .handshake(&mut stream, target)
+                .map_err(|error| match error.kind() {
+                    // Timed out reads and writes are reported as if they'd have blocked.
+                    ErrorKind::WouldBlock => io::Error::other(format!(
+                        "the SOCKS5 proxy didn't answer within {}s",
+                        HANDSHAKE_TIMEOUT.as_secs()
+                    )),
+                    _ => error,
+                })
+                .map_err(|error| failed(error, target))
+                .attach_printable(format!(
+                    "Could not connect through the SOCKS5 proxy (using {} on port {}).",
+                    proxy.addr.ip(),
+                    proxy.addr.port()
+                ))?;
+            set_timeouts(&stream, None)?;
+            stream
+        }
+        None => open(target)?,
+    };
+    configure(&stream, nodelay)?;
+    Ok(stream)
+}
+
+fn set_timeouts(stream: &TcpStream, timeout: Option<Duration>) -> Result<(), InitError> {
+    stream
+        .set_read_timeout(timeout)
+        .and_then(|()| stream.set_write_timeout(timeout))
+        .into_report()
+        .attach_printable("Could not configure the connection's timeouts.")
+        .change_context(InitError::CouldNotConnect)
+}
+
+fn configure(stream: &TcpStream, nodelay: bool) -> Result<(), InitError> {
+    // Flushing a `TcpStream` doesn't do anything by itself; it's Nagle's algorithm that holds small writes back.
+    stream
+        .set_nodelay(nodelay)
+        .into_report()
+        .attach_printable("Could not configure write buffering on the connection.")
+        .change_context(InitError::CouldNotConnect)?;
+    // Not non-blocking: the listener reads from the same socket, and should keep waiting for as long as it takes.
+    stream
+        .set_write_timeout(Some(WRITE_TIMEOUT))
+        .into_report()
+        .attach_printable("Could not configure the connection's write timeout.")
+        .change_context(InitError::CouldNotConnect)
+}
+
+pub fn inherit(fd: RawFd, nodelay: bool) -> Result<Stream, InitError> {
+    let peer = inherited_peer(fd)?;
+    // Filters are run as commands of their own, and they've no business holding the connection open.
+    // SAFETY: the descriptor was just checked to be open, and only its flags are changed.
+    let flags = unsafe { libc::fcntl(fd, libc::F_GETFD) };
+    if flags == -1 || unsafe { libc::fcntl(fd, libc::F_SETFD, flags | libc::FD_CLOEXEC) } == -1 {
+        return Err(io::Error::last_os_error())
+            .into_report()
+            .attach_printable(format!(
+                "Could not keep file descriptor {fd} from being inherited."
+            ))
+            .change_context(InitError::InvalidConnectionSettings);
+    }
+    // SAFETY: the descriptor was just checked to be an open, connected socket of the kind it's taken as, and it was
+    // handed down to be used.
+    match peer {
+        Some(_) => {
+            let stream = unsafe { TcpStream::from_raw_fd(fd) };
+            configure(&stream, nodelay)?;
+            Ok(Stream::Tcp(stream))
+        }
+        // There's no Nagle's algorithm to turn off, but writes still mustn't block for long.
+        None => {
+            let stream = unsafe { UnixStream::from_raw_fd(fd) };
+            stream
+                .set_write_timeout(Some(WRITE_TIMEOUT))
+                .into_report()
+                .attach_printable("Could not configure the connection's write timeout.")
+                .change_context(InitError::CouldNotConnect)?;
+            Ok(Stream::Unix(stream))
+        }
+    }
+}
+
+pub fn inherited_peer(fd: RawFd) -> Result<Option<SocketAddr>, InitError> {
+    let invalid = |reason: String| {
+        Report::new(InitError::InvalidConnectionSettings)
+            .attach_printable(format!("File descriptor {fd} {reason}."))
+    };
+    let mut kind: libc::c_int = 0;
+    let mut length = mem::size_of::<libc::c_int>() as libc::socklen_t;
+    // SAFETY: `kind` and `length` are valid to write to, and `length` is the size of `kind`.
+    let result = unsafe {
+        libc::getsockopt(
+            fd,
+            libc::SOL_SOCKET,
+            libc::SO_TYPE,
+            (&mut kind as *mut libc::c_int).cast(),
+            &mut length,
+        )
+    };
+    if result != 0 {
+        let error = io::Error::last_os_error();
+        return Err(invalid(match error.raw_os_error() {
+            Some(libc::EBADF) => "isn't open".to_string(),
+            Some(libc::ENOTSOCK) => "isn't a socket".to_string(),
+            _ => format!("couldn't be looked at ({error})"),
+        }));
+    }
+    if kind != libc::SOCK_STREAM {
+        return Err(invalid("isn't a stream socket".to_string()));
+    }
+    let not_connected = |error: io::Error, otherwise: &str| {
+        invalid(match error.kind() {
+            ErrorKind::NotConnected => "isn't connected (a listening socket won't do)".to_string(),
+            _ => otherwise.to_string(),
+        })
+    };
+    // Only borrowed to ask, so the descriptor isn't closed afterwards.
+    // SAFETY: the descriptor is an open socket, and it's never closed (or used for anything else) through this.
+    if is_unix(fd) {
+        let stream = ManuallyDrop::new(unsafe { UnixStream::from_raw_fd(fd) });
+        return match stream.peer_addr() {
+            Ok(_) => Ok(None),
+            Err(error) => Err(not_connected(error, "isn't a Unix socket connection")),
+        };
+    }
+    let stream = ManuallyDrop::new(unsafe { TcpStream::from_raw_fd(fd) });
+    match stream.peer_addr() {
+        Ok(peer) => Ok(Some(peer)),
+        Err(error) => Err(not_connected(error, "isn't a TCP connection")),
+    }
+}
+
+fn is_unix(fd: RawFd) -> bool {
+    // SAFETY: an all-zero `sockaddr_storage` is valid, and it's only ever written to by `getsockname`.
+    let mut addr: libc::sockaddr_storage = unsafe { mem::zeroed() };
+    let mut length = mem::size_of::<libc::sockaddr_storage>() as libc::socklen_t;
+    // SAFETY: `addr` and `length` are valid to write to, and `length` is the size of `addr`.
+    let result = unsafe {
+        libc::getsockname(
+            fd,
+            (&mut addr as *mut libc::sockaddr_storage).cast(),
+            &mut length,
+        )
+    };
+    result == 0 && libc::c_int::from(addr.ss_family) == libc::AF_UNIX
+}
+
+fn open(addr: SocketAddr) -> Result<TcpStream, InitError> {
+    TcpStream::connect(addr).map_err(|error| failed(error, addr))
+}
+
+fn failed(error: io::Error, addr: SocketAddr) -> Report<InitError> {
+    let reason = describe_connect_error(&error, addr);
+    Report::new(error)
+        .attach(addr)
+        .attach_printable(format!(
+            "Could not connect to remote server (using {} on port {}).",
+            addr.ip(),
+            addr.port()
+        ))
+        .attach_printable(reason)
+        .change_context(InitError::CouldNotConnect)
+}
+
+pub fn bind(addr: SocketAddr) -> Result<TcpListener, InitError> {
+    TcpListener::bind(addr).map_err(|error| {
+        let reason = describe_bind_error(&error, addr);
+        Report::new(error)
+            .attach_printable(format!(
+                "Could not listen on {} port {}.",
+                addr.ip(),
+                addr.port()
+            ))
+            .attach_printable(reason)
+            .change_context(InitError::CouldNotListen)
+    })
+}
+
+pub fn describe_bind_error(error: &io::Error, addr: SocketAddr) -> String {
+    let (ip, port) = (addr.ip(), addr.port());
+    match error.kind() {
+        ErrorKind::AddrInUse => {
+            format!("Port {port} is already in use: something else is listening on it at {ip}.")
+        }
+        ErrorKind::AddrNotAvailable => format!("{ip} isn't an address this machine has."),
+        ErrorKind::PermissionDenied => {
+            format!("Permission denied: port {port} needs more privileges to listen on.")
+        }
+        _ => format!("Listening failed: {error}."),
+    }
+}
+
+pub fn is_gone(error: &io::Error) -> bool {
+    matches!(
+        error.kind(),
+        ErrorKind::BrokenPipe
+            | ErrorKind::ConnectionReset
+            | ErrorKind::ConnectionAborted
+            | ErrorKind::NotConnected
+    )
+}
+
+pub fn describe_connect_error(error: &io::Error, target: SocketAddr) -> String {
+    let (addr, port) = (target.ip(), target.port());
+    match error.kind() {
+        ErrorKind::ConnectionRefused => {
+            format!("Connection refused: nothing is listening on port {port} at {addr}.")
+        }
+        // These two only got error kinds of their own after the oldest Rust that HexCat builds with.
+        _ if error.raw_os_error() == Some(libc::EHOSTUNREACH) => {
+            format!("Host unreachable: no route to {addr}.")
+        }
+        _ if error.raw_os_error() == Some(libc::ENETUNREACH) => {
+            format!("Network unreachable: {addr} is not on a network this machine can reach.")
+        }
+        ErrorKind::TimedOut => format!(
+            "Timed out: {addr} did not respond (it may be down, or a firewall is dropping packets)."
+        ),
+        ErrorKind::PermissionDenied => {
+            "Permission denied: the operating system or a firewall refused the connection."
+                .to_string()
+        }
+        _ => format!("Connection failed: {error}."),
+    }
+}
+
+pub enum Incoming {
+    Data(TcpMessage),
+    Closed,
+    Reset,
+}
+
+pub fn spawn_listener(
+    connection: &Stream,
+    on_read: impl FnMut(Incoming) + Send + 'static,
+) -> Result<(), InitError> {
+    let thread_connection = connection
+        .try_clone()
+        .into_report()
+        .attach_printable("Could not clone connection for use in TCP thread.")
+        .change_context(InitError::Threads)?;
+    thread::spawn(move || listen(thread_connection, on_read));
+    Ok(())
+}
+
+fn listen(mut connection: Stream, mut on_read: impl FnMut(Incoming)) {
+    let mut buffer = [0u8; BUFFER_SIZE];
+    let end = 'connected: loop {
+        match connection.read(&mut buffer) {
+            Ok(0) => break 'connected Incoming::Closed,
+            Ok(n) => on_read(Incoming::Data(buffer[..n].to_vec())),
+            Err(ref err) if err.kind() == ErrorKind::WouldBlock => (),
+            Err(_) => break 'connected Incoming::Reset,
+        }
+    };
+    on_read(end);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs::File;
+    use std::net::{TcpListener, UdpSocket};
+    use std::os::fd::{AsRawFd, IntoRawFd};
+
+    fn cloexec(fd: RawFd) -> bool {
+        // SAFETY: only the flags of a descriptor the test owns are looked at.
+        unsafe { libc::fcntl(fd, libc::F_GETFD) & libc::FD_CLOEXEC != 0 }
+    }
+
+    fn rejected(fd: RawFd) -> String {
+        let report = inherited_peer(fd).unwrap_err();
+        report
+            .frames()
+            .find_map(|frame| frame.downcast_ref::<String>())
+            .unwrap()
+            .clone()
+    }
+
+    #[test]
+    fn tcp_connections_are_inherited() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let stream = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+        let (mut peer, _) = listener.accept().unwrap();
+        let fd = stream.into_raw_fd();
+        // SAFETY: the test owns the descriptor; this is only setting it up the way a parent process might hand it down.
+        unsafe { libc::fcntl(fd, libc::F_SETFD, 0) };
+        assert!(!cloexec(fd));
+
+        assert_eq!(
+            inherited_peer(fd).unwrap(),
+            Some(listener.local_addr().unwrap())
+        );
+        let mut stream = inherit(fd, true).unwrap();
+        assert!(matches!(stream, Stream::Tcp(_)));
+        assert!(cloexec(fd));
+        stream.write_all(b"hi").unwrap();
+        assert_eq!(read_array::<2>(&mut peer), *b"hi");
+    }
+
+    #[test]
+    fn unix_connections_are_inherited() {
+        let (stream, mut peer) = UnixStream::pair().unwrap();
+        let fd = stream.into_raw_fd();
+        assert_eq!(inherited_peer(fd).unwrap(), None);
+        let mut stream = inherit(fd, true).unwrap();
+        assert!(matches!(stream, Stream::Unix(_)));
+        assert!(cloexec(fd));
+        stream.write_all(b"hi").unwrap();
+        assert_eq!(read_array::<2>(&mut peer), *b"hi");
+        peer.write_all(b"yo").unwrap();
+        assert_eq!(read_array::<2>(&mut stream), *b"yo");
+    }
+
+    #[test]
+    fn other_descriptors_are_rejected() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        assert!(rejected(listener.as_raw_fd()).contains("isn't connected"));
+        let udp = UdpSocket::bind("127.0.0.1:0").unwrap();
+        assert!(rejected(udp.as_raw_fd()).contains("isn't a stream socket"));
+        let file = File::open("Cargo.toml").unwrap();
+        assert!(rejected(file.as_raw_fd()).contains("isn't a socket"));
+    }
+
+    #[test]
+    fn a_port_in_use_says_so() {
+        let taken = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = taken.local_addr().unwrap();
+        let report = bind(addr).unwrap_err();
+        let reason = report
+            .frames()
+            .find_map(|frame| frame.downcast_ref::<String>())
+            .unwrap();
+        assert_eq!(
+            reason,
+            &format!(
+                "Port {} is already in use: something else is listening on it at 127.0.0.1.",
+                addr.port()
+            )
+        );
+    }
+
+    #[test]
+    fn addresses_are_parsed_with_their_zone() {
+        assert_eq!(
+            parse_addr("127.0.0.1", 80),
+            Ok(SocketAddr::from(([127, 0, 0, 1], 80)))
+        );
+        assert_eq!(
+            parse_addr("::1", 80),
+            Ok(SocketAddr::from((Ipv6Addr::LOCALHOST, 80)))
+        );
+        let link_local = "fe80::1".parse().unwrap();
+        assert_eq!(
+            parse_addr("fe80::1%3", 80),
+            Ok(SocketAddr::V6(SocketAddrV6::new(link_local, 80, 0, 3)))
+        );
+        let loopback = interface_index("lo").unwrap();
+        assert_eq!(
+            parse_addr("fe80::1%lo", 80),
+            Ok(SocketAddr::V6(SocketAddrV6::new(
+                link_local, 80, 0, loopback
+            )))
+        );
+    }
+
+    #[test]
+    fn invalid_addresses_say_why() {
+        assert_eq!(
+            parse_addr("localhost", 80),
+            Err("Invalid IP address \"localhost\".".to_string())
+        );
+        assert_eq!(
+            parse_addr("10.0.0.1%eth0", 80),
+            Err("Invalid IPv6 address \"10.0.0.1\" (only IPv6 addresses have zones).".to_string())
+        );
+        assert_eq!(
+            parse_addr("fe80::1%nowhere0", 80),
+            Err("Unknown network interface \"nowhere0\" in \"fe80::1%nowhere0\".".to_string())
+        );
+    }
+
+    fn read_array<const N: usize>(stream: &mut impl Read) -> [u8; N] {
+        let mut buffer = [0; N];
+        stream.read_exact(&mut buffer).unwrap();
+        buffer
+    }
+}
@@ -0,0 +1,30 @@
+use crate::config::Config;
+use crate::connection;
+use crate::error::AppError;
+use error_stack::{Result, ResultExt};
+use std::io;
+use std::net::TcpStream;
+use std::thread;
+
+pub(crate) fn run(config: &Config) -> Result<(), AppError> {
+    let listener = connection::bind(config.target).change_context(AppError::InitError)?;
+    // With port 0, this is the only way to find out which one it got.
+    if let Ok(addr) = listener.local_addr() {
+        eprintln!("Echoing on {addr}.");
+    }
+    for connection in listener.incoming() {
+        let Ok(connection) = connection else {
+            continue;
+        };
+        thread::spawn(move || echo(connection));
+    }
+    Ok(())
+}
+
+fn echo(connection: TcpStream) {
+    let Ok(mut reader) = connection.try_clone() else {
+        return;
+    };
+    let mut writer = connection;
+    _ = io::copy(&mut reader, &mut writer);
+}
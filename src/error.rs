@@ -4,11 +4,17 @@ use thiserror::Error;
 #[derive(Debug, Error)]
 pub enum InitError {
     NotEnoughArguments,
+    InvalidArguments,
     InvalidConnectionSettings,
     CouldNotConnect,
+    CouldNotListen,
     NoTerminal,
     Window,
     Threads,
+    SendFile,
+    SendEnv,
+    SendArgs,
+    OpenSession,
 }
 impl Display for InitError {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
@@ -23,6 +29,8 @@ pub enum AppError {
     TerminalError,
     UserInput,
     StreamRead,
+    StreamWrite,
+    Output,
 }
 impl Display for AppError {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
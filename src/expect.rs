@@ -0,0 +1,53 @@
+use crate::config::{Config, DumpFormat};
+use crate::error::AppError;
+use crate::framing::Framing;
+use crate::Outcome;
+use error_stack::{Result, ResultExt};
+use std::thread;
+use std::time::{Duration, Instant};
+
+const POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+pub(crate) fn run(config: &Config, expected: usize) -> Result<Outcome, AppError> {
+    let mut session = crate::open_session(config, Framing::Whole, usize::MAX)
+        .attach_printable("Could not start waiting for bytes.")
+        .change_context(AppError::InitError)?;
+    if let Some(input) = &config.send_args {
+        match crate::parse_input(input, config).and_then(|bytes| crate::outgoing(bytes, config)) {
+            Ok(message) => session.send(&message)?,
+            Err(reason) => {
+                eprintln!("{reason}");
+                return Ok(Outcome::Failed);
+            }
+        }
+    }
+
+    let deadline = Instant::now() + config.timeout;
+    while session.received() < expected && !session.is_closed() && Instant::now() < deadline {
+        thread::sleep(POLL_INTERVAL);
+        session.retry()?;
+        session.poll();
+    }
+    // Whatever has arrived by now is still shown, once it has been filtered.
+    while session.is_filtering() {
+        thread::sleep(POLL_INTERVAL);
+        session.poll();
+    }
+    session.close();
+
+    let mut received = session.dump(false);
+    received.truncate(expected);
+    crate::dump(&received, config.dump.unwrap_or(DumpFormat::Hex))?;
+    if received.len() < expected {
+        let reason = match session.is_closed() {
+            true => "before the connection was closed",
+            false => "before timing out",
+        };
+        eprintln!(
+            "Expected {expected} bytes, but only {} arrived {reason}.",
+            received.len()
+        );
+        return Ok(Outcome::Incomplete);
+    }
+    Ok(Outcome::Success)
+}
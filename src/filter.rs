@@ -0,0 +1,137 @@
+use crate::error::InitError;
+use crate::TcpMessage;
+use error_stack::{IntoReport, Result, ResultExt};
+use std::io::{Read, Write};
+use std::os::unix::process::CommandExt;
+use std::process::{Command, Stdio};
+use std::sync::mpsc;
+use std::thread;
+use std::time::Duration;
+
+const TIMEOUT: Duration = Duration::from_secs(1);
+
+#[derive(Clone)]
+pub(crate) struct Filter {
+    command: String,
+}
+impl Filter {
+    pub(crate) fn parse(value: &str) -> Result<Self, InitError> {
+        if value.trim().is_empty() {
+            return Err(InitError::InvalidArguments)
+                .into_report()
+                .attach_printable("A filter needs a command to run.");
+        }
+        Ok(Self {
+            command: value.to_string(),
+        })
+    }
+
+    pub(crate) fn apply(&self, message: &[u8]) -> std::result::Result<TcpMessage, String> {
+        let failed = |reason: String| format!("Filter \"{}\" {reason}", self.command);
+        let mut child = Command::new("sh")
+            .arg("-c")
+            .arg(&self.command)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            // In a group of its own, so anything the shell starts can be stopped along with it.
+            .process_group(0)
+            .spawn()
+            .map_err(|error| failed(format!("could not be started: {error}.")))?;
+
+        // Written and read on other threads, so a filter that answers before reading everything can't deadlock on a
+        // full pipe, and one that never answers can be given up on.
+        let mut stdin = child.stdin.take().expect("stdin was piped");
+        let message = message.to_vec();
+        thread::spawn(move || stdin.write_all(&message));
+        let mut stdout = child.stdout.take().expect("stdout was piped");
+        let (sink, read) = mpsc::channel();
+        thread::spawn(move || {
+            let mut output = Vec::new();
+            _ = sink.send(stdout.read_to_end(&mut output).map(|_| output));
+        });
+        let Ok(read) = read.recv_timeout(TIMEOUT) else {
+            // SAFETY: only signals the filter's own process group, which it leads until `wait` reaps it.
+            unsafe { libc::kill(-(child.id() as libc::pid_t), libc::SIGKILL) };
+            _ = child.wait();
+            return Err(failed(format!(
+                "took longer than {}s, and was stopped.",
+                TIMEOUT.as_secs()
+            )));
+        };
+        // A filter doesn't have to read its input at all, so it stopping early isn't an error.
+        let status = child.wait();
+
+        match (read, status) {
+            (Err(error), _) | (_, Err(error)) => Err(failed(format!("failed: {error}."))),
+            (Ok(_), Ok(status)) if !status.success() => Err(failed(format!("failed ({status})."))),
+            (Ok(output), Ok(_)) => Ok(output),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Instant;
+    use std::{env, fs};
+
+    fn filter(command: &str) -> Filter {
+        Filter::parse(command).unwrap()
+    }
+
+    #[test]
+    fn messages_go_through_the_command() {
+        assert_eq!(
+            filter("cat").apply(b"\x00\xffhi"),
+            Ok(b"\x00\xffhi".to_vec())
+        );
+        assert_eq!(filter("tr a-z A-Z").apply(b"hello"), Ok(b"HELLO".to_vec()));
+        let large = vec![b'x'; 1 << 20];
+        assert_eq!(filter("cat").apply(&large), Ok(large));
+    }
+
+    #[test]
+    fn failing_filters_are_reported() {
+        assert!(Filter::parse("  ").is_err());
+        assert_eq!(
+            filter("exit 3").apply(b"hi"),
+            Err("Filter \"exit 3\" failed (exit status: 3).".to_string())
+        );
+        assert_eq!(filter("echo ok").apply(b"hi"), Ok(b"ok\n".to_vec()));
+    }
+
+    #[test]
+    fn slow_filters_are_stopped() {
+        let started = Instant::now();
+        let result = filter("sleep 10").apply(b"hi");
+        assert!(started.elapsed() < Duration::from_secs(5));
+        assert_eq!(
+            result,
+            Err("Filter \"sleep 10\" took longer than 1s, and was stopped.".to_string())
+        );
+    }
+
+    #[test]
+    fn whatever_the_filter_starts_is_stopped_with_it() {
+        let path = env::temp_dir().join(format!("hexcat-{}-filter", std::process::id()));
+        let command = format!("sleep 10 & echo $! > {}", path.display());
+        // The shell is long gone, but what it started still has its output open.
+        assert!(filter(&command).apply(b"hi").is_err());
+        let pid = fs::read_to_string(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        let running = || {
+            Command::new("kill")
+                .args(["-0", pid.trim()])
+                .stderr(Stdio::null())
+                .status()
+                .unwrap()
+                .success()
+        };
+        let started = Instant::now();
+        while running() {
+            assert!(started.elapsed() < Duration::from_secs(5));
+            thread::sleep(Duration::from_millis(10));
+        }
+    }
+}
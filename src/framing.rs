@@ -0,0 +1,273 @@
+use crate::checksum::Checksum;
+use crate::error::InitError;
+use crate::radix::parse_hex;
+use crate::TcpMessage;
+use error_stack::{IntoReport, Result, ResultExt};
+
+#[derive(Clone, Default, PartialEq, Eq)]
+pub enum Framing {
+    #[default]
+    Whole,
+    Size(usize),
+    Delimiter(Vec<u8>),
+    Length(LengthPrefix),
+}
+impl Framing {
+    pub fn parse(value: &str) -> Result<Self, InitError> {
+        if value == "whole" {
+            return Ok(Self::Whole);
+        }
+        if let Some(prefix) = value.strip_prefix("len:") {
+            return Ok(Self::Length(LengthPrefix::parse(prefix)?));
+        }
+        if let Some(hex) = value.strip_prefix("delim:") {
+            return match parse_hex(&hex.chars().collect::<Vec<_>>()) {
+                Some(delimiter) if !delimiter.is_empty() => Ok(Self::Delimiter(delimiter)),
+                _ => Err(InitError::InvalidArguments)
+                    .into_report()
+                    .attach_printable(format!("Invalid hex delimiter \"{hex}\".")),
+            };
+        }
+        match value.parse::<usize>() {
+            Ok(size) if size > 0 => Ok(Self::Size(size)),
+            _ => Err(InitError::InvalidArguments)
+                .into_report()
+                .attach_printable(format!(
+                    "Invalid framing \"{value}\" (use \"whole\", a chunk size, \"delim:<hex>\" or \"len:<1|2|4|8>:<be|le>\")."
+                )),
+        }
+    }
+
+    pub fn split(&self, bytes: &[u8]) -> Vec<TcpMessage> {
+        let (mut messages, consumed) = self.frames(bytes);
+        if consumed < bytes.len() {
+            messages.push(bytes[consumed..].to_vec());
+        }
+        messages
+    }
+
+    pub fn take_frames(&self, buffer: &mut Vec<u8>) -> Vec<TcpMessage> {
+        let (messages, consumed) = self.frames(buffer);
+        buffer.drain(..consumed);
+        messages
+    }
+
+    fn frames(&self, bytes: &[u8]) -> (Vec<TcpMessage>, usize) {
+        match self {
+            Self::Whole if bytes.is_empty() => (Vec::new(), 0),
+            Self::Whole => (vec![bytes.to_vec()], bytes.len()),
+            Self::Size(size) => {
+                let consumed = bytes.len() - bytes.len() % size;
+                let messages = bytes[..consumed]
+                    .chunks(*size)
+                    .map(<[u8]>::to_vec)
+                    .collect();
+                (messages, consumed)
+            }
+            Self::Delimiter(delimiter) => {
+                let mut messages = Vec::new();
+                let mut start = 0;
+                let mut index = 0;
+                while index + delimiter.len() <= bytes.len() {
+                    if bytes[index..].starts_with(delimiter) {
+                        index += delimiter.len();
+                        messages.push(bytes[start..index].to_vec());
+                        start = index;
+                    } else {
+                        index += 1;
+                    }
+                }
+                (messages, start)
+            }
+            Self::Length(prefix) => {
+                let mut messages = Vec::new();
+                let mut start = 0;
+                while let Some(length) = prefix.read(&bytes[start..]) {
+                    let end = usize::try_from(length)
+                        .ok()
+                        .and_then(|length| length.checked_add(start + prefix.width))
+                        .filter(|end| *end <= bytes.len());
+                    let Some(end) = end else {
+                        break;
+                    };
+                    messages.push(bytes[start..end].to_vec());
+                    start = end;
+                }
+                (messages, start)
+            }
+        }
+    }
+
+    pub fn header(&self, frame: &[u8]) -> Option<(usize, u64)> {
+        match self {
+            Self::Length(prefix) => Some((prefix.width, prefix.read(frame)?)),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub struct LengthPrefix {
+    width: usize,
+    big_endian: bool,
+}
+impl LengthPrefix {
+    pub fn parse(value: &str) -> Result<Self, InitError> {
+        let parsed = value.split_once(':').and_then(|(width, endian)| {
+            let width = width
+                .parse::<usize>()
+                .ok()
+                .filter(|width| [1, 2, 4, 8].contains(width))?;
+            let big_endian = match endian {
+                "be" => true,
+                "le" => false,
+                _ => return None,
+            };
+            Some(Self { width, big_endian })
+        });
+        parsed
+            .ok_or(InitError::InvalidArguments)
+            .into_report()
+            .attach_printable(format!(
+                "Invalid length prefix \"{value}\" (use <1|2|4|8>:<be|le>, like \"2:be\")."
+            ))
+    }
+
+    fn read(&self, bytes: &[u8]) -> Option<u64> {
+        let header = bytes.get(..self.width)?;
+        Some(header.iter().enumerate().fold(0, |length, (index, byte)| {
+            let shift = if self.big_endian {
+                self.width - 1 - index
+            } else {
+                index
+            };
+            length | u64::from(*byte) << (shift * 8)
+        }))
+    }
+
+    pub fn apply(&self, message: TcpMessage) -> std::result::Result<TcpMessage, String> {
+        let length = message.len() as u64;
+        if self.width < 8 && length >> (self.width * 8) != 0 {
+            return Err(format!(
+                "Not sent: {length} bytes don't fit in a {}-byte length prefix.",
+                self.width
+            ));
+        }
+        let mut framed = if self.big_endian {
+            length.to_be_bytes()[8 - self.width..].to_vec()
+        } else {
+            length.to_le_bytes()[..self.width].to_vec()
+        };
+        framed.extend(message);
+        Ok(framed)
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub struct Padding {
+    length: usize,
+    fill: u8,
+}
+impl Padding {
+    pub fn parse(value: &str) -> Result<Self, InitError> {
+        let parsed = value.split_once(':').and_then(|(length, fill)| {
+            let length = length.parse::<usize>().ok()?;
+            match parse_hex(&fill.chars().collect::<Vec<_>>())?[..] {
+                [fill] => Some(Self { length, fill }),
+                _ => None,
+            }
+        });
+        parsed
+            .ok_or(InitError::InvalidArguments)
+            .into_report()
+            .attach_printable(format!(
+                "Invalid padding \"{value}\" (use <length>:<hex byte>, like \"32:00\")."
+            ))
+    }
+
+    pub fn apply(&self, mut message: TcpMessage) -> std::result::Result<TcpMessage, String> {
+        if message.len() > self.length {
+            return Err(format!(
+                "Not sent: {} bytes don't fit in a record padded to {} bytes.",
+                message.len(),
+                self.length
+            ));
+        }
+        message.resize(self.length, self.fill);
+        Ok(message)
+    }
+}
+
+// A terminating NUL and any padding are part of the payload, so the checksum covers them.
+#[derive(Clone, Copy, Default)]
+pub struct Encoding {
+    pub nul_terminate: bool,
+    pub padding: Option<Padding>,
+    pub checksum: Option<Checksum>,
+    pub length_prefix: Option<LengthPrefix>,
+}
+impl Encoding {
+    pub fn encode(&self, mut message: TcpMessage) -> std::result::Result<TcpMessage, String> {
+        if self.nul_terminate {
+            message.push(0);
+        }
+        if let Some(padding) = self.padding {
+            message = padding.apply(message)?;
+        }
+        if let Some(checksum) = self.checksum {
+            message.extend(checksum.compute(&message));
+        }
+        match self.length_prefix {
+            Some(prefix) => prefix.apply(message),
+            None => Ok(message),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn padding_is_a_length_and_a_single_fill_byte() {
+        assert!(
+            Padding::parse("32:00").ok()
+                == Some(Padding {
+                    length: 32,
+                    fill: 0
+                })
+        );
+        assert!(
+            Padding::parse("4:ff").ok()
+                == Some(Padding {
+                    length: 4,
+                    fill: 0xff
+                })
+        );
+        for invalid in ["32", "32:", "32:0000", "32:zz", ":00", "-1:00"] {
+            assert!(Padding::parse(invalid).is_err(), "{invalid}");
+        }
+    }
+
+    #[test]
+    fn padding_fills_a_message_out_to_its_length() {
+        let padding = Padding::parse("4:ff").unwrap();
+        assert_eq!(padding.apply(vec![1, 2]), Ok(vec![1, 2, 0xff, 0xff]));
+        assert_eq!(padding.apply(vec![1, 2, 3, 4]), Ok(vec![1, 2, 3, 4]));
+        assert_eq!(
+            padding.apply(vec![1, 2, 3, 4, 5]),
+            Err("Not sent: 5 bytes don't fit in a record padded to 4 bytes.".to_string())
+        );
+    }
+
+    #[test]
+    fn padding_comes_after_the_nul_and_before_the_checksum() {
+        let encoding = Encoding {
+            nul_terminate: true,
+            padding: Some(Padding::parse("4:aa").unwrap()),
+            checksum: Some(Checksum::parse("sum8").unwrap()),
+            length_prefix: None,
+        };
+        assert_eq!(encoding.encode(vec![1]), Ok(vec![1, 0, 0xaa, 0xaa, 0x55]));
+    }
+}
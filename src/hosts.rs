@@ -0,0 +1,145 @@
+use crate::error::InitError;
+use error_stack::{IntoReport, Report, Result, ResultExt};
+use std::env;
+use std::fs;
+use std::io::ErrorKind;
+use std::net::{IpAddr, SocketAddr};
+use std::path::{Path, PathBuf};
+
+// Options to use for particular hosts, so they don't have to be given every time. Each line is a host followed by
+// options, the same as on the command line (but split on whitespace, so no value can have a space in it):
+//
+//     *              --fixed-width
+//     192.0.2.7      --radix bin
+//     [::1]:4000     --compact-labels --recv-framing delim:0a
+//
+// "*" is every host, an address alone is any port on it, and blank lines and lines starting with "#" are skipped.
+// Every line that matches is used, in order, so later (more specific) lines override earlier ones.
+pub(crate) fn options_for(path: &Path, target: SocketAddr) -> Result<Vec<String>, InitError> {
+    let contents = match fs::read_to_string(path) {
+        Ok(contents) => contents,
+        Err(error) if error.kind() == ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(error) => {
+            return Err(error)
+                .into_report()
+                .attach_printable(format!(
+                    "Could not read host settings ({}).",
+                    path.display()
+                ))
+                .change_context(InitError::InvalidArguments)
+        }
+    };
+
+    let mut options = Vec::new();
+    for (index, line) in contents.lines().enumerate() {
+        let invalid = |reason: String| {
+            Report::new(InitError::InvalidArguments).attach_printable(format!(
+                "{reason} ({} line {}).",
+                path.display(),
+                index + 1
+            ))
+        };
+        let mut words = line.split_whitespace();
+        let Some(host) = words.next().filter(|host| !host.starts_with('#')) else {
+            continue;
+        };
+        let matches = match host {
+            "*" => true,
+            host => match (host.parse::<SocketAddr>(), host.parse::<IpAddr>()) {
+                (Ok(addr), _) => addr == target,
+                (_, Ok(ip)) => ip == target.ip(),
+                _ => return Err(invalid(format!("Invalid host \"{host}\""))),
+            },
+        };
+        if !matches {
+            continue;
+        }
+        for word in words {
+            // Everything after it on the command line would be sent as a message.
+            if word == "--" {
+                return Err(invalid("Host settings can't end the options".to_string()));
+            }
+            options.push(word.to_string());
+        }
+    }
+    Ok(options)
+}
+
+pub(crate) fn default_path() -> Option<PathBuf> {
+    let config = env::var_os("XDG_CONFIG_HOME")
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|home| Path::new(&home).join(".config")))?;
+    Some(config.join("hexcat").join("hosts"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+    use hexcat::framing::Framing;
+
+    fn hosts(test: &str, contents: &str) -> PathBuf {
+        let dir = env::temp_dir().join(format!("hexcat-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join(test);
+        fs::write(&path, contents).unwrap();
+        path
+    }
+
+    fn reason(report: Report<InitError>) -> String {
+        report
+            .frames()
+            .find_map(|frame| frame.downcast_ref::<String>())
+            .unwrap()
+            .clone()
+    }
+
+    #[test]
+    fn hosts_match_from_least_to_most_specific() {
+        let path = hosts(
+            "matched",
+            "# Everything.\n*  --fixed-width\n\n192.0.2.7 --radix bin\n192.0.2.7:80  --compact-labels\n\
+             [::1]:4000 --recv-framing delim:0a\n::1 --diff\n",
+        );
+        let options = |target: &str| options_for(&path, target.parse().unwrap()).unwrap();
+        assert_eq!(
+            options("192.0.2.7:80"),
+            ["--fixed-width", "--radix", "bin", "--compact-labels"]
+        );
+        assert_eq!(options("192.0.2.7:81"), ["--fixed-width", "--radix", "bin"]);
+        assert_eq!(options("192.0.2.8:80"), ["--fixed-width"]);
+        assert_eq!(
+            options("[::1]:4000"),
+            ["--fixed-width", "--recv-framing", "delim:0a", "--diff"]
+        );
+
+        let args = options("[::1]:4000")
+            .into_iter()
+            .chain(["::1".to_string(), "4000".to_string()]);
+        let config = Config::from_args(args).unwrap();
+        assert!(config.fixed_width && config.diff);
+        assert!(config.recv_framing == Framing::Delimiter(vec![b'\n']));
+    }
+
+    #[test]
+    fn hosts_that_cant_be_read_are_reported() {
+        let target = "192.0.2.7:80".parse().unwrap();
+        let missing = env::temp_dir().join("hexcat-there-are-no-hosts-here");
+        assert_eq!(options_for(&missing, target).unwrap(), Vec::<String>::new());
+
+        let path = hosts("invalid", "*  --diff\nexample.com --radix bin\n");
+        assert_eq!(
+            reason(options_for(&path, target).unwrap_err()),
+            format!("Invalid host \"example.com\" ({} line 2).", path.display())
+        );
+        let path = hosts("ended", "* -- 01\n");
+        assert_eq!(
+            reason(options_for(&path, target).unwrap_err()),
+            format!(
+                "Host settings can't end the options ({} line 1).",
+                path.display()
+            )
+        );
+    }
+}
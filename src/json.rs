@@ -0,0 +1,249 @@
+use crate::config::{Config, JsonInput};
+use crate::error::AppError;
+use crate::radix::parse_hex;
+use crate::{Message, MessageOrigin, Outcome, TcpMessage};
+use error_stack::{IntoReport, Result, ResultExt};
+use std::io::{self, BufRead, Write};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError};
+use std::thread;
+use std::time::{Duration, UNIX_EPOCH};
+
+const POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+pub(crate) fn run(config: &Config) -> Result<Outcome, AppError> {
+    let mut session = crate::open_session(config, config.recv_framing.clone(), config.max_frame)
+        .attach_printable("Could not start JSON output.")
+        .change_context(AppError::InitError)?;
+    session.set_strip(config.strip_header, config.strip_footer);
+    let mut stdin = Some(read_stdin());
+    let mut stdout = io::stdout().lock();
+    let mut outcome = None;
+    let mut closing = false;
+
+    while !session.is_closed() {
+        match &stdin {
+            Some(lines) => match lines.recv_timeout(POLL_INTERVAL) {
+                Ok(line) if line.trim().is_empty() => {}
+                Ok(line) => match parse(&line, config) {
+                    Ok(message) => {
+                        let sent = session.send(&message);
+                        if crate::unless_gone(&mut session, sent)?.is_none() {
+                            outcome = Some(Outcome::Failed);
+                            stdin = None;
+                        }
+                    }
+                    Err(reason) => eprintln!("{reason}"),
+                },
+                Err(RecvTimeoutError::Timeout) => {}
+                Err(RecvTimeoutError::Disconnected) => {
+                    stdin = None;
+                    if config.half_close_on_eof {
+                        session.close_write();
+                    } else {
+                        closing = true;
+                    }
+                }
+            },
+            None => thread::sleep(POLL_INTERVAL),
+        }
+        let retried = session.retry();
+        if crate::unless_gone(&mut session, retried)?.is_none() {
+            outcome = Some(Outcome::Failed);
+        }
+        session.poll();
+        if let Some(notice) = session.take_notice() {
+            eprintln!("{notice}");
+        }
+        for message in session.take_messages() {
+            writeln!(stdout, "{}", to_json(&message))
+                .and_then(|()| stdout.flush())
+                .into_report()
+                .attach_printable("Could not write message to stdout.")
+                .change_context(AppError::Output)?;
+        }
+        if closing && session.queued().next().is_none() {
+            closing = false;
+            outcome.get_or_insert(Outcome::Success);
+            session.close();
+        }
+    }
+    // Closing the connection ourselves can make the read fail instead of just ending.
+    Ok(outcome.unwrap_or(Outcome::reset_if(session.was_reset())))
+}
+
+fn read_stdin() -> Receiver<String> {
+    let (sink, lines) = mpsc::channel();
+    thread::spawn(move || {
+        for line in io::stdin().lock().lines() {
+            let Ok(line) = line else { break };
+            if sink.send(line).is_err() {
+                break;
+            }
+        }
+    });
+    lines
+}
+
+fn parse(line: &str, config: &Config) -> std::result::Result<TcpMessage, String> {
+    let message = match config.json_input {
+        JsonInput::Hex => crate::parse_input(line, config)?,
+        JsonInput::Json => {
+            let hex = hex_field(line)?;
+            match parse_hex(&hex.chars().collect::<Vec<_>>()) {
+                Some(message) if hex.chars().all(|c| c.is_ascii_hexdigit()) => message,
+                _ => return Err(format!("Not sent: \"{hex}\" isn't a hex payload.")),
+            }
+        }
+    };
+    crate::outgoing(message, config)
+}
+
+fn to_json(message: &Message) -> String {
+    let (direction, length, hex) = match &message.origin {
+        MessageOrigin::Local(message) => ("local", message.len(), Some(message)),
+        MessageOrigin::Remote(message) => ("remote", message.len(), Some(message)),
+        MessageOrigin::Secret(length) => ("local", *length, None),
+    };
+    let timestamp = message
+        .timestamp
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default();
+    let hex = match hex {
+        Some(message) => format!(
+            "\"{}\"",
+            message
+                .iter()
+                .map(|byte| format!("{byte:02x}"))
+                .collect::<String>()
+        ),
+        None => "null".to_string(),
+    };
+    format!(
+        "{{\"direction\":\"{direction}\",\"timestamp\":{}.{:03},\"length\":{length},\"hex\":{hex}}}",
+        timestamp.as_secs(),
+        timestamp.subsec_millis()
+    )
+}
+
+// Just enough JSON to find the payload in an object like `{"hex": "dead"}`.
+fn hex_field(line: &str) -> std::result::Result<String, String> {
+    let invalid = || format!("Not sent: \"{line}\" isn't a JSON object with a \"hex\" field.");
+    let mut chars = line.trim().chars().peekable();
+    if chars.next() != Some('{') {
+        return Err(invalid());
+    }
+    loop {
+        skip_whitespace(&mut chars);
+        if chars.next() != Some('"') {
+            return Err(invalid());
+        }
+        let key = string(&mut chars).ok_or_else(invalid)?;
+        skip_whitespace(&mut chars);
+        if chars.next() != Some(':') {
+            return Err(invalid());
+        }
+        skip_whitespace(&mut chars);
+        let value = match chars.peek() {
+            Some('"') => {
+                chars.next();
+                Some(string(&mut chars).ok_or_else(invalid)?)
+            }
+            _ => {
+                while chars.next_if(|c| !matches!(c, ',' | '}')).is_some() {}
+                None
+            }
+        };
+        if key == "hex" {
+            return value.ok_or_else(invalid);
+        }
+        skip_whitespace(&mut chars);
+        match chars.next() {
+            Some(',') => continue,
+            _ => return Err(invalid()),
+        }
+    }
+}
+
+fn skip_whitespace(chars: &mut std::iter::Peekable<std::str::Chars>) {
+    while chars.next_if(|c| c.is_whitespace()).is_some() {}
+}
+
+fn string(chars: &mut std::iter::Peekable<std::str::Chars>) -> Option<String> {
+    let mut string = String::new();
+    loop {
+        match chars.next()? {
+            '"' => return Some(string),
+            '\\' => match chars.next()? {
+                'n' => string.push('\n'),
+                't' => string.push('\t'),
+                'r' => string.push('\r'),
+                'b' => string.push('\u{8}'),
+                'f' => string.push('\u{c}'),
+                'u' => {
+                    let code = chars.by_ref().take(4).collect::<String>();
+                    string.push(char::from_u32(u32::from_str_radix(&code, 16).ok()?)?);
+                }
+                c => string.push(c),
+            },
+            c => string.push(c),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hex_is_found_among_other_fields() {
+        assert_eq!(hex_field(r#"{"hex": "dead"}"#), Ok("dead".to_string()));
+        assert_eq!(
+            hex_field(r#" { "direction":"local", "length":2, "ok":true, "hex":"be ef" } "#),
+            Ok("be ef".to_string())
+        );
+        let message = Message {
+            origin: MessageOrigin::Remote(vec![0x01, 0xff]),
+            timestamp: UNIX_EPOCH + Duration::from_millis(1_500),
+            sequence: 0,
+            status: hexcat::Status::Complete,
+        };
+        let line = to_json(&message);
+        assert_eq!(
+            line,
+            r#"{"direction":"remote","timestamp":1.500,"length":2,"hex":"01ff"}"#
+        );
+        assert_eq!(hex_field(&line), Ok("01ff".to_string()));
+    }
+
+    #[test]
+    fn objects_without_a_hex_string_are_rejected() {
+        for line in [
+            "",
+            "dead",
+            r#"["dead"]"#,
+            r#"{}"#,
+            r#"{"other": "dead"}"#,
+            r#"{"hex": null}"#,
+            r#"{"hex": 12}"#,
+            r#"{"hex": "dead"#,
+            r#"{hex: "dead"}"#,
+            r#"{"a": "b" "hex": "dead"}"#,
+        ] {
+            assert!(hex_field(line).is_err(), "{line}");
+        }
+    }
+
+    #[test]
+    fn strings_are_unescaped() {
+        let read = |rest: &str| string(&mut rest.chars().peekable());
+        assert_eq!(read(r#"plain" after"#), Some("plain".to_string()));
+        assert_eq!(
+            read(r#"a\"b\\c\n\t\u00e9\u0041""#),
+            Some("a\"b\\c\n\té\u{41}".to_string())
+        );
+        assert_eq!(read(r#"é ✓""#), Some("é ✓".to_string()));
+        assert_eq!(read("open"), None);
+        assert_eq!(read(r#"\ud800""#), None);
+        assert_eq!(read(r#"\uzz""#), None);
+    }
+}
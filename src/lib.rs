@@ -0,0 +1,39 @@
+pub mod checksum;
+pub mod clock;
+pub mod connection;
+pub mod error;
+pub mod framing;
+pub mod radix;
+pub mod session;
+pub mod socks;
+
+pub use session::Session;
+
+use std::time::SystemTime;
+
+pub type TcpMessage = Vec<u8>;
+
+const BUFFER_SIZE: usize = 4_096;
+
+#[derive(Clone)]
+pub enum MessageOrigin {
+    Local(TcpMessage),
+    Remote(TcpMessage),
+    Secret(usize),
+}
+
+#[derive(Clone)]
+pub struct Message {
+    pub origin: MessageOrigin,
+    pub timestamp: SystemTime,
+    // Counts up over the whole session, so it carries on from where it was when the history is cleared.
+    pub sequence: usize,
+    pub status: Status,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Status {
+    Complete,
+    Queued,
+    Recorded,
+}
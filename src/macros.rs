@@ -0,0 +1,173 @@
+use crate::hosts;
+use crate::radix::parse_hex;
+use crate::TcpMessage;
+use std::fs;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+// Sequences of messages saved under a name, to be sent again (with the same time between them) by `:macro run`.
+// They're kept in a plain text file, one message to a line: the name of the macro, how long to wait after the message
+// before it (in milliseconds), then the bytes in hex.
+//
+//     login  0    01 05 61 64 6d 69 6e
+//     login  250  02
+//
+// Blank lines and lines starting with "#" are skipped.
+pub(crate) type Steps = Vec<(Duration, TcpMessage)>;
+
+pub(crate) fn load(path: &Path, name: &str) -> Result<Steps, String> {
+    let contents = fs::read_to_string(path)
+        .map_err(|error| format!("Could not read macros from {}: {error}", path.display()))?;
+    let mut steps = Vec::new();
+    for (index, line) in contents.lines().enumerate() {
+        let mut words = line.split_whitespace();
+        let Some(macro_name) = words.next().filter(|word| !word.starts_with('#')) else {
+            continue;
+        };
+        if macro_name != name {
+            continue;
+        }
+        let invalid = || {
+            format!(
+                "Invalid macro step ({} line {}).",
+                path.display(),
+                index + 1
+            )
+        };
+        let delay = words
+            .next()
+            .and_then(|delay| delay.parse().ok())
+            .map(Duration::from_millis)
+            .ok_or_else(invalid)?;
+        let message =
+            parse_hex(&words.flat_map(str::chars).collect::<Vec<_>>()).ok_or_else(invalid)?;
+        steps.push((delay, message));
+    }
+    if steps.is_empty() {
+        return Err(format!(
+            "There's no macro \"{name}\" in {}.",
+            path.display()
+        ));
+    }
+    Ok(steps)
+}
+
+pub(crate) fn save(
+    path: &Path,
+    name: &str,
+    steps: &[(Duration, TcpMessage)],
+) -> Result<(), String> {
+    let failed =
+        |error: std::io::Error| format!("Could not save macros to {}: {error}", path.display());
+    let contents = match fs::read_to_string(path) {
+        Ok(contents) => contents,
+        Err(error) if error.kind() == ErrorKind::NotFound => String::new(),
+        Err(error) => return Err(failed(error)),
+    };
+    let mut lines = contents
+        .lines()
+        .filter(|line| line.split_whitespace().next() != Some(name))
+        .map(str::to_string)
+        .collect::<Vec<_>>();
+    for (delay, message) in steps {
+        let hex = message
+            .iter()
+            .map(|byte| format!("{byte:02x}"))
+            .collect::<Vec<_>>();
+        lines.push(format!("{name} {} {}", delay.as_millis(), hex.join(" ")));
+    }
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir).map_err(failed)?;
+    }
+    fs::write(path, lines.join("\n") + "\n").map_err(failed)
+}
+
+pub(crate) fn default_path() -> Option<PathBuf> {
+    hosts::default_path().map(|hosts| hosts.with_file_name("macros"))
+}
+
+pub(crate) struct Recording {
+    pub(crate) name: String,
+    pub(crate) steps: Steps,
+    sent_at: Option<Instant>,
+}
+impl Recording {
+    pub(crate) fn new(name: String) -> Self {
+        Self {
+            name,
+            steps: Vec::new(),
+            sent_at: None,
+        }
+    }
+
+    // The first message goes straight away when it's played back, however long it took to get to it.
+    pub(crate) fn record(&mut self, message: &[u8]) {
+        let delay = self.sent_at.map_or(Duration::ZERO, |at| at.elapsed());
+        self.sent_at = Some(Instant::now());
+        self.steps.push((delay, message.to_vec()));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn path(test: &str) -> PathBuf {
+        let path = std::env::temp_dir()
+            .join(format!("hexcat-{}", std::process::id()))
+            .join(test);
+        _ = fs::remove_file(&path);
+        path
+    }
+
+    fn steps(steps: &[(u64, &[u8])]) -> Steps {
+        steps
+            .iter()
+            .map(|(delay, message)| (Duration::from_millis(*delay), message.to_vec()))
+            .collect()
+    }
+
+    #[test]
+    fn macros_are_saved_and_loaded_back() {
+        let path = path("saved");
+        let login = steps(&[(0, &[0x01, 0x05, 0x61]), (250, &[0x02])]);
+        save(&path, "login", &login).unwrap();
+        assert_eq!(
+            fs::read_to_string(&path).unwrap(),
+            "login 0 01 05 61\nlogin 250 02\n"
+        );
+        assert_eq!(load(&path, "login").unwrap(), login);
+        assert_eq!(
+            load(&path, "logout").unwrap_err(),
+            format!("There's no macro \"logout\" in {}.", path.display())
+        );
+    }
+
+    #[test]
+    fn saving_replaces_only_the_same_macro() {
+        let path = path("replaced");
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(&path, "# Kept.\nlogin 0 01\n\nping 10 aa bb\nlogin 5 02\n").unwrap();
+        save(&path, "login", &steps(&[(0, &[0x03])])).unwrap();
+        assert_eq!(
+            fs::read_to_string(&path).unwrap(),
+            "# Kept.\n\nping 10 aa bb\nlogin 0 03\n"
+        );
+        assert_eq!(load(&path, "login").unwrap(), steps(&[(0, &[0x03])]));
+        assert_eq!(load(&path, "ping").unwrap(), steps(&[(10, &[0xaa, 0xbb])]));
+    }
+
+    #[test]
+    fn invalid_steps_are_reported() {
+        let path = path("invalid");
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        for contents in ["login soon 01\n", "login 0 0g\n", "login\n"] {
+            fs::write(&path, contents).unwrap();
+            assert_eq!(
+                load(&path, "login").unwrap_err(),
+                format!("Invalid macro step ({} line 1).", path.display())
+            );
+        }
+    }
+}
@@ -1,95 +1,464 @@
-mod error;
+mod activity;
+mod alert;
+mod command;
+mod config;
+mod echo;
+mod expect;
+mod filter;
+mod hosts;
+mod json;
+mod macros;
+mod oneline;
 mod paint;
+mod pipe;
 mod sections;
 mod terminal;
 mod window;
 
+use crate::config::{Config, DumpFormat};
+use crate::connection::Stream;
 use crate::error::{AppError, InitError};
+use crate::framing::Framing;
 use crate::window::{Window, WindowReceiver};
-use error_stack::{IntoReport, Result, ResultExt};
-use std::net::{IpAddr, SocketAddr, TcpStream};
+use error_stack::{IntoReport, Report, Result, ResultExt};
+use hexcat::{
+    checksum, connection, error, framing, radix, session, socks, Message, MessageOrigin, Session,
+    Status, TcpMessage,
+};
+use std::env;
+use std::fs;
+use std::io::{self, Write};
+use std::path::Path;
 use std::process::ExitCode;
 use std::sync::mpsc;
+use std::thread;
 use std::time::Duration;
-use std::{env, thread};
-use terminal::Terminal;
+use terminal::{Position, Size, Terminal};
 use termion::event::Key;
 
-type TcpMessage = Vec<u8>;
+// Reconnecting leaves the old listener thread to wind down on its own; anything it still sends has to be ignored.
+type ConnectionId = usize;
 
-const BUFFER_SIZE: usize = 4_096;
+pub(crate) enum Event {
+    Remote(ConnectionId, TcpMessage),
+    // Whether it was reset, rather than closed.
+    Closed(ConnectionId, bool),
+    Key(Key),
+    Focus(bool),
+    Hover(Position),
+    Resize(Size),
+    // Whether it's a secret, and what the outgoing filter made of it.
+    Filtered(bool, std::result::Result<TcpMessage, String>),
+    InputClosed,
+}
+
+// Termion has no resize event, so the terminal size still has to be checked periodically (but only in its own thread).
+pub(crate) const RESIZE_POLL_INTERVAL: Duration = Duration::from_millis(100);
 
-pub(crate) enum MessageOrigin {
-    Local(TcpMessage),
-    Remote(TcpMessage),
+// Exit codes: 0 is success, 1 any other error, 2 not being able to connect, 3 missing bytes (see `--expect-bytes`), and
+// 4 a reset connection.
+#[derive(Clone, Copy)]
+pub(crate) enum Outcome {
+    Success = 0,
+    Failed = 1,
+    NotConnected = 2,
+    Incomplete = 3,
+    Reset = 4,
+}
+impl Outcome {
+    pub(crate) fn reset_if(reset: bool) -> Self {
+        match reset {
+            true => Self::Reset,
+            false => Self::Success,
+        }
+    }
+}
+impl From<Outcome> for ExitCode {
+    fn from(outcome: Outcome) -> Self {
+        Self::from(outcome as u8)
+    }
 }
 
-// Don't hog an entire CPU core at 100% in the infinite loop. Chill out for a little bit each iteration.
-pub(crate) const THREAD_SLOW_DOWN: Duration = Duration::from_millis(1);
+fn main() -> ExitCode {
+    match run() {
+        Ok(outcome) => outcome.into(),
+        Err(report) => {
+            eprintln!("Error: {report:?}");
+            let not_connected = report.frames().any(|frame| {
+                matches!(
+                    frame.downcast_ref::<InitError>(),
+                    Some(InitError::CouldNotConnect)
+                )
+            });
+            match not_connected {
+                true => Outcome::NotConnected,
+                false => Outcome::Failed,
+            }
+            .into()
+        }
+    }
+}
 
-fn main() -> Result<ExitCode, AppError> {
-    let mut window: Window = start_window()
+fn run() -> Result<Outcome, AppError> {
+    let config = Config::from_env()
+        .attach_printable("Could not start application due to invalid arguments.")
+        .change_context(AppError::InitError)?;
+    if config.version {
+        println!("hexcat {}", env!("CARGO_PKG_VERSION"));
+        return Ok(Outcome::Success);
+    }
+    if let Some(input) = &config.validate {
+        return validate(input, &config);
+    }
+    if config.echo_server {
+        echo::run(&config)?;
+        return Ok(Outcome::Success);
+    }
+    if config.pipe {
+        return pipe::run(&config);
+    }
+    if config.json {
+        return json::run(&config);
+    }
+    if let Some(expected) = config.expect_bytes {
+        return expect::run(&config, expected);
+    }
+    if let Some(interval) = config.oneline {
+        return oneline::run(&config, interval);
+    }
+    let mut window: Window = start_window(&config)
         .attach_printable("Could not start application due to initialization errors.")
         .change_context(AppError::InitError)?;
     window.run()?;
+    let outcome = Outcome::reset_if(window.was_reset());
+
+    if let Some(format) = config.dump {
+        let received = window.dump(config.dump_local);
+        // Dropping the window restores the terminal, so the dump doesn't end up mangled by raw mode.
+        drop(window);
+        dump(&received, format)?;
+    }
 
-    Ok(ExitCode::SUCCESS)
+    Ok(outcome)
 }
 
-fn start_window() -> Result<Window, InitError> {
-    let terminal: Terminal = Terminal::init()
-        .attach_printable("Could not initialize terminal.")
-        .change_context(InitError::NoTerminal)?;
+fn dump(bytes: &[u8], format: DumpFormat) -> Result<(), AppError> {
+    let mut stdout = io::stdout().lock();
+    match format {
+        DumpFormat::Raw => stdout.write_all(bytes),
+        DumpFormat::Hex => {
+            let hex = bytes
+                .iter()
+                .map(|byte| format!("{byte:02x}"))
+                .collect::<String>();
+            writeln!(stdout, "{hex}")
+        }
+    }
+    .and_then(|()| stdout.flush())
+    .into_report()
+    .attach_printable("Could not dump received bytes to stdout.")
+    .change_context(AppError::Output)
+}
 
-    let connection = connect()?;
-    let thread_connection = connection
-        .try_clone()
+fn validate(input: &str, config: &Config) -> Result<Outcome, AppError> {
+    let radix = config.radix;
+    let result = parse_input(input, config).and_then(|bytes| config.encoding.encode(bytes));
+
+    let mut stdout = io::stdout().lock();
+    let (output, code) = match result {
+        Ok(bytes) => {
+            let formatted = bytes
+                .iter()
+                .map(|byte| radix.format(*byte))
+                .collect::<Vec<_>>()
+                .join(" ");
+            let plural = if bytes.len() == 1 { "" } else { "s" };
+            (
+                format!("({} byte{plural}) {formatted}", bytes.len()),
+                Outcome::Success,
+            )
+        }
+        Err(reason) => (reason, Outcome::Failed),
+    };
+    writeln!(stdout, "{output}")
         .into_report()
-        .attach_printable("Could not clone connection for use in TCP thread.")
-        .change_context(InitError::Threads)?;
+        .attach_printable("Could not print validation result to stdout.")
+        .change_context(AppError::Output)?;
+    Ok(code)
+}
 
-    let window = Window::new(terminal, connection, spawn_threads(thread_connection))
-        .attach_printable("Could not initialize terminal window.")
-        .change_context(InitError::Window)?;
+fn parse_input(input: &str, config: &Config) -> std::result::Result<TcpMessage, String> {
+    if let Some(order) = &config.detect_input {
+        return match radix::detect(order, input, config.keep_trailing_space) {
+            Some((_, bytes)) => Ok(bytes),
+            None => Err(format!(
+                "Invalid input: \"{input}\" doesn't read as {}.",
+                radix::describe(order)
+            )),
+        };
+    }
+    let radix = config.radix;
+    let mut input = input.chars().collect::<Vec<_>>();
+    if config.space_delimits {
+        input = radix.pad_groups(&input);
+    }
+    match input.iter().find(|c| !radix.accepts(**c)) {
+        Some(c) => Err(format!(
+            "Invalid {} input: \"{}\" is not allowed.",
+            radix.name(),
+            c.escape_default()
+        )),
+        None => match radix.split_input(&input) {
+            (_, Some(rest)) => Err(format!(
+                "Invalid {} input: \"{rest}\" doesn't make up a byte.",
+                radix.name()
+            )),
+            (bytes, None) => Ok(bytes),
+        },
+    }
+}
 
-    Ok(window)
+// Whatever is in the variable is likely meant to stay private, so none of it is repeated back in errors.
+fn read_send_env(name: &str, config: &Config) -> Result<TcpMessage, InitError> {
+    let invalid = |reason: String| Report::new(InitError::SendEnv).attach_printable(reason);
+    let value = env::var(name).map_err(|error| {
+        invalid(match error {
+            env::VarError::NotPresent => format!("Environment variable \"{name}\" is not set."),
+            env::VarError::NotUnicode(_) => {
+                format!("Environment variable \"{name}\" isn't valid text.")
+            }
+        })
+    })?;
+    match parse_input(&value, config) {
+        Ok(bytes) if bytes.is_empty() => Err(invalid(format!(
+            "Environment variable \"{name}\" is empty."
+        ))),
+        Ok(bytes) => Ok(bytes),
+        Err(_) => Err(invalid(format!(
+            "Environment variable \"{name}\" doesn't hold valid {} input.",
+            match &config.detect_input {
+                Some(order) => radix::describe(order),
+                None => config.radix.name().to_string(),
+            }
+        ))),
+    }
 }
 
-fn connect() -> Result<TcpStream, InitError> {
-    let args: Vec<String> = env::args().collect();
-    if args.len() < 2 {
-        Err(InitError::NotEnoughArguments)
-            .into_report()
-            .attach_printable("You must supply at least 2 arguments (IP Address and Port).")?;
+fn connect(config: &Config) -> Result<Stream, InitError> {
+    match config.fd {
+        Some(fd) => connection::inherit(fd, config.flush),
+        None => {
+            connection::connect(config.target, config.flush, config.proxy.as_ref()).map(Stream::Tcp)
+        }
     }
+}
 
-    let addr: IpAddr = args[1]
-        .parse()
-        .into_report()
-        .attach_printable("Invalid IP address.")
-        .change_context(InitError::InvalidConnectionSettings)?;
-    let port: u16 = args[2]
-        .parse()
-        .into_report()
-        .attach_printable("Invalid port number.")
-        .change_context(InitError::InvalidConnectionSettings)?;
+fn open_session(config: &Config, framing: Framing, max_frame: usize) -> Result<Session, InitError> {
+    let mut session = Session::over(connect(config)?, config.flush, framing, max_frame)?;
+    if let Some(filter) = config.filter_in.clone() {
+        session.set_filter(move |frame| filter.apply(frame));
+    }
+    Ok(session)
+}
 
-    let socket_addr: SocketAddr = SocketAddr::new(addr, port);
-    let stream = TcpStream::connect(socket_addr)
+// Writes to a peer that has gone away are reported on stderr instead of failing.
+fn unless_gone<T>(
+    session: &mut Session,
+    result: Result<T, AppError>,
+) -> Result<Option<T>, AppError> {
+    match result {
+        Err(report) if report.downcast_ref().is_some_and(connection::is_gone) => {
+            eprintln!("Not sent: the remote server is no longer accepting data.");
+            session.discard_queued();
+            session.close();
+            Ok(None)
+        }
+        result => result.map(Some),
+    }
+}
+
+fn outgoing(message: TcpMessage, config: &Config) -> std::result::Result<TcpMessage, String> {
+    let message = config.encoding.encode(message)?;
+    match &config.filter_out {
+        Some(filter) => filter
+            .apply(&message)
+            .map_err(|notice| format!("Not sent: {notice}")),
+        None => Ok(message),
+    }
+}
+
+fn read_send_lines(path: &Path, config: &Config) -> Result<Vec<TcpMessage>, InitError> {
+    let text = fs::read(path)
         .into_report()
         .attach_printable(format!(
-            "Could not connect to remote server (using {addr} on port {port})."
+            "Could not read lines to send ({}).",
+            path.display()
         ))
-        .change_context(InitError::CouldNotConnect)?;
+        .change_context(InitError::SendFile)?;
+    // A final line ending isn't followed by another (empty) line.
+    let text = text.strip_suffix(b"\n").unwrap_or(&text);
+    if text.is_empty() {
+        return Ok(Vec::new());
+    }
+    Ok(text
+        .split(|byte| *byte == b'\n')
+        .map(|line| line.strip_suffix(b"\r").unwrap_or(line))
+        .filter(|line| {
+            let comment = line.trim_ascii().is_empty() || line.starts_with(b"#");
+            !(config.skip_comments && comment)
+        })
+        .map(|line| [line, &config.line_terminator].concat())
+        .collect())
+}
+
+fn start_window(config: &Config) -> Result<Window, InitError> {
+    let send_file = match &config.send_file {
+        Some(path) => fs::read(path)
+            .into_report()
+            .attach_printable(format!("Could not read file to send ({}).", path.display()))
+            .change_context(InitError::SendFile)?,
+        None => Vec::new(),
+    };
+    let send_lines = match &config.send_lines {
+        Some(path) => read_send_lines(path, config)?,
+        None => Vec::new(),
+    };
+    let send_args = match &config.send_args {
+        Some(input) => Some(
+            parse_input(input, config)
+                .map_err(|reason| Report::new(InitError::SendArgs).attach_printable(reason))?,
+        ),
+        None => None,
+    };
+    let send_env = match &config.send_env {
+        Some(name) => Some(read_send_env(name, config)?),
+        None => None,
+    };
 
-    Ok(stream)
+    let terminal: Terminal = Terminal::init()
+        .attach_printable("Could not initialize terminal.")
+        .change_context(InitError::NoTerminal)?;
+
+    let session = match &config.open {
+        Some(path) => Some(
+            fs::read_to_string(path)
+                .into_report()
+                .attach_printable(format!("Could not read session ({}).", path.display()))
+                .change_context(InitError::OpenSession)?,
+        ),
+        None => None,
+    };
+
+    let receiver = spawn_threads();
+    let connection = match session {
+        Some(_) => None,
+        None => {
+            let connection = connect(config)?;
+            receiver.listen(&connection, 0)?;
+            Some(connection)
+        }
+    };
+
+    let mut window = Window::new(terminal, config, connection, receiver)
+        .attach_printable("Could not initialize terminal window.")
+        .change_context(InitError::Window)?;
+
+    if let (Some(path), Some(log)) = (&config.open, session) {
+        window
+            .review(path, &log)
+            .map_err(|reason| Report::new(InitError::OpenSession).attach_printable(reason))?;
+    }
+
+    if config.send_file.is_some() {
+        for message in config.send_framing.split(&send_file) {
+            window
+                .send(message)
+                .attach_printable("Could not send file contents.")
+                .change_context(InitError::SendFile)?;
+        }
+    }
+
+    let delays = std::iter::once(Duration::ZERO).chain(std::iter::repeat(config.line_delay));
+    window.send_paced(delays.zip(send_lines));
+
+    if let Some(message) = send_args {
+        window
+            .send(message)
+            .attach_printable("Could not send the message from the command line.")
+            .change_context(InitError::SendArgs)?;
+    }
+
+    if let Some(message) = send_env {
+        window
+            .send_secret(message)
+            .attach_printable("Could not send the payload from the environment.")
+            .change_context(InitError::SendEnv)?;
+    }
+
+    Ok(window)
 }
 
-fn spawn_threads(connection: TcpStream) -> WindowReceiver {
-    let (message_sink, message_receiver) = mpsc::channel::<TcpMessage>();
-    thread::spawn(move || sections::Messages::listen(connection, message_sink));
-    let (input_sink, input_receiver) = mpsc::channel::<Key>();
+fn spawn_threads() -> WindowReceiver {
+    let (sink, receiver) = mpsc::channel::<Event>();
+    let input_sink = sink.clone();
     thread::spawn(move || sections::Input::listen(input_sink));
+    let size_sink = sink.clone();
+    thread::spawn(move || Terminal::watch_size(size_sink));
 
-    WindowReceiver::new(message_receiver, input_receiver)
+    WindowReceiver::new(receiver, sink)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config(args: &[&str]) -> Config {
+        Config::from_args(args.iter().map(|arg| arg.to_string())).unwrap()
+    }
+
+    #[test]
+    fn lines_to_send_are_read_from_the_file() {
+        let dir = env::temp_dir().join(format!("hexcat-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("send-lines");
+        fs::write(&path, "one\r\n \t\n# two\nthree\n").unwrap();
+
+        let lines = read_send_lines(&path, &config(&["127.0.0.1", "7"])).unwrap();
+        assert_eq!(lines, [&b"one\n"[..], b" \t\n", b"# two\n", b"three\n"]);
+        let config = config(&[
+            "--skip-comments",
+            "--line-terminator",
+            "0d0a",
+            "127.0.0.1",
+            "7",
+        ]);
+        let lines = read_send_lines(&path, &config).unwrap();
+        assert_eq!(lines, [&b"one\r\n"[..], b"three\r\n"]);
+
+        fs::write(&path, "\n").unwrap();
+        assert_eq!(read_send_lines(&path, &config).unwrap(), [&b"\r\n"[..]; 0]);
+        fs::remove_file(&path).unwrap();
+        assert!(read_send_lines(&path, &config).is_err());
+    }
+
+    #[test]
+    fn input_from_outside_the_window_is_detected_too() {
+        let config = config(&["--detect-input", "dec,hex", "127.0.0.1", "7"]);
+        assert_eq!(parse_input("10 20", &config), Ok(vec![10, 20]));
+        assert_eq!(parse_input("de ad", &config), Ok(vec![0xde, 0xad]));
+        assert_eq!(
+            parse_input("1f 300", &config),
+            Err("Invalid input: \"1f 300\" doesn't read as decimal or hex.".to_string())
+        );
+        let config = self::config(&["--detect-input", "hex,text", "127.0.0.1", "7"]);
+        assert_eq!(parse_input("é ", &config), Ok("é".as_bytes().to_vec()));
+        let config = self::config(&[
+            "--detect-input",
+            "text",
+            "--keep-trailing-space",
+            "127.0.0.1",
+            "7",
+        ]);
+        assert_eq!(parse_input("é ", &config), Ok("é ".as_bytes().to_vec()));
+    }
 }
@@ -0,0 +1,60 @@
+use crate::config::Config;
+use crate::error::AppError;
+use crate::framing::Framing;
+use crate::sections;
+use crate::session::Session;
+use crate::Outcome;
+use error_stack::{IntoReport, Result, ResultExt};
+use std::io::{self, IsTerminal, Write};
+use std::thread;
+use std::time::{Duration, Instant};
+
+pub(crate) fn run(config: &Config, interval: Duration) -> Result<Outcome, AppError> {
+    let mut session = crate::connect(config)
+        .and_then(|connection| Session::over(connection, config.flush, Framing::Whole, usize::MAX))
+        .attach_printable("Could not start watching the connection.")
+        .change_context(AppError::InitError)?;
+    let connected_at = Instant::now();
+    let overwrite = io::stdout().is_terminal();
+
+    loop {
+        session.poll();
+        session.clear();
+        let state = match (session.is_closed(), session.was_reset()) {
+            (false, _) => "up",
+            (true, false) => "closed",
+            (true, true) => "reset",
+        };
+        let line = format!(
+            "{} {state} {}",
+            match config.unix {
+                true => "Unix socket".to_string(),
+                false => config.target.to_string(),
+            },
+            sections::traffic(connected_at.elapsed(), session.sent(), session.received())
+        );
+        match overwrite {
+            true => write(&format!("\r{line}{}", termion::clear::UntilNewline))?,
+            false => write(&format!("{line}\n"))?,
+        }
+        if session.is_closed() {
+            break;
+        }
+        thread::sleep(interval);
+    }
+
+    if overwrite {
+        write("\n")?;
+    }
+    Ok(Outcome::reset_if(session.was_reset()))
+}
+
+fn write(output: &str) -> Result<(), AppError> {
+    let mut stdout = io::stdout().lock();
+    stdout
+        .write_all(output.as_bytes())
+        .and_then(|_| stdout.flush())
+        .into_report()
+        .attach_printable("Could not print the connection's status to stdout.")
+        .change_context(AppError::Output)
+}
@@ -1,9 +1,244 @@
-use crate::error::AppError;
+use crate::error::{AppError, InitError};
 use crate::terminal::Size;
-use error_stack::Result;
+use error_stack::{IntoReport, Result, ResultExt};
+use termion::color::{AnsiValue, Fg};
 
-pub type PaintOutput = Vec<Vec<char>>;
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Style {
+    #[default]
+    Plain,
+    Local,
+    Remote,
+    Warning,
+    Pending,
+    Header,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub struct Cell {
+    pub character: char,
+    pub style: Style,
+    pub bold: bool,
+    pub byte: Option<u8>,
+}
+impl Cell {
+    pub const BLANK: Cell = Cell {
+        character: ' ',
+        style: Style::Plain,
+        bold: false,
+        byte: None,
+    };
+
+    pub fn new(character: char, style: Style) -> Self {
+        Self {
+            character,
+            style,
+            bold: false,
+            byte: None,
+        }
+    }
+}
+
+pub type PaintLine = Vec<Cell>;
+pub type PaintOutput = Vec<PaintLine>;
+
+pub fn styled(text: &str, style: Style) -> PaintLine {
+    text.chars()
+        .map(|character| Cell::new(character, style))
+        .collect()
+}
+
+pub fn plain(text: &str) -> PaintLine {
+    styled(text, Style::Plain)
+}
+
+pub fn columns(c: char) -> usize {
+    match c as u32 {
+        0x0300..=0x036f | 0x200b..=0x200f | 0xfe00..=0xfe0f => 0,
+        0x1100..=0x115f
+        | 0x2e80..=0x303e
+        | 0x3041..=0x33ff
+        | 0x3400..=0x4dbf
+        | 0x4e00..=0x9fff
+        | 0xa000..=0xa4cf
+        | 0xac00..=0xd7a3
+        | 0xf900..=0xfaff
+        | 0xfe30..=0xfe4f
+        | 0xff00..=0xff60
+        | 0xffe0..=0xffe6
+        | 0x1f300..=0x1f64f
+        | 0x1f900..=0x1f9ff
+        | 0x20000..=0x3fffd => 2,
+        _ => 1,
+    }
+}
 
 pub trait Painter {
     fn paint(&self, bounds: Size) -> Result<PaintOutput, AppError>;
 }
+
+// The first 16 are the terminal's own colors, so the user's theme decides the exact shade.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub struct Color(u8);
+impl Color {
+    const NAMES: [&'static str; 16] = [
+        "black",
+        "red",
+        "green",
+        "yellow",
+        "blue",
+        "magenta",
+        "cyan",
+        "white",
+        "bright-black",
+        "bright-red",
+        "bright-green",
+        "bright-yellow",
+        "bright-blue",
+        "bright-magenta",
+        "bright-cyan",
+        "bright-white",
+    ];
+
+    pub fn parse(value: &str) -> Result<Self, InitError> {
+        if let Some(index) = Self::NAMES.iter().position(|name| *name == value) {
+            return Ok(Self(index as u8));
+        }
+        value
+            .parse::<u8>()
+            .map(Self)
+            .into_report()
+            .attach_printable(format!(
+                "Invalid color \"{value}\" (use a name like \"cyan\" or a 256-color index)."
+            ))
+            .change_context(InitError::InvalidArguments)
+    }
+
+    fn css(&self) -> String {
+        const STANDARD: [&str; 16] = [
+            "#000000", "#cd0000", "#00cd00", "#cdcd00", "#0000ee", "#cd00cd", "#00cdcd", "#e5e5e5",
+            "#7f7f7f", "#ff0000", "#00ff00", "#ffff00", "#5c5cff", "#ff00ff", "#00ffff", "#ffffff",
+        ];
+        let Color(index) = *self;
+        match index {
+            0..=15 => STANDARD[index as usize].to_string(),
+            16..=231 => {
+                let level = |value: u8| if value == 0 { 0 } else { 55 + 40 * value };
+                let cube = index - 16;
+                format!(
+                    "#{:02x}{:02x}{:02x}",
+                    level(cube / 36),
+                    level(cube / 6 % 6),
+                    level(cube % 6)
+                )
+            }
+            _ => {
+                let gray = 8 + 10 * (index - 232);
+                format!("#{gray:02x}{gray:02x}{gray:02x}")
+            }
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+pub struct Theme {
+    pub local: Option<Color>,
+    pub remote: Option<Color>,
+    pub warning: Option<Color>,
+}
+impl Default for Theme {
+    // Yellow and cyan stay distinguishable for the common forms of colorblindness.
+    fn default() -> Self {
+        Self {
+            local: Some(Color(3)),
+            remote: Some(Color(6)),
+            warning: Some(Color(1)),
+        }
+    }
+}
+impl Theme {
+    pub fn monochrome() -> Self {
+        Self {
+            local: None,
+            remote: None,
+            warning: None,
+        }
+    }
+
+    fn color(&self, style: Style) -> Option<Color> {
+        match style {
+            Style::Plain | Style::Header => None,
+            Style::Local => self.local,
+            Style::Remote | Style::Pending => self.remote,
+            Style::Warning => self.warning,
+        }
+    }
+
+    pub fn escape(&self, style: Style, bold: bool) -> String {
+        let color = self.color(style);
+        let mut escape = termion::style::Reset.to_string();
+        if style == Style::Pending {
+            escape.push_str(termion::style::Faint.as_ref());
+        }
+        if bold {
+            escape.push_str(termion::style::Bold.as_ref());
+        }
+        if let Some(Color(value)) = color {
+            escape.push_str(&Fg(AnsiValue(value)).to_string());
+        }
+        escape
+    }
+
+    pub fn ansi(&self, line: &[Cell]) -> String {
+        let mut style = (Style::Plain, false);
+        let mut output = String::with_capacity(line.len());
+        for cell in line {
+            if (cell.style, cell.bold) != style {
+                style = (cell.style, cell.bold);
+                output.push_str(&self.escape(cell.style, cell.bold));
+            }
+            output.push(cell.character);
+        }
+        if style != (Style::Plain, false) {
+            output.push_str(&self.escape(Style::Plain, false));
+        }
+        output
+    }
+
+    pub fn html(&self, lines: &[PaintLine]) -> String {
+        let mut output = String::from(
+            "<!DOCTYPE html>\n<meta charset=\"utf-8\">\n<pre style=\"background: #000; color: #e5e5e5\">\n",
+        );
+        for line in lines {
+            for run in line.chunk_by(|a, b| (a.style, a.bold) == (b.style, b.bold)) {
+                let text = run
+                    .iter()
+                    .map(|cell| match cell.character {
+                        '&' => "&amp;".to_string(),
+                        '<' => "&lt;".to_string(),
+                        '>' => "&gt;".to_string(),
+                        c => c.to_string(),
+                    })
+                    .collect::<String>();
+                let mut css = Vec::new();
+                if let Some(color) = self.color(run[0].style) {
+                    css.push(format!("color: {}", color.css()));
+                }
+                if run[0].style == Style::Pending {
+                    css.push("opacity: 0.6".to_string());
+                }
+                if run[0].bold {
+                    css.push("font-weight: bold".to_string());
+                }
+                if css.is_empty() {
+                    output.push_str(&text);
+                } else {
+                    output.push_str(&format!("<span style=\"{}\">{text}</span>", css.join("; ")));
+                }
+            }
+            output.push('\n');
+        }
+        output.push_str("</pre>\n");
+        output
+    }
+}
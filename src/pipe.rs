@@ -0,0 +1,105 @@
+use crate::config::Config;
+use crate::error::AppError;
+use crate::{MessageOrigin, Outcome, TcpMessage};
+use error_stack::{IntoReport, Result, ResultExt};
+use std::io::{self, Read, Write};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError};
+use std::thread;
+use std::time::Duration;
+
+const POLL_INTERVAL: Duration = Duration::from_millis(10);
+const BUFFER_SIZE: usize = 4_096;
+
+pub(crate) fn run(config: &Config) -> Result<Outcome, AppError> {
+    let mut session = crate::open_session(config, config.recv_framing.clone(), config.max_frame)
+        .attach_printable("Could not start pipe.")
+        .change_context(AppError::InitError)?;
+    session.set_strip(config.strip_header, config.strip_footer);
+    let mut stdin = Some(read_stdin());
+    let mut unsent = Vec::new();
+    let mut stdout = io::stdout().lock();
+    let mut outcome = None;
+    let mut closing = false;
+
+    while !session.is_closed() {
+        let mut frames = Vec::new();
+        match &stdin {
+            Some(chunks) => match chunks.recv_timeout(POLL_INTERVAL) {
+                Ok(chunk) => {
+                    unsent.extend_from_slice(&chunk);
+                    frames = config.send_framing.take_frames(&mut unsent);
+                }
+                Err(RecvTimeoutError::Timeout) => {}
+                Err(RecvTimeoutError::Disconnected) => {
+                    stdin = None;
+                    frames = config.send_framing.split(&std::mem::take(&mut unsent));
+                    closing = true;
+                }
+            },
+            None => thread::sleep(POLL_INTERVAL),
+        }
+        for frame in frames {
+            let sent = match &config.filter_out {
+                Some(filter) => match filter.apply(&frame) {
+                    Ok(filtered) => session.send(&filtered),
+                    Err(notice) => {
+                        eprintln!("Not sent: {notice}");
+                        continue;
+                    }
+                },
+                None => session.send(&frame),
+            };
+            if crate::unless_gone(&mut session, sent)?.is_none() {
+                outcome = Some(Outcome::Failed);
+                stdin = None;
+                break;
+            }
+        }
+        let retried = session.retry();
+        if crate::unless_gone(&mut session, retried)?.is_none() {
+            outcome = Some(Outcome::Failed);
+        }
+        session.poll();
+        if let Some(notice) = session.take_notice() {
+            eprintln!("{notice}");
+        }
+        for message in session.take_messages() {
+            if let MessageOrigin::Remote(frame) = message.origin {
+                stdout
+                    .write_all(&frame)
+                    .and_then(|()| stdout.flush())
+                    .into_report()
+                    .attach_printable("Could not copy received bytes to stdout.")
+                    .change_context(AppError::Output)?;
+            }
+        }
+        // Some servers only reply once they've seen the end of the request, so on EOF the sending half can be closed
+        // on its own while replies are still read. Otherwise the whole connection goes, which ends the pipe.
+        if closing && session.queued().next().is_none() {
+            closing = false;
+            match config.half_close_on_eof {
+                true => session.close_write(),
+                false => {
+                    outcome.get_or_insert(Outcome::Success);
+                    session.close();
+                }
+            }
+        }
+    }
+    // Closing the connection ourselves can make the read fail instead of just ending.
+    Ok(outcome.unwrap_or(Outcome::reset_if(session.was_reset())))
+}
+
+fn read_stdin() -> Receiver<TcpMessage> {
+    let (sink, chunks) = mpsc::channel();
+    thread::spawn(move || {
+        let mut stdin = io::stdin().lock();
+        let mut buffer = [0; BUFFER_SIZE];
+        while let Ok(read @ 1..) = stdin.read(&mut buffer) {
+            if sink.send(buffer[..read].to_vec()).is_err() {
+                break;
+            }
+        }
+    });
+    chunks
+}
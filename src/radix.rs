@@ -0,0 +1,250 @@
+use crate::error::InitError;
+use crate::TcpMessage;
+use error_stack::{IntoReport, Result, ResultExt};
+
+// Octal doesn't divide a byte evenly, so its values are separated by spaces instead of packed together.
+#[derive(Clone, Copy, Default, PartialEq, Eq)]
+pub enum Radix {
+    #[default]
+    Hex,
+    Octal,
+    Binary,
+}
+impl Radix {
+    pub const ALL: [Radix; 3] = [Self::Hex, Self::Octal, Self::Binary];
+
+    pub fn parse(value: &str) -> Result<Self, InitError> {
+        match value {
+            "hex" => Ok(Self::Hex),
+            "oct" => Ok(Self::Octal),
+            "bin" => Ok(Self::Binary),
+            _ => Err(InitError::InvalidArguments)
+                .into_report()
+                .attach_printable(format!(
+                    "Invalid radix \"{value}\" (use \"hex\", \"oct\" or \"bin\")."
+                )),
+        }
+    }
+
+    pub fn next(self) -> Self {
+        match self {
+            Self::Hex => Self::Octal,
+            Self::Octal => Self::Binary,
+            Self::Binary => Self::Hex,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Hex => "hex",
+            Self::Octal => "octal",
+            Self::Binary => "binary",
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Self::Hex => "Hex:",
+            Self::Octal => "Oct:",
+            Self::Binary => "Bin:",
+        }
+    }
+
+    pub fn format(self, byte: u8) -> String {
+        match self {
+            Self::Hex => format!("{byte:02x}"),
+            Self::Octal => format!("{byte:03o}"),
+            Self::Binary => format!("{byte:08b}"),
+        }
+    }
+
+    pub fn width(self) -> usize {
+        match self {
+            Self::Hex => 2,
+            Self::Octal => 3,
+            Self::Binary => 8,
+        }
+    }
+
+    pub fn accepts(self, c: char) -> bool {
+        match self {
+            Self::Hex => c.is_ascii_hexdigit() || c == ' ',
+            Self::Octal => c.is_digit(8) || c == ' ',
+            Self::Binary => c == '0' || c == '1' || c == ' ',
+        }
+    }
+
+    pub fn split_input(self, input: &[char]) -> (TcpMessage, Option<String>) {
+        match self {
+            Self::Hex => {
+                let digits = input
+                    .iter()
+                    .copied()
+                    .filter(char::is_ascii_hexdigit)
+                    .collect::<Vec<_>>();
+                let complete = digits.len() - digits.len() % 2;
+                let bytes = parse_hex(&digits[..complete]).unwrap_or_default();
+                (bytes, digits.get(complete).map(char::to_string))
+            }
+            Self::Octal => {
+                let input = input.iter().collect::<String>();
+                let mut bytes = Vec::new();
+                for value in input.split_whitespace() {
+                    match u8::from_str_radix(value, 8) {
+                        Ok(byte) => bytes.push(byte),
+                        Err(_) => return (bytes, Some(value.to_string())),
+                    }
+                }
+                (bytes, None)
+            }
+            Self::Binary => {
+                let bits = input
+                    .iter()
+                    .filter(|c| **c == '0' || **c == '1')
+                    .collect::<String>();
+                let complete = bits.len() - bits.len() % 8;
+                let bytes = (0..complete)
+                    .step_by(8)
+                    .filter_map(|start| u8::from_str_radix(&bits[start..start + 8], 2).ok())
+                    .collect();
+                let rest = Some(bits[complete..].to_string()).filter(|rest| !rest.is_empty());
+                (bytes, rest)
+            }
+        }
+    }
+
+    // A group too short to fill its last byte is zero-padded at the front, so "1 2" is 01 02 instead of 12.
+    pub fn pad_groups(self, input: &[char]) -> Vec<char> {
+        let digits = match self {
+            Self::Hex => 2,
+            Self::Binary => 8,
+            Self::Octal => return input.to_vec(),
+        };
+        let mut padded = Vec::with_capacity(input.len());
+        for group in input.split(|c| *c == ' ') {
+            if !padded.is_empty() {
+                padded.push(' ');
+            }
+            padded.extend(std::iter::repeat('0').take((digits - group.len() % digits) % digits));
+            padded.extend_from_slice(group);
+        }
+        padded
+    }
+
+    pub fn parse_input(self, input: &[char]) -> Option<TcpMessage> {
+        match self.split_input(input) {
+            (bytes, None) => Some(bytes),
+            (_, Some(_)) => None,
+        }
+    }
+}
+
+// Returns `None` on a dangling nibble, since there's no telling which byte it belonged to.
+pub fn parse_hex(input: &[char]) -> Option<TcpMessage> {
+    let input = input
+        .iter()
+        .copied()
+        .filter(char::is_ascii_hexdigit)
+        .collect::<Vec<char>>();
+    if input.len() % 2 != 0 {
+        return None;
+    }
+
+    let hex = input
+        .chunks(2)
+        .map(|double_hex_chars| double_hex_chars.iter().collect::<String>())
+        .filter_map(|hex_string| u8::from_str_radix(&hex_string, 16).ok())
+        .collect::<Vec<_>>();
+    Some(hex)
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Guess {
+    Hex,
+    Decimal,
+    Text,
+}
+impl Guess {
+    // Input like "10 20" fits more than one, so the order they're tried in decides which it's read as.
+    pub fn parse_order(value: &str) -> Result<Vec<Self>, InitError> {
+        value
+            .split(',')
+            .map(|guess| match guess {
+                "" => Err(InitError::InvalidArguments)
+                    .into_report()
+                    .attach_printable(format!(
+                        "Missing input encoding in \"{value}\" (use a list of \"hex\", \"dec\" and \"text\", like \"hex,dec,text\")."
+                    )),
+                "hex" => Ok(Self::Hex),
+                "dec" => Ok(Self::Decimal),
+                "text" => Ok(Self::Text),
+                _ => Err(InitError::InvalidArguments)
+                    .into_report()
+                    .attach_printable(format!(
+                        "Invalid input encoding \"{guess}\" (use a list of \"hex\", \"dec\" and \"text\", like \"hex,dec,text\")."
+                    )),
+            })
+            .collect()
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Hex => "hex",
+            Self::Decimal => "decimal",
+            Self::Text => "text",
+        }
+    }
+
+    pub fn accepts(self, c: char) -> bool {
+        match self {
+            Self::Hex => c.is_ascii_hexdigit() || c == ' ',
+            Self::Decimal => c.is_ascii_digit() || c == ' ',
+            Self::Text => true,
+        }
+    }
+
+    pub fn read(self, input: &str) -> Option<TcpMessage> {
+        let mut values = input.split_whitespace();
+        match self {
+            Self::Hex => values.try_fold(Vec::new(), |mut bytes, value| {
+                let digits = value.chars().collect::<Vec<_>>();
+                if !digits.iter().all(char::is_ascii_hexdigit) {
+                    return None;
+                }
+                bytes.extend(parse_hex(&digits)?);
+                Some(bytes)
+            }),
+            Self::Decimal => values
+                .map(|value| match value.chars().all(|c| c.is_ascii_digit()) {
+                    true => value.parse::<u8>().ok(),
+                    false => None,
+                })
+                .collect(),
+            Self::Text => Some(input.as_bytes().to_vec()),
+        }
+    }
+}
+
+pub fn describe(order: &[Guess]) -> String {
+    let names = order.iter().map(|guess| guess.name()).collect::<Vec<_>>();
+    match names.split_last() {
+        Some((last, [])) => last.to_string(),
+        Some((last, rest)) => format!("{} or {last}", rest.join(", ")),
+        None => String::new(),
+    }
+}
+
+// Trailing whitespace is only a separator in hex and decimal, so as text it's dropped unless it's meant to be kept.
+pub fn detect(
+    order: &[Guess],
+    input: &str,
+    keep_trailing_space: bool,
+) -> Option<(Guess, TcpMessage)> {
+    order.iter().find_map(|guess| {
+        let input = match guess {
+            Guess::Text if !keep_trailing_space => input.trim_end(),
+            _ => input,
+        };
+        guess.read(input).map(|bytes| (*guess, bytes))
+    })
+}
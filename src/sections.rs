@@ -1,210 +1,3865 @@
-use crate::error::AppError;
-use crate::paint::{PaintOutput, Painter};
-use crate::terminal::{Size, Terminal};
-use crate::{MessageOrigin, TcpMessage, BUFFER_SIZE};
+use crate::checksum::Checksum;
+use crate::command::COMMAND_PREFIX;
+use crate::connection::Transport;
+use crate::error::{AppError, InitError};
+use crate::filter::Filter;
+use crate::framing::Framing;
+use crate::paint::{columns, plain, styled, Cell, PaintLine, PaintOutput, Painter, Style};
+use crate::radix::{self, parse_hex, Guess, Radix};
+use crate::session::Session;
+use crate::terminal::{InputReader, Position, Size};
+use crate::{Event, Message, MessageOrigin, Status, TcpMessage};
 use error_stack::{IntoReport, Result, ResultExt};
-use std::cmp::min;
-use std::io::Write;
-use std::io::{ErrorKind, Read};
-use std::net::{SocketAddr, TcpStream};
+use std::cell::{Ref, RefCell};
+use std::cmp::{max, min};
+use std::net::SocketAddr;
+use std::path::Path;
 use std::sync::mpsc::Sender;
+use std::time::{Duration, Instant, UNIX_EPOCH};
 use termion::event::Key;
 
+pub(crate) const LOCAL_LABEL: &str = "LOCAL";
+pub(crate) const REMOTE_LABEL: &str = "REMOTE";
+pub(crate) const PREVIEW_LABEL: &str = "Bytes:";
+const EMPTY_MARKER: &str = "(empty)";
+const DETECT_LABEL: &str = "Auto:";
+const TOO_NARROW: &str = "Too narrow.";
+const INPUT_ROW: usize = 2;
+const TITLE_ROWS: usize = 2;
+pub(crate) const FIXED_COLUMNS: usize = 16;
+const SECRET_LABEL: &str = "SECRET";
+pub(crate) const DEFAULT_WRAP_MARKER: &str = "↳";
+pub(crate) const DEFAULT_SEPARATOR: char = '│';
+pub(crate) const DEFAULT_UNPRINTABLE: char = '.';
+
+#[derive(Clone, Copy, Default, PartialEq, Eq, Debug)]
+pub(crate) enum Controls {
+    #[default]
+    Dot,
+    Caret,
+    Pictures,
+}
+impl Controls {
+    pub(crate) fn parse(value: &str) -> Result<Self, InitError> {
+        match value {
+            "dot" => Ok(Self::Dot),
+            "caret" => Ok(Self::Caret),
+            "pictures" => Ok(Self::Pictures),
+            _ => Err(InitError::InvalidArguments)
+                .into_report()
+                .attach_printable(format!(
+                    "Invalid control character style \"{value}\" (use \"dot\", \"caret\" or \"pictures\")."
+                )),
+        }
+    }
+
+    fn width(self) -> usize {
+        match self {
+            Self::Caret => 2,
+            Self::Dot | Self::Pictures => 1,
+        }
+    }
+
+    fn show(self, byte: u8, unprintable: char) -> String {
+        let control = byte < 0x20 || byte == 0x7f;
+        let shown = match (self, byte) {
+            (_, 0x20..=0x7e) => (byte as char).to_string(),
+            (Self::Caret, _) if control => format!("^{}", (byte ^ 0x40) as char),
+            (Self::Pictures, 0x7f) => '␡'.to_string(),
+            (Self::Pictures, _) if control => char::from_u32(0x2400 + u32::from(byte))
+                .map_or(unprintable.to_string(), String::from),
+            _ => unprintable.to_string(),
+        };
+        format!("{shown:>width$}", width = self.width())
+    }
+}
+
+#[derive(Clone, Copy)]
+pub(crate) struct Gutter {
+    width: usize,
+    separator: char,
+    compact: bool,
+}
+impl Gutter {
+    pub(crate) fn new(labels: &[&str]) -> Self {
+        let longest = labels
+            .iter()
+            .map(|label| label.chars().count())
+            .max()
+            .unwrap_or(0);
+        Self {
+            width: longest + 2,
+            separator: DEFAULT_SEPARATOR,
+            compact: false,
+        }
+    }
+
+    pub(crate) fn compact(labels: &[&str]) -> Self {
+        let labels = labels
+            .iter()
+            .map(|label| compact_label(label))
+            .collect::<Vec<_>>();
+        Self {
+            compact: true,
+            ..Self::new(&labels)
+        }
+    }
+
+    pub(crate) fn with_separator(self, separator: char) -> Self {
+        Self { separator, ..self }
+    }
+
+    pub(crate) fn label(&self, label: &str) -> String {
+        let label = if self.compact {
+            compact_label(label)
+        } else {
+            label
+        };
+        format!(
+            "{label:>width$} {} ",
+            self.separator,
+            width = self.width - 1
+        )
+    }
+
+    pub(crate) fn empty(&self) -> String {
+        format!("{}{}", " ".repeat(self.width), self.separator)
+    }
+
+    pub(crate) fn fits(&self, width: usize) -> bool {
+        width > self.width + 1
+    }
+
+    pub(crate) fn narrow(&self, size: Size) -> PaintOutput {
+        let mut output = vec![vec![Cell::BLANK; size.width]; size.height];
+        if let Some(first) = output.first_mut() {
+            let mut notice = plain(TOO_NARROW);
+            notice.resize(size.width, Cell::BLANK);
+            *first = notice;
+        }
+        output
+    }
+
+    pub(crate) fn seam(&self, joint: char) -> String {
+        let joint = match self.separator {
+            DEFAULT_SEPARATOR => joint,
+            separator => separator,
+        };
+        format!("{}{joint}", "─".repeat(self.width))
+    }
+}
+
+fn compact_label(label: &str) -> &str {
+    match label {
+        LOCAL_LABEL => "→",
+        REMOTE_LABEL => "←",
+        PREVIEW_LABEL => "=",
+        label => &label[..label.chars().next().map_or(0, char::len_utf8)],
+    }
+}
+
+pub(crate) fn traffic(connected_for: Duration, sent: usize, received: usize) -> String {
+    let seconds = connected_for.as_secs();
+    format!(
+        "{:02}:{:02}:{:02} ↑{sent} B ↓{received} B",
+        seconds / 3_600,
+        seconds / 60 % 60,
+        seconds % 60
+    )
+}
+
 pub(crate) struct Title {
-    addr: SocketAddr,
+    heading: String,
+    status: String,
+    activity: Option<Vec<char>>,
+    alerted: bool,
+    notice: Option<String>,
+    hover: Option<String>,
+    gutter: Gutter,
+}
+impl Title {
+    pub(crate) fn new(gutter: Gutter) -> Self {
+        Self {
+            heading: "HexCat.".to_string(),
+            status: String::new(),
+            activity: None,
+            alerted: false,
+            notice: None,
+            hover: None,
+            gutter,
+        }
+    }
 }
 impl Title {
-    pub(crate) fn new(addr: SocketAddr) -> Self {
-        Self { addr }
+    pub(crate) fn set_connected(&mut self, local_addr: SocketAddr, peer_addr: Option<SocketAddr>) {
+        let peer = match peer_addr {
+            Some(peer_addr) => peer_addr.to_string(),
+            None => "unknown peer".to_string(),
+        };
+        self.heading = format!("HexCat. Connected {local_addr} → {peer}.");
+    }
+
+    pub(crate) fn set_reviewing(&mut self, path: &Path) {
+        self.heading = format!("HexCat. Reviewing {} (not connected).", path.display());
+        self.status.clear();
+    }
+
+    pub(crate) fn set_connected_unix(&mut self) {
+        self.heading = "HexCat. Connected over a Unix socket.".to_string();
+    }
+
+    pub(crate) fn set_disconnected(&mut self, peer_addr: Option<SocketAddr>) {
+        self.heading = match peer_addr {
+            Some(peer_addr) => format!("HexCat. Disconnected from {peer_addr}."),
+            None => "HexCat. Disconnected from the Unix socket.".to_string(),
+        };
+        self.status.clear();
+    }
+
+    pub(crate) fn set_status(
+        &mut self,
+        connected_for: Duration,
+        sent: usize,
+        received: usize,
+        idle_left: Option<Duration>,
+    ) {
+        self.status = traffic(connected_for, sent, received);
+        // Rounded up, so it only says 0s once it's about to quit.
+        if let Some(idle_left) = idle_left {
+            let seconds = idle_left.as_secs() + u64::from(idle_left.subsec_nanos() > 0);
+            self.status.push_str(&format!(" idle {seconds}s"));
+        }
+    }
+
+    pub(crate) fn set_activity(&mut self, activity: Option<Vec<char>>) {
+        self.activity = activity;
+    }
+
+    pub(crate) fn set_alerted(&mut self, alerted: bool) {
+        self.alerted = alerted;
+    }
+
+    pub(crate) fn is_alerted(&self) -> bool {
+        self.alerted
+    }
+
+    pub(crate) fn set_notice(&mut self, notice: Option<String>) {
+        self.notice = notice;
+    }
+
+    pub(crate) fn has_notice(&self) -> bool {
+        self.notice.is_some()
+    }
+
+    pub(crate) fn set_hover(&mut self, byte: Option<u8>) -> bool {
+        let hover = byte.map(describe_byte);
+        let changed = hover != self.hover;
+        self.hover = hover;
+        changed
     }
 }
 impl Painter for Title {
     fn paint(&self, size: Size) -> Result<PaintOutput, AppError> {
+        if !self.gutter.fits(size.width) {
+            return Ok(self.gutter.narrow(size));
+        }
         let mut output: PaintOutput = Vec::with_capacity(size.height);
 
-        let mut title: Vec<char> = format!(
-            "HexCat. Connected to {} (on port {}).",
-            self.addr.ip(),
-            self.addr.port()
-        )
-        .chars()
-        .collect();
-        title.resize(size.width, ' ');
-        output.push(title);
-
-        let mut divider: Vec<char> = "────────┬".chars().collect();
-        divider.resize(size.width, '─');
-        output.push(divider);
+        let style = if self.alerted {
+            Style::Warning
+        } else {
+            Style::Plain
+        };
+        let mut title: PaintLine = styled(&self.heading, style);
+        let mut status = match (&self.notice, &self.hover) {
+            (Some(notice), _) => styled(notice, Style::Warning),
+            (None, Some(hover)) => plain(hover),
+            (None, None) => plain(&self.status),
+        };
+        let standing_in = self.notice.is_some() || self.hover.is_some();
+        if let (Some(activity), false, false) = (&self.activity, standing_in, status.is_empty()) {
+            let mut sparkline = activity
+                .iter()
+                .map(|c| Cell::new(*c, Style::Remote))
+                .collect::<PaintLine>();
+            sparkline.push(Cell::BLANK);
+            status.splice(0..0, sparkline);
+        }
+        if size.height > TITLE_ROWS {
+            title.resize(size.width, Cell::BLANK);
+            output.push(title);
+            status.resize(size.width, Cell::BLANK);
+            output.push(status);
+        } else {
+            // The heading matters more, so the status only goes in when there's room for both (and a space between).
+            if !status.is_empty() && title.len() + 1 + status.len() <= size.width {
+                title.resize(size.width - status.len(), Cell::BLANK);
+                title.extend(status);
+            } else if standing_in {
+                // Unless it's a notice (or the byte under the mouse), which wouldn't be anywhere else to be seen.
+                title = status;
+            }
+            title.resize(size.width, Cell::BLANK);
+            output.push(title);
+        }
 
-        output.resize(size.height, vec![' '; size.width]);
+        if output.len() < size.height {
+            output.resize(size.height - 1, vec![Cell::BLANK; size.width]);
+            let mut divider: PaintLine = plain(&self.gutter.seam('┬'));
+            divider.resize(size.width, Cell::new('─', Style::Plain));
+            output.push(divider);
+        }
+        output.truncate(size.height);
         Ok(output)
     }
 }
 
 pub(crate) struct Messages {
-    messages: Vec<MessageOrigin>,
-    connection: TcpStream,
+    session: Session,
+    show_local: bool,
+    stream: bool,
+    diff: bool,
+    radix: Radix,
+    fixed_width: bool,
+    scroll: usize,
+    highlight: Duration,
+    highlight_until: Option<Instant>,
+    inspecting: Option<usize>,
+    scrollback: usize,
+    held: usize,
+    newest_first: bool,
+    context: usize,
+    wrap_marker: String,
+    placeholder: bool,
+    grouped: bool,
+    unprintable: char,
+    controls: Controls,
+    local_columns: usize,
+    remote_columns: usize,
+    show_empty: bool,
+    checksum: Option<Checksum>,
+    verify_checksum: bool,
+    gutter: Gutter,
+    layout: RefCell<Layout>,
 }
 impl Messages {
-    pub(crate) fn new(connection: TcpStream) -> Self {
+    pub(crate) fn new(session: Session, gutter: Gutter) -> Self {
         Self {
-            messages: Vec::new(),
-            connection,
+            session,
+            show_local: true,
+            stream: false,
+            diff: false,
+            radix: Radix::default(),
+            fixed_width: false,
+            scroll: 0,
+            highlight: Duration::ZERO,
+            highlight_until: None,
+            inspecting: None,
+            scrollback: 0,
+            held: 0,
+            newest_first: false,
+            context: 0,
+            wrap_marker: DEFAULT_WRAP_MARKER.to_string(),
+            placeholder: false,
+            grouped: false,
+            unprintable: DEFAULT_UNPRINTABLE,
+            controls: Controls::default(),
+            local_columns: FIXED_COLUMNS,
+            remote_columns: FIXED_COLUMNS,
+            show_empty: true,
+            checksum: None,
+            verify_checksum: false,
+            gutter,
+            layout: RefCell::default(),
+        }
+    }
+
+    #[cfg(test)]
+    fn with_clock(mut self, clock: impl hexcat::clock::Clock + 'static) -> Self {
+        self.session.set_clock(clock);
+        self
+    }
+
+    // Echo servers send back everything we write, so every byte would otherwise show up twice.
+    pub(crate) fn toggle_local_echo(&mut self) {
+        self.show_local = !self.show_local;
+    }
+
+    pub(crate) fn toggle_diff(&mut self) {
+        self.diff = !self.diff;
+    }
+
+    pub(crate) fn toggle_stream(&mut self) {
+        self.stream = !self.stream;
+    }
+
+    pub(crate) fn toggle_grouped(&mut self) {
+        self.grouped = !self.grouped;
+    }
+
+    pub(crate) fn set_radix(&mut self, radix: Radix) {
+        self.radix = radix;
+        self.scroll = 0;
+    }
+
+    pub(crate) fn set_fixed_width(&mut self, fixed_width: bool) {
+        self.fixed_width = fixed_width;
+    }
+
+    pub(crate) fn set_wrap_marker(&mut self, marker: &str) {
+        self.wrap_marker = marker.chars().take(self.gutter.width - 1).collect();
+    }
+
+    pub(crate) fn set_newest_first(&mut self, newest_first: bool) {
+        self.newest_first = newest_first;
+    }
+
+    pub(crate) fn set_placeholder(&mut self, placeholder: bool) {
+        self.placeholder = placeholder;
+    }
+
+    pub(crate) fn set_unprintable(&mut self, unprintable: char) {
+        self.unprintable = unprintable;
+    }
+
+    pub(crate) fn set_fixed_columns(&mut self, local: usize, remote: usize) {
+        self.local_columns = local;
+        self.remote_columns = remote;
+        self.scroll = 0;
+    }
+
+    pub(crate) fn set_controls(&mut self, controls: Controls) {
+        self.controls = controls;
+    }
+
+    pub(crate) fn set_show_empty(&mut self, show_empty: bool) {
+        self.show_empty = show_empty;
+    }
+
+    pub(crate) fn set_diff(&mut self, diff: bool) {
+        self.diff = diff;
+    }
+
+    // Scrolls by a whole byte at a time, so the columns never get cut in half.
+    pub(crate) fn scroll_left(&mut self) -> bool {
+        let step = self.radix.width() + 1;
+        let changed = self.fixed_width && self.scroll > 0;
+        self.scroll = self.scroll.saturating_sub(step);
+        changed
+    }
+
+    pub(crate) fn scroll_right(&mut self) -> bool {
+        let step = self.radix.width() + 1;
+        // Never further than the start of the character column.
+        let limit = max(self.local_columns, self.remote_columns) * step;
+        let changed = self.fixed_width && self.scroll < limit;
+        if changed {
+            self.scroll = min(self.scroll + step, limit);
         }
+        changed
+    }
+
+    pub(crate) fn set_highlight(&mut self, highlight: Duration) {
+        self.highlight = highlight;
+    }
+
+    pub(crate) fn highlight_ends(&self) -> Option<Instant> {
+        self.highlight_until
+            .filter(|until| *until > self.session.clock().now())
     }
 
-    pub(crate) fn handle_message(&mut self, message: MessageOrigin) {
-        if let MessageOrigin::Local(message) = &message {
-            _ = self.connection.write_all(message);
+    fn added(&mut self) {
+        if !self.highlight.is_zero() {
+            self.highlight_until = Some(self.session.clock().now() + self.highlight);
         }
-        self.messages.push(message);
     }
 
-    pub(crate) fn listen(mut connection: TcpStream, sink: Sender<TcpMessage>) {
-        let mut buffer = [0u8; BUFFER_SIZE];
-        let mut message: Vec<u8> = vec![];
-        'connected: loop {
-            match connection.read(&mut buffer) {
-                Ok(0) => break 'connected,
-                Ok(n) => {
-                    message.extend_from_slice(&buffer[..n]);
-                    _ = sink.send(message.clone());
-                    message.truncate(0);
-                }
-                Err(ref err) if err.kind() == ErrorKind::WouldBlock => (),
-                Err(_) => break 'connected,
+    fn emphasize(&self, origin: &MessageOrigin, mut lines: Vec<PaintLine>) -> Vec<PaintLine> {
+        let newest = self
+            .session
+            .messages()
+            .last()
+            .is_some_and(|last| std::ptr::eq(&last.origin, origin));
+        if newest && self.highlight_ends().is_some() {
+            lines
+                .iter_mut()
+                .flatten()
+                .filter(|cell| **cell != Cell::BLANK)
+                .for_each(|cell| cell.bold = true);
+        }
+        lines
+    }
+
+    fn previous(&self, message: &Message) -> Option<&Message> {
+        let messages = self.session.messages();
+        let index = messages
+            .binary_search_by_key(&message.sequence, |message| message.sequence)
+            .ok()?;
+        messages[..index].iter().rev().find(|earlier| {
+            matches!(
+                (&earlier.origin, &message.origin),
+                (MessageOrigin::Local(_), MessageOrigin::Local(_))
+                    | (MessageOrigin::Remote(_), MessageOrigin::Remote(_))
+            )
+        })
+    }
+
+    pub(crate) fn set_context(&mut self, context: usize) {
+        self.context = context;
+    }
+
+    fn inspect_previous(&mut self) {
+        let before = self.inspecting.unwrap_or(self.session.messages().len());
+        let previous = (0..before).rev().find(|index| self.is_visible_at(*index));
+        self.inspecting = previous.or(self.inspecting);
+    }
+
+    fn inspect_next(&mut self) {
+        if let Some(current) = self.inspecting {
+            let next = (current + 1..self.session.messages().len())
+                .find(|index| self.is_visible_at(*index));
+            self.inspecting = next.or(Some(current));
+        }
+    }
+
+    pub(crate) fn stop_inspecting(&mut self) -> bool {
+        self.inspecting.take().is_some()
+    }
+
+    pub(crate) fn histogram(&self) -> Histogram {
+        let messages = self.session.messages();
+        let mut histogram = Histogram::new(self.radix, self.inspecting.is_some());
+        let counted = match self.inspecting {
+            Some(index) => &messages[index..=index],
+            None => messages,
+        };
+        for message in counted {
+            histogram.count(&message.origin);
+        }
+        histogram
+    }
+
+    pub(crate) fn inspected(&self) -> std::result::Result<&[u8], String> {
+        let Some(index) = self.inspecting else {
+            return Err("Pick a message with Up and Down first.".to_string());
+        };
+        match &self.session.messages()[index].origin {
+            MessageOrigin::Local(message) | MessageOrigin::Remote(message) => Ok(message),
+            MessageOrigin::Secret(_) => {
+                Err("Only the length of a secret message was kept.".to_string())
             }
         }
     }
-}
-impl Painter for Messages {
-    fn paint(&self, size: Size) -> Result<PaintOutput, AppError> {
-        fn vec_to_line(width: usize, lhs: &str, message: &[u8], rhs: &str) -> Vec<char> {
-            let mut human_readable: String = message
-                .iter()
-                .map(|byte| format!("{byte:02x} "))
-                .collect::<String>();
-            human_readable.truncate(width - lhs.len() - rhs.len());
-            let mut line = format!("{lhs}{human_readable}{rhs}")
-                .chars()
-                .collect::<Vec<_>>();
-            line.resize(width, ' ');
-            line
+
+    fn is_visible_at(&self, index: usize) -> bool {
+        self.is_visible(&self.session.messages()[index].origin)
+    }
+
+    // Up and down are about where things are on screen, which is the other way around with the newest on top.
+    pub(crate) fn page_up(&mut self, size: Size) -> std::result::Result<(), String> {
+        match self.newest_first {
+            true => self.page_newer(size),
+            false => self.page_older(size),
         }
+    }
 
-        let mut output: PaintOutput = self
-            .messages
-            .iter()
-            .rev()
-            .take(size.height - 1)
-            .rev()
-            .map(|origin| match origin {
-                MessageOrigin::Local(message) => {
-                    vec_to_line(size.width, "  LOCAL │ ", message, " ")
-                }
-                MessageOrigin::Remote(message) => {
-                    vec_to_line(size.width, " REMOTE │ ", message, " ")
-                }
-            })
-            .collect::<Vec<_>>();
+    pub(crate) fn page_down(&mut self, size: Size) -> std::result::Result<(), String> {
+        match self.newest_first {
+            true => self.page_older(size),
+            false => self.page_newer(size),
+        }
+    }
 
-        let mut empty_line: Vec<char> = "        │".chars().collect();
-        empty_line.resize(size.width, ' ');
-        output.resize(size.height, empty_line);
-        Ok(output)
+    // Either way, inspecting starts from the newest message.
+    pub(crate) fn inspect_up(&mut self) {
+        match self.newest_first {
+            true if self.inspecting.is_some() => self.inspect_next(),
+            _ => self.inspect_previous(),
+        }
     }
-}
 
-pub(crate) struct Input {
-    input: Vec<char>,
-    prompt: String,
-}
-impl Input {
-    pub(crate) fn new() -> Self {
-        Self {
-            input: Vec::new(),
-            prompt: " Input: │ ".to_string(),
+    pub(crate) fn inspect_down(&mut self) {
+        match self.newest_first {
+            true => self.inspect_previous(),
+            false => self.inspect_next(),
         }
     }
 
-    pub(crate) fn drain_user_message(&mut self) -> Option<TcpMessage> {
-        let input = self
-            .input
-            .clone()
-            .into_iter()
-            .filter(char::is_ascii_hexdigit)
-            .collect::<Vec<char>>();
-        if input.len() % 2 != 0 {
-            return None;
+    // No further than the oldest row, so paging back on a short history doesn't strand the view beyond it.
+    fn page_older(&mut self, size: Size) -> std::result::Result<(), String> {
+        let limit = scrollback_limit(self.line_count(size.width), size.height);
+        if self.scrollback >= limit {
+            self.scrollback = limit;
+            return Err("Already at the oldest message.".to_string());
         }
+        self.scrollback = min(self.scrollback + page(size.height), limit);
+        Ok(())
+    }
 
-        let hex = input
-            .chunks(2)
-            .map(|double_hex_chars| double_hex_chars.iter().collect::<String>())
-            .filter_map(|hex_string| u8::from_str_radix(&hex_string, 16).ok())
-            .collect::<Vec<_>>();
-        self.input.truncate(0);
-        Some(hex)
+    pub(crate) fn hold_back(&mut self, rows: usize) {
+        self.scrollback += rows;
+        self.held += rows;
     }
 
-    pub(crate) fn handle_key(&mut self, key: Key) -> bool {
-        match key {
-            Key::Char(c) => {
-                if c.is_ascii_hexdigit() || c == ' ' {
-                    self.input.push(c);
-                    return true;
-                }
+    pub(crate) fn is_holding(&self) -> bool {
+        min(self.held, self.scrollback) > 0
+    }
+
+    pub(crate) fn advance(&mut self, size: Size) {
+        // Until the pane has filled up, there's nowhere further back for the view to be held.
+        let limit = scrollback_limit(self.line_count(size.width), size.height);
+        self.scrollback = min(self.scrollback, limit);
+        self.held = min(self.held, self.scrollback);
+        let rows = min(self.held, page(size.height));
+        self.scrollback -= rows;
+        self.held -= rows;
+    }
+
+    fn page_newer(&mut self, size: Size) -> std::result::Result<(), String> {
+        if self.scrollback == 0 {
+            return Err("Already at the newest message.".to_string());
+        }
+        // The history might have been shown taller (or been cleared) since it was scrolled back.
+        let limit = scrollback_limit(self.line_count(size.width), size.height);
+        self.scrollback = min(self.scrollback, limit).saturating_sub(page(size.height));
+        Ok(())
+    }
+
+    // Anything that changes how many rows messages take (or the history changing underneath it) means counting it all
+    // again; otherwise only new messages are placed.
+    fn layout(&self, width: usize) -> Ref<'_, Layout> {
+        let key = LayoutKey {
+            fixed_width: self.fixed_width,
+            stream: self.stream,
+            show_local: self.show_local,
+            show_empty: self.show_empty,
+            local_bytes: self.bytes_per_line(width, Direction::Local),
+            remote_bytes: self.bytes_per_line(width, Direction::Remote),
+        };
+        let messages = self.session.messages();
+        let mut layout = self.layout.borrow_mut();
+        let last_placed = layout
+            .placed
+            .checked_sub(1)
+            .and_then(|index| messages.get(index));
+        if layout.key != Some(key) || last_placed.map(|message| message.sequence) != layout.last {
+            *layout = Layout {
+                key: Some(key),
+                ..Layout::default()
+            };
+        }
+        for index in layout.placed..messages.len() {
+            self.place(width, &mut layout, index);
+        }
+        layout.placed = messages.len();
+        layout.last = messages.last().map(|message| message.sequence);
+        drop(layout);
+        self.layout.borrow()
+    }
+
+    // In the stream layout, everything sent the same way in a row is joined back together (reads aren't messages as far
+    // as TCP is concerned) and wrapped as one.
+    fn place(&self, width: usize, layout: &mut Layout, index: usize) {
+        let message = &self.session.messages()[index];
+        if !self.is_visible(&message.origin) {
+            return;
+        }
+        let (style, bytes) = match &message.origin {
+            _ if !self.stream => {
+                let rows = self.block_rows(width, &Block::Message(message, true));
+                return layout.push(Placement::Message(index), rows);
             }
-            Key::Backspace => {
-                if self.input.pop().is_some() {
-                    return true;
-                }
+            MessageOrigin::Secret(length) => return layout.push(Placement::Secret(*length), 1),
+            MessageOrigin::Local(message) => (Style::Local, message),
+            MessageOrigin::Remote(message) => (Style::Remote, message),
+        };
+        let rows = |length| self.wrapped_rows(width, length, Direction::of_run(style));
+        if let Some(Placed {
+            rows: run_rows,
+            placement: Placement::Run(run_style, parts, length),
+            ..
+        }) = layout.blocks.last_mut()
+        {
+            if *run_style == style {
+                parts.push((index, *length));
+                *length += bytes.len();
+                layout.rows = layout.rows - *run_rows + rows(*length);
+                *run_rows = rows(*length);
+                return;
             }
-            _ => (),
         }
-        false
+        let placement = Placement::Run(style, vec![(index, 0)], bytes.len());
+        layout.push(placement, rows(bytes.len()));
     }
 
-    pub(crate) fn listen(sink: Sender<Key>) -> Result<(), AppError> {
-        loop {
-            if let Some(key) = Terminal::read_key()? {
-                sink.send(key)
-                    .into_report()
-                    .attach_printable("Could not communicate user input to main thread.")
-                    .change_context(AppError::ChannelBroken)?;
+    fn tail_blocks(&self) -> Vec<Block<'_>> {
+        let mut blocks = Vec::new();
+        if !self.session.pending().is_empty() {
+            // Still-accumulating data is shown dimmed so it isn't mistaken for a finished frame.
+            blocks.push(match self.stream {
+                true => Block::Run(Style::Pending, vec![self.session.pending()]),
+                false => Block::Pending,
+            });
+        }
+        blocks.extend(self.session.queued().map(Block::Queued));
+        blocks
+    }
+
+    fn placed_block<'a>(&'a self, layout: &'a Layout, index: usize) -> Block<'a> {
+        let messages = self.session.messages();
+        let remote = |placed: &Placed| match placed.placement {
+            Placement::Message(index) => {
+                Some(matches!(messages[index].origin, MessageOrigin::Remote(_)))
+            }
+            _ => None,
+        };
+        match &layout.blocks[index].placement {
+            Placement::Message(message) => {
+                let above = match self.newest_first {
+                    true => layout.blocks.get(index + 1),
+                    false => index.checked_sub(1).map(|index| &layout.blocks[index]),
+                };
+                let labelled =
+                    !self.grouped || above.and_then(remote) != remote(&layout.blocks[index]);
+                Block::Message(&messages[*message], labelled)
             }
+            Placement::Secret(length) => Block::Secret(*length),
+            Placement::Run(style, parts, _) => Block::Run(
+                *style,
+                parts
+                    .iter()
+                    .filter_map(|(index, _)| match &messages[*index].origin {
+                        MessageOrigin::Local(bytes) | MessageOrigin::Remote(bytes) => {
+                            Some(bytes.as_slice())
+                        }
+                        MessageOrigin::Secret(_) => None,
+                    })
+                    .collect(),
+            ),
         }
     }
 
-    pub(crate) fn get_cursor_x_position(&self, terminal_width: usize) -> u16 {
-        let max_input_width = terminal_width - self.prompt.len() - 1;
-        (self.prompt.len() + min(self.input.len(), max_input_width) - 2) as u16
+    fn placed_lines(
+        &self,
+        width: usize,
+        layout: &Layout,
+        index: usize,
+        skip: usize,
+        take: usize,
+    ) -> PaintOutput {
+        let Placement::Run(style, parts, length) = &layout.blocks[index].placement else {
+            let lines = self.block_lines(width, &self.placed_block(layout, index));
+            return lines.into_iter().skip(skip).take(take).collect();
+        };
+        let direction = Direction::of_run(*style);
+        let per_line = self.bytes_per_line(width, direction);
+        let (start, end) = (
+            min(skip * per_line, *length),
+            min((skip + take) * per_line, *length),
+        );
+        let first = parts
+            .partition_point(|(_, offset)| *offset <= start)
+            .saturating_sub(1);
+        let mut bytes = Vec::with_capacity(end - start);
+        for (message, offset) in &parts[first..] {
+            if *offset >= end {
+                break;
+            }
+            if let MessageOrigin::Local(message) | MessageOrigin::Remote(message) =
+                &self.session.messages()[*message].origin
+            {
+                let from = start.saturating_sub(*offset).min(message.len());
+                let to = (end - offset).min(message.len());
+                bytes.extend_from_slice(&message[from..to]);
+            }
+        }
+        let label = match (skip, direction) {
+            (1.., _) => self.gutter.label(&self.wrap_marker),
+            (0, Direction::Local) => self.gutter.label(LOCAL_LABEL),
+            (0, Direction::Remote) => self.gutter.label(REMOTE_LABEL),
+        };
+        let mut lines = self.wrapped_lines(width, &label, &bytes, &plain(" "), *style, direction);
+        lines.truncate(take);
+        lines
     }
-}
-impl Painter for Input {
-    fn paint(&self, size: Size) -> Result<PaintOutput, AppError> {
-        let mut output: PaintOutput = Vec::with_capacity(size.height);
 
-        let mut divider: Vec<char> = "────────┼".chars().collect();
-        divider.resize(size.width, '─');
-        output.push(divider);
+    fn block_rows(&self, width: usize, block: &Block) -> usize {
+        match block {
+            Block::Message(message, _) if self.fixed_width => match &message.origin {
+                MessageOrigin::Local(bytes) => {
+                    self.wrapped_rows(width, bytes.len(), Direction::Local)
+                }
+                MessageOrigin::Remote(bytes) => {
+                    self.wrapped_rows(width, bytes.len(), Direction::Remote)
+                }
+                MessageOrigin::Secret(_) => 1,
+            },
+            Block::Pending if self.fixed_width => {
+                self.wrapped_rows(width, self.session.pending().len(), Direction::Remote)
+            }
+            Block::Queued(Message {
+                origin: MessageOrigin::Local(message),
+                ..
+            }) => self.wrapped_rows(width, message.len(), Direction::Local),
+            Block::Run(style, run) => self.wrapped_rows(
+                width,
+                run.iter().map(|bytes| bytes.len()).sum(),
+                Direction::of_run(*style),
+            ),
+            Block::Message(..) | Block::Pending | Block::Queued(_) | Block::Secret(_) => 1,
+        }
+    }
+
+    fn wrapped_rows(&self, width: usize, length: usize, direction: Direction) -> usize {
+        max(1, length.div_ceil(self.bytes_per_line(width, direction)))
+    }
+
+    fn block_lines(&self, width: usize, block: &Block) -> PaintOutput {
+        let local_label = self.gutter.label(LOCAL_LABEL);
+        let remote_label = self.gutter.label(REMOTE_LABEL);
+        match block {
+            Block::Message(message, labelled) => {
+                let origin = &message.origin;
+                let mut lines = self.message_lines(width, origin);
+                if let Some(previous) = self.diff.then(|| self.previous(message)).flatten() {
+                    mark_changes(&mut lines, &self.message_lines(width, &previous.origin));
+                }
+                let mut lines = self.emphasize(origin, lines);
+                if !labelled {
+                    if let Some(first) = lines.first_mut() {
+                        first
+                            .iter_mut()
+                            .take(self.gutter.width - 1)
+                            .for_each(|cell| *cell = Cell::BLANK);
+                    }
+                }
+                lines
+            }
+            Block::Pending if self.fixed_width => self.wrapped_lines(
+                width,
+                &remote_label,
+                self.session.pending(),
+                &plain(" "),
+                Style::Pending,
+                Direction::Remote,
+            ),
+            Block::Pending => vec![self.vec_to_line(
+                width,
+                &remote_label,
+                self.session.pending(),
+                &plain(" "),
+                Style::Pending,
+                Direction::Remote,
+            )],
+            Block::Queued(Message { origin, .. }) => {
+                let mut rows = match origin {
+                    MessageOrigin::Local(message) => self.wrapped_lines(
+                        width,
+                        &local_label,
+                        message,
+                        &plain("(queued) "),
+                        Style::Pending,
+                        Direction::Local,
+                    ),
+                    _ => self.message_lines(width, origin),
+                };
+                rows.iter_mut()
+                    .flatten()
+                    .filter(|cell| cell.style != Style::Plain)
+                    .for_each(|cell| cell.style = Style::Pending);
+                rows
+            }
+            Block::Run(style, run) => {
+                let direction = Direction::of_run(*style);
+                let label = match direction {
+                    Direction::Local => &local_label,
+                    Direction::Remote => &remote_label,
+                };
+                self.wrapped_lines(width, label, &run.concat(), &plain(" "), *style, direction)
+            }
+            Block::Secret(length) => vec![self.secret_line(width, &local_label, *length)],
+        }
+    }
 
-        let max_input_length: usize = size.width - self.prompt.len() - 1;
-        let mut input = self
-            .input
+    pub(crate) fn line_count(&self, width: usize) -> usize {
+        let tail: usize = self
+            .tail_blocks()
             .iter()
-            .rev()
-            .take(max_input_length)
-            .rev()
+            .map(|block| self.block_rows(width, block))
+            .sum();
+        self.layout(width).rows + tail
+    }
+
+    fn lines_between(&self, width: usize, from: usize, to: usize) -> PaintOutput {
+        let layout = self.layout(width);
+        let tail = self.tail_blocks();
+        let tail_rows = tail
+            .iter()
+            .map(|block| self.block_rows(width, block))
             .collect::<Vec<_>>();
-        input.resize(max_input_length, &' ');
+        let total = layout.rows + tail_rows.iter().sum::<usize>();
+        let to = min(to, total);
+        if from >= to {
+            return Vec::new();
+        }
+        // Whole blocks swap places with the newest on top, but each still reads top to bottom.
+        let (oldest, newest) = match self.newest_first {
+            true => (total - to, total - from),
+            false => (from, to),
+        };
+        let shown = |row: usize, rows: usize| {
+            let top = match self.newest_first {
+                true => total - row - rows,
+                false => row,
+            };
+            let skip = from.saturating_sub(top);
+            (skip, min(to, top + rows) - max(from, top))
+        };
+
+        let mut pieces = Vec::new();
+        let first = layout
+            .blocks
+            .partition_point(|placed| placed.row + placed.rows <= oldest);
+        for (index, placed) in layout.blocks.iter().enumerate().skip(first) {
+            if placed.row >= newest {
+                break;
+            }
+            let (skip, take) = shown(placed.row, placed.rows);
+            pieces.push(self.placed_lines(width, &layout, index, skip, take));
+        }
+        let mut row = layout.rows;
+        for (block, rows) in tail.iter().zip(tail_rows) {
+            if row < newest && row + rows > oldest {
+                let (skip, take) = shown(row, rows);
+                let lines = self.block_lines(width, block);
+                pieces.push(lines.into_iter().skip(skip).take(take).collect());
+            }
+            row += rows;
+        }
+        if self.newest_first {
+            pieces.reverse();
+        }
+        pieces.concat()
+    }
+
+    // Rows hidden above (or below, when scrolled back) are counted on an indicator row instead.
+    fn scrolled_rows(&self, size: Size) -> (usize, usize, usize, usize) {
+        let (total, height) = (self.line_count(size.width), size.height);
+        let scrollback = min(self.scrollback, scrollback_limit(total, height));
+        let mut rows = height;
+        if scrollback > 0 {
+            rows = rows.saturating_sub(1);
+        }
+        let end = total - scrollback;
+        if end > rows {
+            rows = rows.saturating_sub(1);
+        }
+        (end.saturating_sub(rows), end, scrollback, total)
+    }
 
-        let mut line: Vec<char> = Vec::new();
-        line.extend(self.prompt.chars());
-        line.extend(input);
-        output.push(line);
+    fn scrolled_lines(&self, size: Size) -> PaintOutput {
+        let height = size.height;
+        let (start, end, scrollback, total) = self.scrolled_rows(size);
 
-        output.resize(size.height, vec![' '; size.width]);
-        Ok(output)
+        let indicator = |text: String| {
+            let mut line = plain(&format!("{} {text}", self.gutter.empty()));
+            line.resize(size.width, Cell::BLANK);
+            line
+        };
+        let (arrow_older, arrow_newer) = if self.newest_first {
+            ('▼', '▲')
+        } else {
+            ('▲', '▼')
+        };
+        let older =
+            (start > 0 && height > 0).then(|| indicator(format!("{arrow_older} {start} more")));
+        let newer = (scrollback > 0 && height > 0)
+            .then(|| indicator(format!("{arrow_newer} {scrollback} newer")));
+        // Counted from the newest end either way, but the rows themselves are in the order they're shown.
+        let (from, to, top, bottom) = if self.newest_first {
+            (total - end, total - start, newer, older)
+        } else {
+            (start, end, older, newer)
+        };
+        let mut lines = self.lines_between(size.width, from, to);
+        if let Some(top) = top {
+            lines.insert(0, top);
+        }
+        lines.extend(bottom);
+        lines
+    }
+    pub(crate) fn set_receiving(
+        &mut self,
+        framing: Framing,
+        max_frame: usize,
+        strip: (usize, usize),
+        filter: Option<Filter>,
+    ) {
+        self.session.set_framing(framing, max_frame);
+        self.session.set_strip(strip.0, strip.1);
+        match filter {
+            Some(filter) => self.session.set_filter(move |frame| filter.apply(frame)),
+            None => self.session.clear_filter(),
+        }
+    }
+
+    pub(crate) fn set_checksum(&mut self, checksum: Option<Checksum>, verify: bool) {
+        self.checksum = checksum;
+        self.verify_checksum = verify;
+    }
+
+    pub(crate) fn handle_message(&mut self, message: MessageOrigin) -> Result<(), AppError> {
+        match message {
+            MessageOrigin::Remote(chunk) => {
+                // Frames going through a filter are only added once they're back (see `take_filtered`).
+                if self.session.receive(&chunk) > 0 && !self.session.is_filtering() {
+                    self.added();
+                }
+                return Ok(());
+            }
+            MessageOrigin::Local(message) => self.session.send(&message)?,
+            // Only its length is known, so there's nothing to send.
+            secret @ MessageOrigin::Secret(_) => self.session.record(secret),
+        }
+        self.sent();
+        Ok(())
+    }
+
+    pub(crate) fn handle_secret(&mut self, message: TcpMessage) -> Result<(), AppError> {
+        self.session.send_secret(&message)?;
+        self.sent();
+        Ok(())
+    }
+
+    fn sent(&mut self) {
+        // Whatever was just sent should be on screen, not somewhere below a page of old history.
+        self.scrollback = 0;
+        self.held = 0;
+        self.added();
+    }
+
+    pub(crate) fn take_notice(&mut self) -> Option<String> {
+        self.session.take_notice()
+    }
+
+    pub(crate) fn take_filtered(&mut self) -> bool {
+        let taken = self.session.take_filtered() > 0;
+        if taken {
+            self.added();
+        }
+        taken
+    }
+
+    pub(crate) fn is_filtering(&self) -> bool {
+        self.session.is_filtering()
+    }
+
+    pub(crate) fn retry_queued(&mut self) -> Result<bool, AppError> {
+        if self.session.queued().next().is_none() {
+            return Ok(false);
+        }
+        self.session.retry()
+    }
+
+    pub(crate) fn byte_at(&self, size: Size, position: Position) -> Result<Option<u8>, AppError> {
+        let cell = |output: PaintOutput, y: usize| {
+            output
+                .get(y)
+                .and_then(|line| line.get(position.x))
+                .and_then(|cell| cell.byte)
+        };
+        if self.inspecting.is_some() || !self.gutter.fits(size.width) {
+            return Ok(cell(self.paint(size)?, position.y));
+        }
+        if position.y >= size.height {
+            return Ok(None);
+        }
+        // Only the one row under the position is laid out, the same way `scrolled_lines` would.
+        let (start, end, scrollback, total) = self.scrolled_rows(size);
+        let (from, to, above) = if self.newest_first {
+            (total - end, total - start, scrollback > 0)
+        } else {
+            (start, end, start > 0)
+        };
+        let row = match position.y.checked_sub(usize::from(above)) {
+            Some(row) if from + row < to => from + row,
+            _ => return Ok(None),
+        };
+        Ok(cell(self.lines_between(size.width, row, row + 1), 0))
+    }
+
+    pub(crate) fn discard_queued(&mut self) {
+        self.session.discard_queued();
+    }
+
+    pub(crate) fn has_queued(&self) -> bool {
+        self.session.queued().next().is_some()
+    }
+
+    pub(crate) fn traffic(&self) -> (usize, usize) {
+        (self.session.sent(), self.session.received())
+    }
+
+    pub(crate) fn clear(&mut self) {
+        self.session.clear();
+        self.inspecting = None;
+    }
+
+    pub(crate) fn replace_connection(
+        &mut self,
+        connection: Box<dyn Transport>,
+    ) -> Box<dyn Transport> {
+        self.session.replace_connection(connection)
+    }
+
+    // A line a message: its sequence number, seconds since the Unix epoch, direction and bytes.
+    pub(crate) fn to_log(&self) -> String {
+        self.session
+            .messages()
+            .iter()
+            .map(|message| {
+                let time = message
+                    .timestamp
+                    .duration_since(UNIX_EPOCH)
+                    .unwrap_or_default();
+                let hex = |bytes: &TcpMessage| {
+                    bytes
+                        .iter()
+                        .map(|byte| format!("{byte:02x}"))
+                        .collect::<Vec<_>>()
+                        .join(" ")
+                };
+                let (label, rest) = match &message.origin {
+                    MessageOrigin::Local(bytes) => (LOCAL_LABEL, hex(bytes)),
+                    MessageOrigin::Remote(bytes) => (REMOTE_LABEL, hex(bytes)),
+                    MessageOrigin::Secret(length) => (SECRET_LABEL, length.to_string()),
+                };
+                format!(
+                    "{} {}.{:09} {label} {rest}\n",
+                    message.sequence,
+                    time.as_secs(),
+                    time.subsec_nanos()
+                )
+            })
+            .collect()
+    }
+
+    pub(crate) fn load_log(&mut self, log: &str) -> std::result::Result<(), String> {
+        for (index, line) in log.lines().enumerate() {
+            if line.trim().is_empty() {
+                continue;
+            }
+            let invalid = |what: &str| format!("Line {}: invalid {what}.", index + 1);
+            let mut fields = line.splitn(4, ' ');
+            let sequence = fields
+                .next()
+                .and_then(|sequence| sequence.parse().ok())
+                .ok_or_else(|| invalid("sequence number"))?;
+            let timestamp = fields
+                .next()
+                .and_then(|time| time.split_once('.'))
+                .filter(|(_, nanos)| nanos.len() == 9)
+                .and_then(|(seconds, nanos)| {
+                    let nanos = nanos.parse().ok()?;
+                    Some(UNIX_EPOCH + Duration::new(seconds.parse().ok()?, nanos))
+                })
+                .ok_or_else(|| invalid("timestamp"))?;
+            let label = fields.next().unwrap_or("");
+            let rest = fields.next().unwrap_or("");
+            let origin = match label {
+                SECRET_LABEL => {
+                    MessageOrigin::Secret(rest.trim().parse().map_err(|_| invalid("length"))?)
+                }
+                LOCAL_LABEL | REMOTE_LABEL => {
+                    let bytes = parse_hex(&rest.chars().collect::<Vec<_>>())
+                        .ok_or_else(|| invalid("hex"))?;
+                    match label {
+                        LOCAL_LABEL => MessageOrigin::Local(bytes),
+                        _ => MessageOrigin::Remote(bytes),
+                    }
+                }
+                _ => {
+                    return Err(format!(
+                        "Line {}: unknown direction \"{label}\".",
+                        index + 1
+                    ))
+                }
+            };
+            self.session.restore(Message {
+                origin,
+                timestamp,
+                sequence,
+                status: Status::Recorded,
+            });
+        }
+        Ok(())
+    }
+
+    pub(crate) fn painted_log(&self) -> PaintOutput {
+        self.session
+            .messages()
+            .iter()
+            .map(|message| &message.origin)
+            .flat_map(|origin| {
+                let length = match origin {
+                    MessageOrigin::Local(message) | MessageOrigin::Remote(message) => message.len(),
+                    MessageOrigin::Secret(_) => 0,
+                };
+                // Wide enough that nothing gets cut off; the padding is trimmed again below.
+                let width = self.gutter.label(LOCAL_LABEL).chars().count()
+                    + max(length, max(self.local_columns, self.remote_columns))
+                        * (self.radix.width() + 1 + self.controls.width())
+                    + 64;
+                self.message_lines(width, origin)
+            })
+            .map(|mut line| {
+                while line.last() == Some(&Cell::BLANK) {
+                    line.pop();
+                }
+                line
+            })
+            .collect()
+    }
+
+    pub(crate) fn dump(&self, include_local: bool) -> Vec<u8> {
+        self.session.dump(include_local)
+    }
+}
+impl Messages {
+    fn fixed_columns(&self, direction: Direction) -> usize {
+        match direction {
+            Direction::Local => self.local_columns,
+            Direction::Remote => self.remote_columns,
+        }
+    }
+
+    fn is_visible(&self, origin: &MessageOrigin) -> bool {
+        match origin {
+            MessageOrigin::Remote(message) => self.show_empty || !message.is_empty(),
+            MessageOrigin::Local(_) | MessageOrigin::Secret(_) => self.show_local,
+        }
+    }
+
+    fn bytes_per_line(&self, width: usize, direction: Direction) -> usize {
+        if self.fixed_width {
+            return self.fixed_columns(direction);
+        }
+        let label_width = self.gutter.label(LOCAL_LABEL).chars().count();
+        max(
+            1,
+            width.saturating_sub(label_width + 1) / (self.radix.width() + 1),
+        )
+    }
+
+    fn message_lines(&self, width: usize, origin: &MessageOrigin) -> Vec<PaintLine> {
+        match origin {
+            MessageOrigin::Local(message) if self.fixed_width => self.wrapped_lines(
+                width,
+                &self.gutter.label(LOCAL_LABEL),
+                message,
+                &plain(" "),
+                Style::Local,
+                Direction::Local,
+            ),
+            MessageOrigin::Remote(message) if self.fixed_width => self.wrapped_lines(
+                width,
+                &self.gutter.label(REMOTE_LABEL),
+                message,
+                &self.checksum_suffix(message),
+                Style::Remote,
+                Direction::Remote,
+            ),
+            _ => vec![self.message_line(width, origin)],
+        }
+    }
+
+    fn message_line(&self, width: usize, origin: &MessageOrigin) -> PaintLine {
+        match origin {
+            MessageOrigin::Local(message) => self.vec_to_line(
+                width,
+                &self.gutter.label(LOCAL_LABEL),
+                message,
+                &plain(" "),
+                Style::Local,
+                Direction::Local,
+            ),
+            MessageOrigin::Remote(message) => match self.session.framing().header(message) {
+                Some((header_width, length)) => {
+                    self.header_line(width, message.split_at(header_width), length)
+                }
+                None => self.vec_to_line(
+                    width,
+                    &self.gutter.label(REMOTE_LABEL),
+                    message,
+                    &self.checksum_suffix(message),
+                    Style::Remote,
+                    Direction::Remote,
+                ),
+            },
+            MessageOrigin::Secret(length) => {
+                self.secret_line(width, &self.gutter.label(LOCAL_LABEL), *length)
+            }
+        }
+    }
+
+    fn header_line(
+        &self,
+        width: usize,
+        (header, payload): (&[u8], &[u8]),
+        length: u64,
+    ) -> PaintLine {
+        let label = self.gutter.label(REMOTE_LABEL);
+        let formatted = header
+            .iter()
+            .map(|byte| self.radix.format(*byte))
+            .collect::<Vec<_>>()
+            .join(" ");
+        let segment = match payload.len() as u64 {
+            shown if shown == length => format!("[{formatted}: {length}] "),
+            shown => format!("[{formatted}: {length}, {shown} shown] "),
+        };
+        let mut line = self.vec_to_line(
+            width,
+            &format!("{label}{segment}"),
+            payload,
+            &self.checksum_suffix(payload),
+            Style::Remote,
+            Direction::Remote,
+        );
+        let start = label.chars().count();
+        let end = min(start + segment.chars().count(), line.len());
+        line.iter_mut()
+            .take(end)
+            .skip(start)
+            .for_each(|cell| cell.style = Style::Header);
+        // Past the opening bracket.
+        if let Some(cells) = line.get_mut(start + 1..end) {
+            self.tag_bytes(cells, header, 0);
+        }
+        line
+    }
+
+    fn secret_line(&self, width: usize, label: &str, length: usize) -> PaintLine {
+        let plural = if length == 1 { "" } else { "s" };
+        let mut line = styled(
+            &format!("{label}({length} byte{plural} hidden)"),
+            Style::Local,
+        );
+        line.resize(width, Cell::BLANK);
+        line
+    }
+
+    fn paint_inspect(&self, size: Size, focused: usize) -> PaintOutput {
+        let history = self.session.messages();
+        let visible = |index: &usize| self.is_visible(&history[*index].origin);
+        let mut before = (0..focused)
+            .rev()
+            .filter(visible)
+            .take(self.context)
+            .collect::<Vec<_>>();
+        before.reverse();
+        let after = (focused + 1..history.len())
+            .filter(visible)
+            .take(self.context)
+            .collect::<Vec<_>>();
+
+        let rows = |indices: &[usize]| {
+            let mut blocks = indices
+                .iter()
+                .map(|index| {
+                    self.message_lines(size.width, &self.session.messages()[*index].origin)
+                })
+                .collect::<Vec<_>>();
+            if self.newest_first {
+                blocks.reverse();
+            }
+            blocks.concat()
+        };
+        let (before, after) = (rows(&before), rows(&after));
+
+        let focus = |label: &str| {
+            format!(
+                "▶{}",
+                self.gutter.label(label).chars().skip(1).collect::<String>()
+            )
+        };
+        let focused = match &self.session.messages()[focused].origin {
+            MessageOrigin::Local(message) => self.wrapped_lines(
+                size.width,
+                &focus(LOCAL_LABEL),
+                message,
+                &plain(" "),
+                Style::Local,
+                Direction::Local,
+            ),
+            MessageOrigin::Remote(message) => self.wrapped_lines(
+                size.width,
+                &focus(REMOTE_LABEL),
+                message,
+                &self.checksum_suffix(message),
+                Style::Remote,
+                Direction::Remote,
+            ),
+            MessageOrigin::Secret(length) => {
+                vec![self.secret_line(size.width, &focus(LOCAL_LABEL), *length)]
+            }
+        };
+
+        let excess = (before.len() + focused.len() + after.len()).saturating_sub(size.height);
+        let older = min(excess, before.len());
+        let mut output = if self.newest_first {
+            // The older context is below, but it's still the first to go, so the start of the inspected message stays
+            // in view.
+            let newer = min(excess - older, after.len());
+            [&after[newer..], &focused, &before[..before.len() - older]].concat()
+        } else {
+            [&before[older..], &focused, &after[..]].concat()
+        };
+        output.truncate(size.height);
+        output
+    }
+
+    fn wrapped_lines(
+        &self,
+        width: usize,
+        label: &str,
+        message: &[u8],
+        suffix: &[Cell],
+        style: Style,
+        direction: Direction,
+    ) -> Vec<PaintLine> {
+        let continuation = self.gutter.label(&self.wrap_marker);
+        let chunks = message
+            .chunks(self.bytes_per_line(width, direction))
+            .collect::<Vec<_>>();
+        if chunks.is_empty() {
+            return vec![self.vec_to_line(width, label, &[], suffix, style, direction)];
+        }
+        chunks
+            .iter()
+            .enumerate()
+            .map(|(index, chunk)| {
+                let lhs = if index == 0 { label } else { &continuation };
+                let rhs = if index + 1 == chunks.len() {
+                    suffix.to_vec()
+                } else {
+                    plain(" ")
+                };
+                self.vec_to_line(width, lhs, chunk, &rhs, style, direction)
+            })
+            .collect()
+    }
+
+    fn vec_to_line(
+        &self,
+        width: usize,
+        lhs: &str,
+        message: &[u8],
+        rhs: &[Cell],
+        style: Style,
+        direction: Direction,
+    ) -> PaintLine {
+        // Marked, so it can't be mistaken for a line that's only there for spacing.
+        if message.is_empty() {
+            let mut line = styled(lhs, style);
+            line.extend(styled(&format!("{EMPTY_MARKER} "), Style::Pending));
+            line.extend_from_slice(rhs);
+            line.resize(width, Cell::BLANK);
+            return line;
+        }
+        let mut human_readable: String = message
+            .iter()
+            .map(|byte| format!("{} ", self.radix.format(*byte)))
+            .collect::<String>();
+        if self.fixed_width {
+            // Padded out to the full row so the character column always lines up.
+            let columns = self.fixed_columns(direction) * (self.radix.width() + 1);
+            let characters = message
+                .iter()
+                .map(|byte| self.controls.show(*byte, self.unprintable))
+                .collect::<String>();
+            human_readable = format!(
+                "{human_readable:columns$}{} {characters} ",
+                self.gutter.separator
+            )
+            .chars()
+            .skip(self.scroll)
+            .collect();
+        }
+        // Counted in characters, since the separator (and the stand-in for unprintable bytes) can take more than one.
+        human_readable = human_readable
+            .chars()
+            .take(
+                width
+                    .saturating_sub(lhs.chars().count())
+                    .saturating_sub(rhs.len()),
+            )
+            .collect();
+        let mut line = styled(&format!("{lhs}{human_readable}"), style);
+        let lhs_width = lhs.chars().count();
+        let scroll = if self.fixed_width { self.scroll } else { 0 };
+        self.tag_bytes(&mut line[lhs_width..], message, scroll);
+        if self.fixed_width {
+            let character_column = self.fixed_columns(direction) * (self.radix.width() + 1) + 2;
+            for (position, cell) in (scroll..).zip(&mut line[lhs_width..]) {
+                if let Some(byte) = position
+                    .checked_sub(character_column)
+                    .and_then(|index| message.get(index / self.controls.width()))
+                {
+                    cell.byte = Some(*byte);
+                }
+            }
+        }
+        line.extend_from_slice(rhs);
+        line.resize(width, Cell::BLANK);
+        line
+    }
+
+    fn tag_bytes(&self, cells: &mut [Cell], bytes: &[u8], offset: usize) {
+        let digits = self.radix.width();
+        for (position, cell) in (offset..).zip(cells) {
+            if position % (digits + 1) < digits {
+                cell.byte = bytes.get(position / (digits + 1)).copied();
+            }
+        }
+    }
+
+    fn checksum_suffix(&self, message: &[u8]) -> PaintLine {
+        let Some(checksum) = self.checksum else {
+            return plain(" ");
+        };
+        let format = |bytes: &[u8]| {
+            bytes
+                .iter()
+                .map(|byte| self.radix.format(*byte))
+                .collect::<Vec<_>>()
+                .join(" ")
+        };
+        if !self.verify_checksum {
+            return plain(&format!("= {} ", format(&checksum.compute(message))));
+        }
+        if message.len() <= checksum.width() {
+            return styled("✗ too short ", Style::Warning);
+        }
+        let (payload, embedded) = message.split_at(message.len() - checksum.width());
+        let expected = checksum.compute(payload);
+        if expected == embedded {
+            plain("✓ ")
+        } else {
+            styled(
+                &format!("✗ expected {} ", format(&expected)),
+                Style::Warning,
+            )
+        }
+    }
+}
+impl Painter for Messages {
+    fn paint(&self, size: Size) -> Result<PaintOutput, AppError> {
+        if !self.gutter.fits(size.width) {
+            return Ok(self.gutter.narrow(size));
+        }
+        let mut output = match self.inspecting {
+            Some(focused) => self.paint_inspect(size, focused),
+            None => self.scrolled_lines(size),
+        };
+        // Only until anything at all has been said, so it doesn't come back just because local echo is hidden.
+        if self.placeholder && output.is_empty() && self.session.messages().is_empty() {
+            let mut line = plain(&format!("{} ", self.gutter.empty()));
+            line.extend(styled(
+                &format!(
+                    "Waiting for data… type {} and press Enter to send",
+                    self.radix.name()
+                ),
+                Style::Pending,
+            ));
+            line.resize(size.width, Cell::BLANK);
+            output.push(line);
+        }
+
+        let mut empty_line: PaintLine = plain(&self.gutter.empty());
+        empty_line.resize(size.width, Cell::BLANK);
+        output.resize(size.height, empty_line);
+        Ok(output)
+    }
+}
+
+#[derive(Clone, Copy)]
+enum Direction {
+    Local,
+    Remote,
+}
+impl Direction {
+    fn of_run(style: Style) -> Self {
+        match style {
+            Style::Local => Self::Local,
+            _ => Self::Remote,
+        }
+    }
+}
+
+enum Block<'a> {
+    Message(&'a Message, bool),
+    Pending,
+    Queued(&'a Message),
+    Run(Style, Vec<&'a [u8]>),
+    Secret(usize),
+}
+
+#[derive(Default)]
+struct Layout {
+    key: Option<LayoutKey>,
+    blocks: Vec<Placed>,
+    rows: usize,
+    // How many messages have been placed, and the last one's sequence number, to tell when the history has changed.
+    placed: usize,
+    last: Option<usize>,
+}
+impl Layout {
+    fn push(&mut self, placement: Placement, rows: usize) {
+        self.blocks.push(Placed {
+            row: self.rows,
+            rows,
+            placement,
+        });
+        self.rows += rows;
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+struct LayoutKey {
+    fixed_width: bool,
+    stream: bool,
+    show_local: bool,
+    show_empty: bool,
+    local_bytes: usize,
+    remote_bytes: usize,
+}
+
+struct Placed {
+    row: usize,
+    rows: usize,
+    placement: Placement,
+}
+
+enum Placement {
+    Message(usize),
+    Secret(usize),
+    Run(Style, Vec<(usize, usize)>, usize),
+}
+
+// Paging keeps a row of the previous page on screen, so it's clear how the two pages join up.
+fn page(height: usize) -> usize {
+    max(height.saturating_sub(2), 1)
+}
+
+// The newer-rows indicator takes up a row once scrolled back at all, so the limit leaves room for it.
+fn scrollback_limit(total: usize, height: usize) -> usize {
+    if total <= height {
+        0
+    } else {
+        total - height.saturating_sub(1)
+    }
+}
+
+fn mark_changes(lines: &mut [PaintLine], before: &[PaintLine]) {
+    for (row, line) in lines.iter_mut().enumerate() {
+        for (column, cell) in line.iter_mut().enumerate() {
+            let was = before.get(row).and_then(|line| line.get(column)?.byte);
+            if cell.byte.is_some() && cell.byte != was {
+                cell.style = Style::Warning;
+            }
+        }
+    }
+}
+
+fn describe_byte(byte: u8) -> String {
+    let ascii = match byte {
+        0x20..=0x7e => format!("'{}'", byte as char),
+        _ => "(not printable)".to_string(),
+    };
+    format!("0x{byte:02x} = {byte} = 0o{byte:03o} = 0b{byte:08b} = {ascii}")
+}
+
+pub(crate) struct Input {
+    input: Vec<char>,
+    prompt: String,
+    preview_label: String,
+    notice: Option<String>,
+    strict: bool,
+    secret: bool,
+    radix: Radix,
+    space_delimits: bool,
+    hover: Option<String>,
+    auto_group: bool,
+    grouping_spaces: Vec<usize>,
+    detect: Option<Vec<Guess>>,
+    keep_trailing_space: bool,
+    gutter: Gutter,
+}
+impl Input {
+    pub(crate) fn new(gutter: Gutter, strict: bool) -> Self {
+        let radix = Radix::default();
+        Self {
+            input: Vec::new(),
+            prompt: gutter.label(radix.label()),
+            preview_label: gutter.label(PREVIEW_LABEL),
+            notice: None,
+            strict,
+            secret: false,
+            radix,
+            space_delimits: false,
+            hover: None,
+            auto_group: false,
+            grouping_spaces: Vec::new(),
+            detect: None,
+            keep_trailing_space: false,
+            gutter,
+        }
+    }
+
+    pub(crate) fn set_hover(&mut self, byte: Option<u8>) -> bool {
+        let hover = byte.map(describe_byte);
+        let changed = hover != self.hover;
+        self.hover = hover;
+        changed
+    }
+
+    pub(crate) fn set_auto_group(&mut self, auto_group: bool) {
+        self.auto_group = auto_group;
+    }
+
+    // Octal bytes don't all take the same number of digits, so there's no telling where one ends.
+    fn group_length(&self) -> Option<usize> {
+        match self.radix {
+            Radix::Octal => None,
+            radix if self.auto_group => Some(radix.width()),
+            _ => None,
+        }
+    }
+
+    fn clear_input(&mut self) {
+        self.input.clear();
+        self.grouping_spaces.clear();
+    }
+
+    fn push_digit(&mut self, c: char) {
+        let Some(length) = self.group_length() else {
+            self.input.push(c);
+            return;
+        };
+        // A space typed right after one that was put in already would only make an empty group.
+        if c == ' ' && self.grouping_spaces.last() == Some(&self.input.len().wrapping_sub(1)) {
+            return;
+        }
+        self.input.push(c);
+        let group = self.input.iter().rev().take_while(|c| **c != ' ').count();
+        if c != ' ' && group == length {
+            self.grouping_spaces.push(self.input.len());
+            self.input.push(' ');
+        }
+    }
+
+    // A space that was put in for the user goes along with the digit before it, as if it was never there.
+    fn backspace(&mut self) -> bool {
+        if self.grouping_spaces.last() == Some(&self.input.len().wrapping_sub(1)) {
+            self.grouping_spaces.pop();
+            self.input.pop();
+        }
+        self.input.pop().is_some()
+    }
+
+    pub(crate) fn set_space_delimits(&mut self, space_delimits: bool) {
+        self.space_delimits = space_delimits;
+    }
+
+    fn digits(&self) -> Vec<char> {
+        if self.space_delimits {
+            self.radix.pad_groups(&self.input)
+        } else {
+            self.input.clone()
+        }
+    }
+
+    pub(crate) fn set_radix(&mut self, radix: Radix) {
+        self.radix = radix;
+        if self.detect.is_some() {
+            return;
+        }
+        self.prompt = self.gutter.label(radix.label());
+        if !self.is_command() {
+            self.clear_input();
+        }
+    }
+
+    pub(crate) fn set_detect(&mut self, order: Option<Vec<Guess>>) {
+        self.prompt = self.gutter.label(match order {
+            Some(_) => DETECT_LABEL,
+            None => self.radix.label(),
+        });
+        self.detect = order;
+    }
+
+    pub(crate) fn set_keep_trailing_space(&mut self, keep: bool) {
+        self.keep_trailing_space = keep;
+    }
+
+    pub(crate) fn set_strict(&mut self, strict: bool) {
+        self.strict = strict;
+    }
+
+    fn accepts(&self, c: char) -> bool {
+        match &self.detect {
+            Some(order) => order.iter().any(|guess| guess.accepts(c)),
+            None => self.radix.accepts(c),
+        }
+    }
+
+    fn input_name(&self) -> String {
+        match &self.detect {
+            Some(order) => radix::describe(order),
+            None => self.radix.name().to_string(),
+        }
+    }
+
+    fn split_input(&self) -> (TcpMessage, Option<String>, Option<Guess>) {
+        let Some(order) = &self.detect else {
+            let (bytes, rest) = self.radix.split_input(&self.digits());
+            return (bytes, rest, None);
+        };
+        let input = self.input.iter().collect::<String>();
+        match radix::detect(order, &input, self.keep_trailing_space) {
+            Some((guess, bytes)) => (bytes, None, Some(guess)),
+            None => (Vec::new(), Some(input), None),
+        }
+    }
+
+    pub(crate) fn set_message(&mut self, message: &[u8]) {
+        self.clear_input();
+        self.notice = None;
+        let digits = message
+            .iter()
+            .map(|byte| self.radix.format(*byte))
+            .collect::<Vec<_>>()
+            .join(" ");
+        self.input.extend(digits.chars());
+    }
+
+    pub(crate) fn user_message_len(&self) -> Option<usize> {
+        match self.split_input() {
+            (bytes, None, _) => Some(bytes.len()),
+            (_, Some(_), _) => None,
+        }
+    }
+
+    pub(crate) fn drain_user_message(&mut self) -> Option<TcpMessage> {
+        let (bytes, None, _) = self.split_input() else {
+            return None;
+        };
+        self.clear_input();
+        Some(bytes)
+    }
+
+    pub(crate) fn toggle_secret(&mut self) {
+        self.secret = !self.secret;
+    }
+
+    // Masking only lasts for a single message, so it can't be forgotten about afterwards.
+    pub(crate) fn take_secret(&mut self) -> bool {
+        std::mem::take(&mut self.secret)
+    }
+
+    pub(crate) fn drain_command(&mut self) -> Option<String> {
+        if !self.is_command() {
+            return None;
+        }
+        let command = self.input.iter().collect::<String>();
+        self.clear_input();
+        Some(command)
+    }
+
+    pub(crate) fn is_command(&self) -> bool {
+        self.input.first() == Some(&COMMAND_PREFIX)
+    }
+
+    pub(crate) fn set_notice(&mut self, notice: String) {
+        self.notice = Some(notice);
+    }
+
+    pub(crate) fn handle_key(&mut self, key: Key) -> bool {
+        // Some key combinations come through as control characters, which were never meant to be typed.
+        if matches!(key, Key::Null) || matches!(key, Key::Char(c) if c.is_control()) {
+            return false;
+        }
+        let had_notice = self.notice.take().is_some();
+        let changed = match key {
+            Key::Char(c) if self.is_command() && !c.is_control() => {
+                self.input.push(c);
+                true
+            }
+            Key::Char(COMMAND_PREFIX) if self.input.is_empty() => {
+                self.input.push(COMMAND_PREFIX);
+                true
+            }
+            // Digits are only grouped for a fixed base.
+            Key::Char(c) if self.accepts(c) => {
+                match self.detect {
+                    Some(_) => self.input.push(c),
+                    None => self.push_digit(c),
+                }
+                true
+            }
+            Key::Backspace => self.backspace(),
+            _ if self.strict => {
+                self.notice = Some(match key {
+                    Key::Char(c) => format!(
+                        "Ignored \"{}\": not valid {} input.",
+                        c.escape_default(),
+                        self.input_name()
+                    ),
+                    _ => format!("Ignored key: not valid {} input.", self.input_name()),
+                });
+                true
+            }
+            _ => false,
+        };
+        changed || had_notice
+    }
+
+    pub(crate) fn listen(sink: Sender<Event>) -> Result<(), AppError> {
+        let mut reader = InputReader::default();
+        // Once stdin is closed, every read would return straight away with nothing, so there's no point carrying on.
+        let read = loop {
+            match reader.next() {
+                Ok(Some(event)) => sink
+                    .send(event)
+                    .into_report()
+                    .attach_printable("Could not communicate user input to main thread.")
+                    .change_context(AppError::ChannelBroken)?,
+                Ok(None) => break Ok(()),
+                Err(report) => break Err(report),
+            }
+        };
+        // Input that can't be read any more is just as closed, so the main thread still hears about it.
+        sink.send(Event::InputClosed)
+            .into_report()
+            .attach_printable("Could not communicate user input to main thread.")
+            .change_context(AppError::ChannelBroken)?;
+        read
+    }
+
+    pub(crate) fn get_cursor_position(&self, size: Size) -> (u16, u16) {
+        if !self.gutter.fits(size.width) {
+            return (0, 0);
+        }
+        let rows = self.input_rows(size);
+        let x = self.prompt.chars().count() + rows.last().map_or(0, |row| self.row_columns(row));
+        (x as u16, (INPUT_ROW + rows.len() - 1) as u16)
+    }
+
+    fn input_rows(&self, size: Size) -> Vec<&[char]> {
+        let row_width = size.width.saturating_sub(self.prompt.chars().count() + 1);
+        let height = max(1, size.height.saturating_sub(INPUT_ROW));
+        let shown = &self.input[self.input.len().saturating_sub(row_width * height)..];
+        if row_width == 0 || shown.is_empty() {
+            return vec![&[]];
+        }
+        // Wide characters fill a row sooner, and never straddle two.
+        let mut rows = Vec::new();
+        let (mut start, mut used) = (0, 0);
+        for (index, c) in shown.iter().enumerate() {
+            let columns = self.row_columns(&[*c]);
+            if used > 0 && used + columns > row_width {
+                rows.push(&shown[start..index]);
+                (start, used) = (index, 0);
+            }
+            used += columns;
+        }
+        rows.push(&shown[start..]);
+        rows.split_off(rows.len().saturating_sub(height))
+    }
+
+    fn row_columns(&self, row: &[char]) -> usize {
+        match self.secret && !self.is_command() {
+            true => row.len(),
+            false => row.iter().map(|c| columns(*c)).sum(),
+        }
+    }
+}
+impl Input {
+    fn paint_preview(&self, width: usize) -> PaintLine {
+        let mut line: PaintLine = plain(&self.preview_label);
+        if let Some(notice) = &self.notice {
+            line.extend(styled(notice, Style::Warning));
+            line.resize(width, Cell::BLANK);
+            return line;
+        }
+        if let Some(hover) = &self.hover {
+            line.extend(plain(hover));
+            line.resize(width, Cell::BLANK);
+            return line;
+        }
+        if self.is_command() {
+            line.extend(plain("(command)"));
+            line.resize(width, Cell::BLANK);
+            return line;
+        }
+
+        let (bytes, rest, guess) = self.split_input();
+        let plural = if bytes.len() == 1 { "" } else { "s" };
+        if self.secret {
+            line.extend(plain(&format!("({} byte{plural}, secret) ", bytes.len())));
+            if rest.is_some() {
+                line.extend(styled("•?", Style::Warning));
+            }
+            line.resize(width, Cell::BLANK);
+            return line;
+        }
+        let read_as = guess.map_or(String::new(), |guess| format!(", as {}", guess.name()));
+        line.extend(plain(&format!("({} byte{plural}{read_as}) ", bytes.len())));
+        line.extend(plain(
+            &bytes
+                .iter()
+                .map(|byte| format!("{} ", self.radix.format(*byte)))
+                .collect::<String>(),
+        ));
+        if let Some(rest) = rest {
+            line.extend(styled(&format!("{rest}?"), Style::Warning));
+        }
+        line.resize(width, Cell::BLANK);
+        line
+    }
+}
+impl Painter for Input {
+    fn paint(&self, size: Size) -> Result<PaintOutput, AppError> {
+        if !self.gutter.fits(size.width) {
+            return Ok(self.gutter.narrow(size));
+        }
+        let mut output: PaintOutput = Vec::with_capacity(size.height);
+
+        let mut divider: PaintLine = plain(&self.gutter.seam('┼'));
+        divider.resize(size.width, Cell::new('─', Style::Plain));
+        output.push(divider);
+        output.push(self.paint_preview(size.width));
+
+        let masked = self.secret && !self.is_command();
+        for (index, row) in self.input_rows(size).into_iter().enumerate() {
+            let mut line: PaintLine = match index {
+                0 => plain(&self.prompt),
+                _ => plain(&self.gutter.label("")),
+            };
+            line.extend(row.iter().map(|c| match c {
+                ' ' => Cell::BLANK,
+                _ if masked => Cell::new('•', Style::Plain),
+                c => Cell::new(*c, Style::Plain),
+            }));
+            // Padded out to the width of the screen, not just to as many characters.
+            line.resize(size.width + row.len() - self.row_columns(row), Cell::BLANK);
+            output.push(line);
+        }
+
+        output.resize(size.height, vec![Cell::BLANK; size.width]);
+        Ok(output)
+    }
+}
+
+pub(crate) struct Passthrough {
+    lines: Vec<Vec<char>>,
+    partial: Vec<u8>,
+}
+impl Passthrough {
+    pub(crate) fn new() -> Self {
+        Self {
+            lines: vec![Vec::new()],
+            partial: Vec::new(),
+        }
+    }
+
+    pub(crate) fn handle_message(&mut self, message: &[u8]) {
+        let mut bytes = std::mem::take(&mut self.partial);
+        bytes.extend_from_slice(message);
+        // No character takes more than four bytes, so only the last three can be one that isn't finished yet.
+        let complete = (bytes.len().saturating_sub(3)..bytes.len())
+            .find(|start| {
+                std::str::from_utf8(&bytes[*start..])
+                    .is_err_and(|error| error.valid_up_to() == 0 && error.error_len().is_none())
+            })
+            .unwrap_or(bytes.len());
+        self.partial = bytes.split_off(complete);
+        for c in String::from_utf8_lossy(&bytes).chars() {
+            match c {
+                '\n' => self.lines.push(Vec::new()),
+                '\x08' | '\x7f' => _ = self.current_line().pop(),
+                c if c.is_control() => (),
+                c => self.current_line().push(c),
+            }
+        }
+    }
+
+    pub(crate) fn clear(&mut self) {
+        self.lines = vec![Vec::new()];
+        self.partial.clear();
+    }
+
+    fn current_line(&mut self) -> &mut Vec<char> {
+        if self.lines.is_empty() {
+            self.lines.push(Vec::new());
+        }
+        self.lines.last_mut().unwrap()
+    }
+
+    pub(crate) fn key_to_bytes(key: Key) -> Option<TcpMessage> {
+        let bytes = match key {
+            Key::Char(c) => c.to_string().into_bytes(),
+            Key::Ctrl(c) if c.is_ascii_alphabetic() => vec![(c.to_ascii_lowercase() as u8) & 0x1f],
+            Key::Backspace => vec![0x7f],
+            Key::Esc => vec![0x1b],
+            Key::Up => b"\x1b[A".to_vec(),
+            Key::Down => b"\x1b[B".to_vec(),
+            Key::Right => b"\x1b[C".to_vec(),
+            Key::Left => b"\x1b[D".to_vec(),
+            Key::Home => b"\x1b[H".to_vec(),
+            Key::End => b"\x1b[F".to_vec(),
+            Key::Delete => b"\x1b[3~".to_vec(),
+            _ => return None,
+        };
+        Some(bytes)
+    }
+
+    fn wrapped_lines(&self, size: Size) -> Vec<Vec<char>> {
+        let width = max(1, size.width);
+        let mut lines = self
+            .lines
+            .iter()
+            .rev()
+            .flat_map(|line| {
+                let empty = line.is_empty().then(Vec::new);
+                empty
+                    .into_iter()
+                    .chain(line.chunks(width).rev().map(<[char]>::to_vec))
+            })
+            .take(size.height)
+            .collect::<Vec<_>>();
+        lines.reverse();
+        lines
+    }
+
+    pub(crate) fn get_cursor_position(&self, size: Size) -> (u16, u16) {
+        let lines = self.wrapped_lines(size);
+        let y = lines.len().saturating_sub(1);
+        let x = lines.last().map_or(0, Vec::len);
+        (min(x, size.width.saturating_sub(1)) as u16, y as u16)
+    }
+}
+impl Painter for Passthrough {
+    fn paint(&self, size: Size) -> Result<PaintOutput, AppError> {
+        let mut output: PaintOutput = self
+            .wrapped_lines(size)
+            .into_iter()
+            .map(|line| {
+                let mut line: PaintLine = line
+                    .into_iter()
+                    .map(|c| Cell::new(c, Style::Plain))
+                    .collect();
+                line.resize(size.width, Cell::BLANK);
+                line
+            })
+            .collect();
+        output.resize(size.height, vec![Cell::BLANK; size.width]);
+        Ok(output)
+    }
+}
+
+pub(crate) struct Histogram {
+    local: [usize; 256],
+    remote: [usize; 256],
+    radix: Radix,
+    inspected: bool,
+}
+impl Histogram {
+    pub(crate) fn new(radix: Radix, inspected: bool) -> Self {
+        Self {
+            local: [0; 256],
+            remote: [0; 256],
+            radix,
+            inspected,
+        }
+    }
+
+    pub(crate) fn count(&mut self, origin: &MessageOrigin) {
+        let (counts, message) = match origin {
+            MessageOrigin::Local(message) => (&mut self.local, message),
+            MessageOrigin::Remote(message) => (&mut self.remote, message),
+            MessageOrigin::Secret(_) => return,
+        };
+        for byte in message {
+            counts[*byte as usize] += 1;
+        }
+    }
+
+    fn total(&self, value: usize) -> usize {
+        self.local[value] + self.remote[value]
+    }
+}
+impl Painter for Histogram {
+    fn paint(&self, size: Size) -> Result<PaintOutput, AppError> {
+        let mut values = (0..256)
+            .filter(|value| self.total(*value) > 0)
+            .collect::<Vec<_>>();
+        // Ties stay in order of value.
+        values.sort_by_key(|value| std::cmp::Reverse(self.total(*value)));
+        let total = values.iter().map(|value| self.total(*value)).sum::<usize>();
+        let of = match self.inspected {
+            true => "the inspected message",
+            false => "all messages",
+        };
+        let heading = match values.len() {
+            0 => format!("Byte frequencies of {of}: nothing to count yet."),
+            distinct => {
+                format!("Byte frequencies of {of}: {total} bytes, {distinct} of 256 values.")
+            }
+        };
+
+        let most = values.first().map_or(0, |value| self.total(*value));
+        let digits = |counts: &[usize; 256]| counts.iter().max().unwrap_or(&0).to_string().len();
+        let (local_digits, remote_digits) = (digits(&self.local), digits(&self.remote));
+        let mut output: PaintOutput = vec![plain(&heading)];
+        for value in values.into_iter().take(size.height.saturating_sub(1)) {
+            let (local, remote) = (self.local[value], self.remote[value]);
+            let mut line = plain(&format!(
+                "{} ↑{local:>local_digits$} ↓{remote:>remote_digits$} ",
+                self.radix.format(value as u8)
+            ));
+            // Received first, then sent, so the two share a bar without either being hidden behind the other.
+            let room = size.width.saturating_sub(line.len());
+            let length = (local + remote) * room / most;
+            let remote_length = min(remote * room / most, length);
+            line.extend(vec![Cell::new('█', Style::Remote); remote_length]);
+            line.extend(vec![Cell::new('█', Style::Local); length - remote_length]);
+            output.push(line);
+        }
+        for line in output.iter_mut() {
+            line.resize(size.width, Cell::BLANK);
+        }
+        output.resize(size.height, vec![Cell::BLANK; size.width]);
+        Ok(output)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::framing::LengthPrefix;
+    use hexcat::clock::Clock;
+    use std::io::{self, Write};
+    use std::time::{SystemTime, UNIX_EPOCH};
+
+    // Whatever the painters get to show was sent somewhere; where doesn't matter.
+    struct Sink;
+    impl Write for Sink {
+        fn write(&mut self, buffer: &[u8]) -> io::Result<usize> {
+            Ok(buffer.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+    impl Transport for Sink {
+        fn shutdown(&self) {}
+    }
+
+    // Refuses to take anything (like a full non-blocking socket would) until it's told it's ready.
+    struct Busy(std::rc::Rc<std::cell::Cell<bool>>);
+    impl Write for Busy {
+        fn write(&mut self, buffer: &[u8]) -> io::Result<usize> {
+            match self.0.get() {
+                true => Ok(buffer.len()),
+                false => Err(io::ErrorKind::WouldBlock.into()),
+            }
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+    impl Transport for Busy {
+        fn shutdown(&self) {}
+    }
+
+    // The other end has stopped reading.
+    struct Gone;
+    impl Write for Gone {
+        fn write(&mut self, _: &[u8]) -> io::Result<usize> {
+            Err(io::ErrorKind::BrokenPipe.into())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+    impl Transport for Gone {
+        fn shutdown(&self) {}
+    }
+
+    // Only moves on when it's told to. The time of day starts at the epoch.
+    #[derive(Clone)]
+    struct ManualClock {
+        start: Instant,
+        elapsed: std::rc::Rc<std::cell::Cell<Duration>>,
+    }
+    impl ManualClock {
+        fn new() -> Self {
+            Self {
+                start: Instant::now(),
+                elapsed: Default::default(),
+            }
+        }
+
+        fn advance(&self, by: Duration) {
+            self.elapsed.set(self.elapsed.get() + by);
+        }
+    }
+    impl Clock for ManualClock {
+        fn now(&self) -> Instant {
+            self.start + self.elapsed.get()
+        }
+
+        fn time(&self) -> SystemTime {
+            UNIX_EPOCH + self.elapsed.get()
+        }
+    }
+
+    fn gutter() -> Gutter {
+        Gutter::new(&[LOCAL_LABEL, REMOTE_LABEL, PREVIEW_LABEL])
+    }
+
+    fn messages(framing: Framing, history: Vec<MessageOrigin>) -> Messages {
+        let session = Session::new(Box::new(Sink), true, framing, 1_024);
+        let mut messages = Messages::new(session, gutter());
+        for message in history {
+            messages.handle_message(message).unwrap();
+        }
+        messages
+    }
+
+    // Trailing blanks are trimmed so the expectations stay readable; the width of every line is checked separately.
+    fn render(painter: &dyn Painter, size: Size) -> Vec<String> {
+        let output = painter.paint(size).unwrap();
+        assert_eq!(output.len(), size.height);
+        output
+            .iter()
+            .map(|line| {
+                let width = line
+                    .iter()
+                    .map(|cell| columns(cell.character))
+                    .sum::<usize>();
+                assert_eq!(width, size.width);
+                let text = line.iter().map(|cell| cell.character).collect::<String>();
+                text.trim_end().to_string()
+            })
+            .collect()
+    }
+
+    const ZERO: Size = Size {
+        width: 0,
+        height: 0,
+    };
+
+    #[test]
+    fn painters_survive_zero_size() {
+        assert!(Title::new(gutter()).paint(ZERO).is_ok());
+
+        let mut messages = messages(
+            Framing::Size(4),
+            vec![
+                MessageOrigin::Local(vec![0xde, 0xad]),
+                MessageOrigin::Remote(vec![0xbe, 0xef]),
+            ],
+        );
+        assert!(messages.paint(ZERO).is_ok());
+        messages.toggle_stream();
+        assert!(messages.paint(ZERO).is_ok());
+
+        let mut input = Input::new(gutter(), false);
+        input.handle_key(Key::Char('a'));
+        assert!(input.paint(ZERO).is_ok());
+        input.get_cursor_position(ZERO);
+
+        let mut passthrough = Passthrough::new();
+        passthrough.handle_message(b"hello\nworld");
+        assert!(passthrough.paint(ZERO).is_ok());
+        passthrough.get_cursor_position(ZERO);
+    }
+
+    #[test]
+    fn passthrough_finishes_characters_split_between_reads() {
+        let mut passthrough = Passthrough::new();
+        let text = "café €".as_bytes();
+        passthrough.handle_message(&text[..4]);
+        passthrough.handle_message(&text[4..7]);
+        passthrough.handle_message(&text[7..]);
+        let size = Size {
+            width: 10,
+            height: 1,
+        };
+        assert_eq!(render(&passthrough, size), ["café €"]);
+        // Anything that can't be finished is still shown as not readable.
+        passthrough.handle_message(b"\n\xff!");
+        assert_eq!(render(&passthrough, size), ["\u{fffd}!"]);
+    }
+
+    #[test]
+    fn passthrough_shows_the_newest_rows() {
+        let mut passthrough = Passthrough::new();
+        passthrough.handle_message(b"one\n\ntwo three four");
+        let size = Size {
+            width: 5,
+            height: 3,
+        };
+        assert_eq!(render(&passthrough, size), ["two t", "hree", "four"]);
+        assert_eq!(passthrough.get_cursor_position(size), (4, 2));
+        let size = Size { height: 6, ..size };
+        assert_eq!(
+            render(&passthrough, size),
+            ["one", "", "two t", "hree", "four", ""]
+        );
+        assert_eq!(passthrough.get_cursor_position(size), (4, 4));
+    }
+
+    #[test]
+    fn painters_say_when_too_narrow() {
+        let size = Size {
+            width: 9,
+            height: 3,
+        };
+        let messages = messages(Framing::Whole, vec![MessageOrigin::Remote(vec![0xbe])]);
+        let mut input = Input::new(gutter(), false);
+        input.handle_key(Key::Char('a'));
+        for painter in [&Title::new(gutter()) as &dyn Painter, &messages, &input] {
+            assert_eq!(render(painter, size), ["Too narro", "", ""]);
+        }
+        assert_eq!(input.get_cursor_position(size), (0, 0));
+
+        // A column more is enough to line anything up.
+        let size = Size { width: 10, ..size };
+        assert_eq!(render(&messages, size)[0], " REMOTE │");
+    }
+
+    #[test]
+    fn title_shows_the_byte_under_the_mouse_in_place_of_the_status() {
+        let mut title = Title::new(gutter());
+        title.set_status(Duration::from_secs(3), 12, 340, None);
+        title.set_activity(Some(vec!['▁', '█']));
+        assert!(title.set_hover(Some(b'A')));
+        assert!(!title.set_hover(Some(b'A')));
+        let size = Size {
+            width: 60,
+            height: 2,
+        };
+        assert_eq!(
+            render(&title, size)[0],
+            "HexCat.                 0x41 = 65 = 0o101 = 0b01000001 = 'A'"
+        );
+        // A notice still goes over it.
+        title.set_notice(Some("Stdin was closed.".to_string()));
+        assert_eq!(
+            render(&title, size)[0],
+            "HexCat.                                    Stdin was closed."
+        );
+        title.set_notice(None);
+        assert!(title.set_hover(None));
+        assert!(!render(&title, size)[0].contains("0x41"));
+    }
+
+    #[test]
+    fn title_shows_notices_in_place_of_the_status() {
+        let mut title = Title::new(gutter());
+        title.set_connected(
+            "127.0.0.1:9000".parse().unwrap(),
+            "127.0.0.1:9001".parse().ok(),
+        );
+        title.set_status(Duration::from_secs(3), 12, 340, None);
+        title.set_activity(Some(vec!['▁', '█']));
+        title.set_notice(Some("Stdin was closed.".to_string()));
+        let size = Size {
+            width: 70,
+            height: 2,
+        };
+        assert_eq!(
+            render(&title, size)[0],
+            "HexCat. Connected 127.0.0.1:9000 → 127.0.0.1:9001.   Stdin was closed."
+        );
+        assert_eq!(title.paint(size).unwrap()[0][69].style, Style::Warning);
+        // Without room for both, it's the notice that's shown.
+        let size = Size { width: 60, ..size };
+        assert_eq!(render(&title, size)[0], "Stdin was closed.");
+
+        title.set_notice(None);
+        assert!(!title.has_notice());
+        assert_eq!(
+            render(&title, size)[0],
+            "HexCat. Connected 127.0.0.1:9000 → 127.0.0.1:9001."
+        );
+    }
+
+    #[test]
+    fn title() {
+        let mut title = Title::new(gutter());
+        title.set_connected(
+            "127.0.0.1:9000".parse().unwrap(),
+            "127.0.0.1:9001".parse().ok(),
+        );
+        let size = Size {
+            width: 60,
+            height: 2,
+        };
+        assert_eq!(
+            render(&title, size),
+            [
+                "HexCat. Connected 127.0.0.1:9000 → 127.0.0.1:9001.",
+                "────────┬───────────────────────────────────────────────────",
+            ]
+        );
+
+        // Only once there's room for it next to the heading.
+        title.set_status(Duration::from_secs(3_754), 12, 340, None);
+        assert_eq!(
+            render(&title, size)[0],
+            "HexCat. Connected 127.0.0.1:9000 → 127.0.0.1:9001."
+        );
+        let wide = Size {
+            width: 80,
+            height: 1,
+        };
+        assert_eq!(
+            render(&title, wide),
+            ["HexCat. Connected 127.0.0.1:9000 → 127.0.0.1:9001.         01:02:34 ↑12 B ↓340 B"]
+        );
+        title.set_activity(Some(vec![' ', '▁', '█']));
+        assert_eq!(
+            render(&title, wide)[0],
+            "HexCat. Connected 127.0.0.1:9000 → 127.0.0.1:9001.      ▁█ 01:02:34 ↑12 B ↓340 B"
+        );
+
+        // A taller title has a row for the status, and keeps the divider at the bottom.
+        let tall = Size {
+            width: 60,
+            height: 4,
+        };
+        assert_eq!(
+            render(&title, tall),
+            [
+                "HexCat. Connected 127.0.0.1:9000 → 127.0.0.1:9001.",
+                " ▁█ 01:02:34 ↑12 B ↓340 B",
+                "",
+                "────────┬───────────────────────────────────────────────────",
+            ]
+        );
+
+        // The idle time left goes on the end, rounded up.
+        title.set_status(
+            Duration::from_secs(3_754),
+            12,
+            340,
+            Some(Duration::from_millis(4_200)),
+        );
+        assert_eq!(render(&title, tall)[1], " ▁█ 01:02:34 ↑12 B ↓340 B idle 5s");
+
+        title.set_alerted(true);
+        let output = title.paint(size).unwrap();
+        assert_eq!(output[0][0].style, Style::Warning);
+    }
+
+    #[test]
+    fn messages_grouped_by_direction() {
+        let mut messages = messages(
+            Framing::Whole,
+            vec![
+                MessageOrigin::Local(vec![0x01]),
+                MessageOrigin::Remote(vec![0x02]),
+                MessageOrigin::Remote(vec![0x03]),
+                MessageOrigin::Local(vec![0x04]),
+            ],
+        );
+        messages.toggle_grouped();
+        let size = Size {
+            width: 30,
+            height: 4,
+        };
+        assert_eq!(
+            render(&messages, size),
+            [
+                "  LOCAL │ 01",
+                " REMOTE │ 02",
+                "        │ 03",
+                "  LOCAL │ 04"
+            ]
+        );
+
+        messages.set_newest_first(true);
+        assert_eq!(
+            render(&messages, size),
+            [
+                "  LOCAL │ 04",
+                " REMOTE │ 03",
+                "        │ 02",
+                "  LOCAL │ 01"
+            ]
+        );
+    }
+
+    #[test]
+    fn messages_keep_their_order_and_status() {
+        let clock = ManualClock::new();
+        let mut messages = messages(Framing::Size(2), vec![]).with_clock(clock.clone());
+        for message in [
+            MessageOrigin::Local(vec![0xde, 0xad]),
+            MessageOrigin::Remote(vec![0xbe]),
+            MessageOrigin::Secret(4),
+            MessageOrigin::Remote(vec![0xef]),
+        ] {
+            messages.handle_message(message).unwrap();
+            clock.advance(Duration::from_millis(250));
+        }
+        let history = messages.session.messages();
+        let sequences: Vec<usize> = history.iter().map(|message| message.sequence).collect();
+        assert_eq!(sequences, vec![0, 1, 2]);
+        let statuses: Vec<Status> = history.iter().map(|message| message.status).collect();
+        assert_eq!(
+            statuses,
+            vec![Status::Complete, Status::Recorded, Status::Complete]
+        );
+        assert!(
+            matches!(&history[2].origin, MessageOrigin::Remote(frame) if frame == &[0xbe, 0xef])
+        );
+        // A frame is timed by when the last of it arrived.
+        let timestamps: Vec<SystemTime> = history.iter().map(|message| message.timestamp).collect();
+        assert_eq!(
+            timestamps,
+            [0, 500, 750].map(|millis| UNIX_EPOCH + Duration::from_millis(millis))
+        );
+    }
+
+    #[test]
+    fn messages_are_read_back_as_they_were_saved() {
+        let clock = ManualClock::new();
+        let mut saved = messages(Framing::Whole, vec![]).with_clock(clock.clone());
+        saved
+            .handle_message(MessageOrigin::Local(vec![0x01]))
+            .unwrap();
+        // The numbering carries on after clearing, so the first one saved isn't the first there was.
+        saved.clear();
+        for message in [
+            MessageOrigin::Remote(vec![0xde, 0xad]),
+            MessageOrigin::Secret(4),
+        ] {
+            clock.advance(Duration::new(1, 500_000_007));
+            saved.handle_message(message).unwrap();
+        }
+        let log = saved.to_log();
+        assert_eq!(log, "1 1.500000007 REMOTE de ad\n2 3.000000014 SECRET 4\n");
+
+        let mut loaded = messages(Framing::Whole, vec![]);
+        loaded.load_log(&log).unwrap();
+        let history = loaded.session.messages();
+        let sequences: Vec<usize> = history.iter().map(|message| message.sequence).collect();
+        assert_eq!(sequences, [1, 2]);
+        let timestamps: Vec<SystemTime> = history.iter().map(|message| message.timestamp).collect();
+        assert_eq!(
+            timestamps,
+            [
+                UNIX_EPOCH + Duration::new(1, 500_000_007),
+                UNIX_EPOCH + Duration::new(3, 14)
+            ]
+        );
+        assert!(history
+            .iter()
+            .all(|message| message.status == Status::Recorded));
+        assert_eq!(loaded.to_log(), log);
+        // Anything after them is numbered on from the last one.
+        loaded.handle_message(MessageOrigin::Secret(1)).unwrap();
+        assert_eq!(loaded.session.messages()[2].sequence, 3);
+
+        let mut invalid = messages(Framing::Whole, vec![]);
+        assert_eq!(
+            invalid.load_log("REMOTE de ad"),
+            Err("Line 1: invalid sequence number.".to_string())
+        );
+        assert_eq!(
+            invalid.load_log("\n1 1.5 REMOTE de ad"),
+            Err("Line 2: invalid timestamp.".to_string())
+        );
+        assert_eq!(
+            invalid.load_log("1 1.500000007 ELSEWHERE de ad"),
+            Err("Line 1: unknown direction \"ELSEWHERE\".".to_string())
+        );
+    }
+
+    #[test]
+    fn messages_keep_their_place_while_queued() {
+        let ready = std::rc::Rc::new(std::cell::Cell::new(false));
+        let session = Session::new(Box::new(Busy(ready.clone())), true, Framing::Whole, 1_024);
+        let clock = ManualClock::new();
+        let mut messages = Messages::new(session, gutter()).with_clock(clock.clone());
+        messages
+            .handle_message(MessageOrigin::Local(vec![0x01]))
+            .unwrap();
+        let queued: Vec<(usize, Status)> = messages
+            .session
+            .queued()
+            .map(|message| (message.sequence, message.status))
+            .collect();
+        assert_eq!(queued, [(0, Status::Queued)]);
+
+        clock.advance(Duration::from_millis(100));
+        messages
+            .handle_message(MessageOrigin::Remote(vec![0x02]))
+            .unwrap();
+        clock.advance(Duration::from_millis(100));
+        ready.set(true);
+        assert!(messages.retry_queued().unwrap());
+        let history: Vec<(usize, SystemTime)> = messages
+            .session
+            .messages()
+            .iter()
+            .map(|message| (message.sequence, message.timestamp))
+            .collect();
+        assert_eq!(
+            history,
+            [
+                (0, UNIX_EPOCH),
+                (1, UNIX_EPOCH + Duration::from_millis(100))
+            ]
+        );
+    }
+
+    #[test]
+    fn messages_mark_or_hide_empty_ones() {
+        // Stripping the delimiter off back-to-back ones leaves an empty frame.
+        let mut session = Session::new(Box::new(Sink), true, Framing::Delimiter(vec![0x0a]), 1_024);
+        session.set_strip(0, 1);
+        let mut messages = Messages::new(session, gutter());
+        messages
+            .handle_message(MessageOrigin::Remote(vec![0xde, 0x0a, 0x0a]))
+            .unwrap();
+        let size = Size {
+            width: 30,
+            height: 2,
+        };
+        assert_eq!(
+            render(&messages, size),
+            [" REMOTE │ de", " REMOTE │ (empty)"]
+        );
+        messages.set_show_empty(false);
+        assert_eq!(render(&messages, size), [" REMOTE │ de", "        │"]);
+    }
+
+    #[test]
+    fn messages_pick_out_what_changed() {
+        let mut messages = messages(
+            Framing::Whole,
+            vec![
+                MessageOrigin::Remote(vec![0x01, 0x02, 0x03]),
+                MessageOrigin::Local(vec![0xaa]),
+                MessageOrigin::Remote(vec![0x01, 0x05, 0x03, 0x04]),
+            ],
+        );
+        let size = Size {
+            width: 30,
+            height: 3,
+        };
+        messages.set_diff(true);
+        let output = messages.paint(size).unwrap();
+        // The first each way has nothing to compare with.
+        assert_eq!(output[0][13].style, Style::Remote);
+        assert_eq!(output[1][10].style, Style::Local);
+        let styles = [10, 13, 16, 19].map(|column| output[2][column].style);
+        assert_eq!(
+            styles,
+            [Style::Remote, Style::Warning, Style::Remote, Style::Warning]
+        );
+        messages.toggle_diff();
+        assert_eq!(messages.paint(size).unwrap()[2][13].style, Style::Remote);
+    }
+
+    #[test]
+    fn messages_count_rows_without_laying_them_out() {
+        let ready = std::rc::Rc::new(std::cell::Cell::new(true));
+        let session = Session::new(
+            Box::new(Busy(ready.clone())),
+            true,
+            Framing::Size(40),
+            1_024,
+        );
+        let mut messages = Messages::new(session, gutter());
+        for message in [
+            MessageOrigin::Local(vec![0x01; 20]),
+            MessageOrigin::Secret(4),
+            MessageOrigin::Remote(vec![0x02; 45]),
+        ] {
+            messages.handle_message(message).unwrap();
+        }
+        ready.set(false);
+        messages
+            .handle_message(MessageOrigin::Local(vec![0x03; 30]))
+            .unwrap();
+        for toggle in [
+            Messages::toggle_stream,
+            Messages::toggle_stream,
+            |messages: &mut Messages| messages.set_fixed_width(true),
+            Messages::toggle_stream,
+        ] {
+            toggle(&mut messages);
+            let width = 40;
+            let total = messages.line_count(width);
+            assert_eq!(messages.lines_between(width, 0, total).len(), total);
+            assert_eq!(messages.lines_between(width, 0, usize::MAX).len(), total);
+            assert_eq!(messages.lines_between(width, 1, 3).len(), 2);
+        }
+    }
+
+    #[test]
+    fn messages_lay_out_only_what_arrived() {
+        let mut messages = Messages::new(
+            Session::new(Box::new(Sink), true, Framing::Whole, 1_024),
+            gutter(),
+        );
+        messages.toggle_stream();
+        messages.toggle_grouped();
+        let width = 40;
+        let history = [
+            MessageOrigin::Remote(vec![0x01; 7]),
+            MessageOrigin::Remote(vec![0x02; 12]),
+            MessageOrigin::Local(vec![0x03; 3]),
+            MessageOrigin::Secret(5),
+            MessageOrigin::Remote(vec![0x04; 30]),
+            MessageOrigin::Remote(vec![0x05; 2]),
+        ];
+        for message in history {
+            messages.handle_message(message).unwrap();
+            for newest_first in [false, true] {
+                messages.set_newest_first(newest_first);
+                let total = messages.line_count(width);
+                let kept = messages.lines_between(width, 0, total);
+                // Laying the whole history out again from scratch has to give the same rows.
+                *messages.layout.borrow_mut() = Layout::default();
+                let all = messages.lines_between(width, 0, total);
+                assert!(kept == all);
+                assert_eq!(messages.line_count(width), total);
+                for row in 0..total {
+                    assert!(messages.lines_between(width, row, row + 1)[..] == all[row..=row]);
+                }
+            }
+        }
+        // A run that carries on over several reads is still one run.
+        messages.set_newest_first(false);
+        let layout = messages.layout(width);
+        assert_eq!(layout.placed, 6);
+        assert!(
+            matches!(layout.blocks.last().unwrap().placement, Placement::Run(Style::Remote, ref parts, 32) if parts.len() == 2)
+        );
+    }
+
+    #[test]
+    fn messages_are_let_through_a_page_at_a_time() {
+        let mut messages = messages(Framing::Whole, vec![MessageOrigin::Remote(vec![0x01])]);
+        let size = Size {
+            width: 30,
+            height: 4,
+        };
+        for byte in 2..=7 {
+            let rows = messages.line_count(size.width);
+            messages
+                .handle_message(MessageOrigin::Remote(vec![byte]))
+                .unwrap();
+            messages.hold_back(messages.line_count(size.width) - rows);
+        }
+        // The pane still fills up, but then it stays put.
+        assert_eq!(
+            render(&messages, size),
+            [
+                " REMOTE │ 01",
+                " REMOTE │ 02",
+                " REMOTE │ 03",
+                "        │ ▼ 4 newer"
+            ]
+        );
+        assert!(messages.is_holding());
+        messages.advance(size);
+        assert_eq!(
+            render(&messages, size),
+            [
+                "        │ ▲ 3 more",
+                " REMOTE │ 04",
+                " REMOTE │ 05",
+                "        │ ▼ 2 newer"
+            ]
+        );
+        messages.advance(size);
+        assert!(!messages.is_holding());
+        assert_eq!(
+            render(&messages, size),
+            [
+                "        │ ▲ 4 more",
+                " REMOTE │ 05",
+                " REMOTE │ 06",
+                " REMOTE │ 07"
+            ]
+        );
+
+        // Sending something shows it (and everything before it) straight away.
+        messages.hold_back(3);
+        messages
+            .handle_message(MessageOrigin::Local(vec![0x08]))
+            .unwrap();
+        assert!(!messages.is_holding());
+        assert_eq!(render(&messages, size)[3], "  LOCAL │ 08");
+    }
+
+    #[test]
+    fn messages_with_pending_frame() {
+        let messages = messages(
+            Framing::Size(2),
+            vec![
+                MessageOrigin::Local(vec![0xde, 0xad]),
+                MessageOrigin::Remote(vec![0xbe, 0xef, 0x01]),
+            ],
+        );
+        let size = Size {
+            width: 30,
+            height: 4,
+        };
+        assert_eq!(
+            render(&messages, size),
+            [
+                "  LOCAL │ de ad",
+                " REMOTE │ be ef",
+                " REMOTE │ 01",
+                "        │",
+            ]
+        );
+        let output = messages.paint(size).unwrap();
+        assert_eq!(output[0][10].style, Style::Local);
+        assert_eq!(output[1][10].style, Style::Remote);
+        assert_eq!(output[2][10].style, Style::Pending);
+    }
+
+    #[test]
+    fn messages_split_length_headers() {
+        let prefix = LengthPrefix::parse("2:be").unwrap();
+        let messages = messages(
+            Framing::Length(prefix),
+            vec![MessageOrigin::Remote(vec![0x00, 0x02, 0x41, 0x42, 0x00])],
+        );
+        let size = Size {
+            width: 30,
+            height: 2,
+        };
+        assert_eq!(
+            render(&messages, size),
+            [" REMOTE │ [00 02: 2] 41 42", " REMOTE │ 00"]
+        );
+        let output = messages.paint(size).unwrap();
+        assert_eq!(output[0][10].style, Style::Header);
+        assert_eq!(output[0][21].style, Style::Remote);
+    }
+
+    #[test]
+    fn messages_strip_frame_wrappers() {
+        let mut sized = messages(Framing::Size(5), vec![]);
+        sized.session.set_strip(1, 2);
+        sized
+            .handle_message(MessageOrigin::Remote(vec![
+                0xaa, 0x01, 0x02, 0xff, 0xff, 0xaa,
+            ]))
+            .unwrap();
+        let size = Size {
+            width: 30,
+            height: 2,
+        };
+        // Only complete frames are stripped; the rest is still on its way in.
+        assert_eq!(render(&sized, size), [" REMOTE │ 01 02", " REMOTE │ aa"]);
+
+        // The length header stays, and what it's followed by is stripped instead.
+        let prefix = LengthPrefix::parse("1:be").unwrap();
+        let mut framed = messages(Framing::Length(prefix), vec![]);
+        framed.session.set_strip(1, 1);
+        framed
+            .handle_message(MessageOrigin::Remote(vec![0x03, 0xaa, 0x41, 0xff]))
+            .unwrap();
+        assert_eq!(render(&framed, size)[0], " REMOTE │ [03: 3, 1 shown] 41");
+    }
+
+    #[test]
+    fn messages_queue_what_the_connection_cannot_take() {
+        let ready = std::rc::Rc::new(std::cell::Cell::new(false));
+        let session = Session::new(Box::new(Busy(ready.clone())), true, Framing::Whole, 1_024);
+        let mut messages = Messages::new(session, gutter());
+        messages
+            .handle_message(MessageOrigin::Local(vec![0x01, 0x02]))
+            .unwrap();
+        let size = Size {
+            width: 30,
+            height: 1,
+        };
+        assert_eq!(render(&messages, size), ["  LOCAL │ 01 02 (queued)"]);
+        assert_eq!(messages.paint(size).unwrap()[0][10].style, Style::Pending);
+        assert!(!messages.retry_queued().unwrap());
+
+        ready.set(true);
+        assert!(messages.retry_queued().unwrap());
+        assert_eq!(render(&messages, size), ["  LOCAL │ 01 02"]);
+        assert_eq!(messages.paint(size).unwrap()[0][10].style, Style::Local);
+
+        // Nothing to write at all isn't the same as the connection not taking it.
+        messages
+            .handle_message(MessageOrigin::Local(vec![]))
+            .unwrap();
+        assert!(!messages.has_queued());
+    }
+
+    #[test]
+    fn messages_indicate_hidden_history() {
+        let messages = messages(
+            Framing::Whole,
+            (0..5u8).map(|n| MessageOrigin::Remote(vec![n])).collect(),
+        );
+        assert_eq!(
+            render(
+                &messages,
+                Size {
+                    width: 20,
+                    height: 3,
+                }
+            ),
+            ["        │ ▲ 3 more", " REMOTE │ 03", " REMOTE │ 04"]
+        );
+    }
+
+    #[test]
+    fn messages_page_through_history() {
+        let mut messages = messages(
+            Framing::Whole,
+            (0..8u8).map(|n| MessageOrigin::Remote(vec![n])).collect(),
+        );
+        let size = Size {
+            width: 20,
+            height: 4,
+        };
+        messages.page_up(size).unwrap();
+        assert_eq!(
+            render(&messages, size),
+            [
+                "        │ ▲ 4 more",
+                " REMOTE │ 04",
+                " REMOTE │ 05",
+                "        │ ▼ 2 newer"
+            ]
+        );
+        messages.page_down(size).unwrap();
+        assert_eq!(
+            render(&messages, size),
+            [
+                "        │ ▲ 5 more",
+                " REMOTE │ 05",
+                " REMOTE │ 06",
+                " REMOTE │ 07"
+            ]
+        );
+        assert!(messages.page_down(size).is_err());
+    }
+
+    #[test]
+    fn messages_newest_first() {
+        let mut messages = messages(
+            Framing::Whole,
+            (0..8u8).map(|n| MessageOrigin::Remote(vec![n])).collect(),
+        );
+        messages.set_newest_first(true);
+        let size = Size {
+            width: 20,
+            height: 4,
+        };
+        assert_eq!(
+            render(&messages, size),
+            [
+                " REMOTE │ 07",
+                " REMOTE │ 06",
+                " REMOTE │ 05",
+                "        │ ▼ 5 more"
+            ]
+        );
+        assert!(messages.page_up(size).is_err());
+        messages.page_down(size).unwrap();
+        assert_eq!(
+            render(&messages, size),
+            [
+                "        │ ▲ 2 newer",
+                " REMOTE │ 05",
+                " REMOTE │ 04",
+                "        │ ▼ 4 more"
+            ]
+        );
+
+        messages.set_context(1);
+        messages.inspect_up();
+        messages.inspect_down();
+        assert_eq!(
+            render(&messages, size)[..3],
+            [" REMOTE │ 07", "▶REMOTE │ 06", " REMOTE │ 05"]
+        );
+    }
+
+    #[test]
+    fn messages_page_up_stays_at_the_top() {
+        let size = Size {
+            width: 20,
+            height: 4,
+        };
+        // Nothing to page through when everything already fits.
+        let mut short = messages(Framing::Whole, vec![MessageOrigin::Remote(vec![0])]);
+        assert!(short.page_up(size).is_err());
+        assert_eq!(
+            render(&short, size),
+            [" REMOTE │ 00", "        │", "        │", "        │"]
+        );
+
+        let mut messages = messages(
+            Framing::Whole,
+            (0..5u8).map(|n| MessageOrigin::Remote(vec![n])).collect(),
+        );
+        let top = [
+            " REMOTE │ 00",
+            " REMOTE │ 01",
+            " REMOTE │ 02",
+            "        │ ▼ 2 newer",
+        ];
+        messages.page_up(size).unwrap();
+        assert_eq!(render(&messages, size), top);
+        for _ in 0..3 {
+            assert!(messages.page_up(size).is_err());
+            assert_eq!(render(&messages, size), top);
+        }
+        // Coming back down takes as many pages as going up did, however often the top was hit.
+        messages.page_down(size).unwrap();
+        assert!(messages.page_down(size).is_err());
+    }
+
+    #[test]
+    fn messages_verify_checksums() {
+        let mut messages = messages(
+            Framing::Whole,
+            vec![
+                MessageOrigin::Remote(vec![0x01, 0x02, 0x03]),
+                MessageOrigin::Remote(vec![0x01, 0x02, 0x04]),
+            ],
+        );
+        let size = Size {
+            width: 34,
+            height: 2,
+        };
+        messages.set_checksum(Some(Checksum::Xor), false);
+        assert_eq!(
+            render(&messages, size),
+            [" REMOTE │ 01 02 03 = 00", " REMOTE │ 01 02 04 = 07"]
+        );
+        messages.set_checksum(Some(Checksum::Xor), true);
+        assert_eq!(
+            render(&messages, size),
+            [" REMOTE │ 01 02 03 ✓", " REMOTE │ 01 02 04 ✗ expected 03"]
+        );
+        assert_eq!(messages.paint(size).unwrap()[1][19].style, Style::Warning);
+    }
+
+    #[test]
+    fn messages_inspect_with_context() {
+        let mut messages = messages(
+            Framing::Whole,
+            (0..4u8)
+                .map(|n| MessageOrigin::Remote(vec![n; 4]))
+                .collect(),
+        );
+        messages.set_context(1);
+        messages.inspect_previous();
+        messages.inspect_previous();
+        let size = Size {
+            width: 22,
+            height: 5,
+        };
+        assert_eq!(
+            render(&messages, size),
+            [
+                " REMOTE │ 01 01 01 01",
+                "▶REMOTE │ 02 02 02",
+                "      ↳ │ 02",
+                " REMOTE │ 03 03 03 03",
+                "        │",
+            ]
+        );
+
+        messages.inspect_next();
+        messages.inspect_next();
+        assert_eq!(
+            render(&messages, size)[..3],
+            [
+                " REMOTE │ 02 02 02 02",
+                "▶REMOTE │ 03 03 03",
+                "      ↳ │ 03"
+            ]
+        );
+        assert!(messages.stop_inspecting());
+        assert!(!messages.stop_inspecting());
+    }
+
+    #[test]
+    fn messages_inspect_only_what_is_shown() {
+        let mut messages = messages(
+            Framing::Whole,
+            vec![
+                MessageOrigin::Remote(vec![1]),
+                MessageOrigin::Local(vec![2]),
+                MessageOrigin::Remote(vec![3]),
+                MessageOrigin::Local(vec![4]),
+            ],
+        );
+        messages.toggle_local_echo();
+        messages.inspect_previous();
+        assert_eq!(messages.inspecting, Some(2));
+        messages.inspect_previous();
+        assert_eq!(messages.inspecting, Some(0));
+        messages.inspect_previous();
+        assert_eq!(messages.inspecting, Some(0));
+        messages.inspect_next();
+        assert_eq!(messages.inspecting, Some(2));
+        messages.inspect_next();
+        assert_eq!(messages.inspecting, Some(2));
+    }
+
+    #[test]
+    fn messages_fixed_width() {
+        let mut messages = messages(
+            Framing::Whole,
+            vec![MessageOrigin::Remote((b'a'..=b'r').collect())],
+        );
+        messages.set_fixed_width(true);
+        let size = Size {
+            width: 80,
+            height: 2,
+        };
+        assert_eq!(
+            render(&messages, size),
+            [
+                " REMOTE │ 61 62 63 64 65 66 67 68 69 6a 6b 6c 6d 6e 6f 70 │ abcdefghijklmnop",
+                "      ↳ │ 71 72                                           │ qr",
+            ]
+        );
+
+        assert!(!messages.scroll_left());
+        assert!(messages.scroll_right());
+        assert_eq!(
+            render(&messages, size)[1],
+            "      ↳ │ 72                                           │ qr"
+        );
+    }
+
+    #[test]
+    fn messages_fixed_width_with_a_stand_in_for_unprintable_bytes() {
+        let mut unprintable = messages(
+            Framing::Whole,
+            vec![MessageOrigin::Remote(vec![0x00, 0x41, 0xff])],
+        );
+        unprintable.set_fixed_width(true);
+        unprintable.set_unprintable('·');
+        // Narrow enough to cut the row off partway through the character column.
+        let size = Size {
+            width: 62,
+            height: 1,
+        };
+        assert_eq!(
+            render(&unprintable, size),
+            [" REMOTE │ 00 41 ff                                        │ ·"]
+        );
+    }
+
+    #[test]
+    fn messages_fixed_width_per_direction() {
+        let mut per_direction = messages(
+            Framing::Whole,
+            vec![
+                MessageOrigin::Local(b"abcdef".to_vec()),
+                MessageOrigin::Remote(b"abcdef".to_vec()),
+            ],
+        );
+        per_direction.set_fixed_width(true);
+        per_direction.set_fixed_columns(4, 2);
+        let size = Size {
+            width: 40,
+            height: 5,
+        };
+        assert_eq!(
+            render(&per_direction, size),
+            [
+                "  LOCAL │ 61 62 63 64 │ abcd",
+                "      ↳ │ 65 66       │ ef",
+                " REMOTE │ 61 62 │ ab",
+                "      ↳ │ 63 64 │ cd",
+                "      ↳ │ 65 66 │ ef",
+            ]
+        );
+    }
+
+    #[test]
+    fn messages_fixed_width_with_control_characters() {
+        let mut controls = messages(
+            Framing::Whole,
+            vec![MessageOrigin::Remote(vec![0x00, 0x41, 0x0a, 0x7f, 0xff])],
+        );
+        controls.set_fixed_width(true);
+        let size = Size {
+            width: 80,
+            height: 1,
+        };
+        controls.set_controls(Controls::Caret);
+        assert_eq!(
+            render(&controls, size),
+            [" REMOTE │ 00 41 0a 7f ff                                  │ ^@ A^J^? ."]
+        );
+        controls.set_controls(Controls::Pictures);
+        assert_eq!(
+            render(&controls, size),
+            [" REMOTE │ 00 41 0a 7f ff                                  │ ␀A␊␡."]
+        );
+
+        // Taking two characters a byte, a long row still fits in the export.
+        let mut long = messages(Framing::Whole, vec![MessageOrigin::Remote(vec![0x01; 100])]);
+        long.set_fixed_width(true);
+        long.set_fixed_columns(100, 100);
+        long.set_controls(Controls::Caret);
+        let exported = long.painted_log()[0]
+            .iter()
+            .map(|cell| cell.character)
+            .collect::<String>();
+        assert!(exported
+            .trim_end()
+            .ends_with(&format!("│ {}", "^A".repeat(100))));
+    }
+
+    #[test]
+    fn messages_highlight_the_newest_for_a_moment() {
+        let clock = ManualClock::new();
+        let start = clock.now();
+        let mut messages = messages(Framing::Whole, vec![]).with_clock(clock.clone());
+        messages.set_highlight(Duration::from_secs(1));
+        messages
+            .handle_message(MessageOrigin::Remote(vec![0xbe, 0xef]))
+            .unwrap();
+        let size = Size {
+            width: 30,
+            height: 1,
+        };
+        assert_eq!(
+            messages.highlight_ends(),
+            Some(start + Duration::from_secs(1))
+        );
+        assert!(messages.paint(size).unwrap()[0][10].bold);
+
+        clock.advance(Duration::from_millis(999));
+        assert!(messages.paint(size).unwrap()[0][10].bold);
+        clock.advance(Duration::from_millis(1));
+        assert_eq!(messages.highlight_ends(), None);
+        assert!(!messages.paint(size).unwrap()[0][10].bold);
+    }
+
+    #[test]
+    fn messages_tell_when_the_connection_is_gone() {
+        let session = Session::new(Box::new(Gone), true, Framing::Whole, 1_024);
+        let mut messages = Messages::new(session, gutter());
+        let report = messages
+            .handle_message(MessageOrigin::Local(vec![0x01]))
+            .unwrap_err();
+        assert!(report
+            .downcast_ref()
+            .is_some_and(crate::connection::is_gone));
+        // Nothing went out, so it's still waiting (until whoever's in charge of the connection gives up on it).
+        assert!(messages.has_queued());
+        messages.discard_queued();
+        assert!(!messages.has_queued());
+    }
+
+    #[test]
+    fn messages_find_the_byte_at_a_position() {
+        let mut messages = messages(
+            Framing::Whole,
+            vec![MessageOrigin::Remote((b'a'..=b'r').collect())],
+        );
+        let size = Size {
+            width: 80,
+            height: 2,
+        };
+        let at = |messages: &Messages, x, y| messages.byte_at(size, Position { x, y }).unwrap();
+        assert_eq!(at(&messages, 10, 0), Some(b'a'));
+        assert_eq!(at(&messages, 11, 0), Some(b'a'));
+        assert_eq!(at(&messages, 12, 0), None);
+        assert_eq!(at(&messages, 13, 0), Some(b'b'));
+        assert_eq!(at(&messages, 3, 0), None);
+        assert_eq!(at(&messages, 10, 1), None);
+
+        messages.set_fixed_width(true);
+        assert_eq!(at(&messages, 10, 1), Some(b'q'));
+        // Over in the character column.
+        assert_eq!(at(&messages, 61, 1), Some(b'r'));
+        assert!(messages.scroll_right());
+        assert_eq!(at(&messages, 10, 1), Some(b'r'));
+
+        let mut input = Input::new(gutter(), false);
+        assert!(input.set_hover(Some(b'A')));
+        assert!(!input.set_hover(Some(b'A')));
+        assert_eq!(
+            render(
+                &input,
+                Size {
+                    width: 60,
+                    height: 2
+                }
+            )[1],
+            " Bytes: │ 0x41 = 65 = 0o101 = 0b01000001 = 'A'"
+        );
+    }
+
+    #[test]
+    fn messages_find_the_byte_at_a_position_from_the_layout() {
+        // Without painting the pane, it has to find the same byte the painted pane shows there.
+        let mut messages = messages(
+            Framing::Whole,
+            (0..6)
+                .map(|index| MessageOrigin::Remote(vec![index; 30]))
+                .collect(),
+        );
+        let size = Size {
+            width: 40,
+            height: 5,
+        };
+        for newest_first in [false, true] {
+            messages.set_newest_first(newest_first);
+            for scrollback in 0..messages.line_count(size.width) {
+                messages.scrollback = scrollback;
+                let output = messages.paint(size).unwrap();
+                for (y, line) in output.iter().enumerate() {
+                    for (x, cell) in line.iter().enumerate() {
+                        let position = Position { x, y };
+                        assert_eq!(messages.byte_at(size, position).unwrap(), cell.byte);
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn messages_mark_wrapped_rows() {
+        let mut messages = messages(
+            Framing::Whole,
+            vec![MessageOrigin::Remote((b'a'..=b'r').collect())],
+        );
+        messages.set_fixed_width(true);
+        let size = Size {
+            width: 80,
+            height: 2,
+        };
+        messages.set_wrap_marker("...wraps");
+        assert_eq!(
+            render(&messages, size)[1],
+            "...wrap │ 71 72                                           │ qr"
+        );
+        messages.set_wrap_marker("");
+        assert_eq!(
+            render(&messages, size)[1],
+            "        │ 71 72                                           │ qr"
+        );
+    }
+
+    #[test]
+    fn gutter_separator() {
+        let ascii = gutter().with_separator('|');
+        assert_eq!(ascii.label(LOCAL_LABEL), "  LOCAL | ");
+        assert_eq!(ascii.empty(), "        |");
+        assert_eq!(ascii.seam('┼'), "────────|");
+        assert_eq!(gutter().seam('┼'), "────────┼");
+    }
+
+    #[test]
+    fn gutter_compact_labels() {
+        let compact = Gutter::compact(&[LOCAL_LABEL, REMOTE_LABEL, PREVIEW_LABEL, "Hex:"]);
+        assert_eq!(compact.label(LOCAL_LABEL), " → │ ");
+        assert_eq!(compact.label(REMOTE_LABEL), " ← │ ");
+        assert_eq!(compact.label("Hex:"), " H │ ");
+        assert_eq!(compact.empty(), "   │");
+
+        let session = Session::new(Box::new(Sink), true, Framing::Whole, 1_024);
+        let mut messages = Messages::new(session, compact);
+        messages
+            .handle_message(MessageOrigin::Local(vec![0xde, 0xad]))
+            .unwrap();
+        messages.inspect_up();
+        let size = Size {
+            width: 12,
+            height: 1,
+        };
+        assert_eq!(render(&messages, size), ["▶→ │ de ad"]);
+    }
+
+    #[test]
+    fn messages_placeholder_until_anything_is_said() {
+        let mut messages = messages(Framing::Whole, vec![]);
+        let size = Size {
+            width: 40,
+            height: 2,
+        };
+        assert_eq!(render(&messages, size), ["        │", "        │"]);
+        messages.set_placeholder(true);
+        assert_eq!(
+            render(&messages, size),
+            ["        │ Waiting for data… type hex and", "        │"]
+        );
+        messages.toggle_local_echo();
+        messages
+            .handle_message(MessageOrigin::Local(vec![0x01]))
+            .unwrap();
+        assert_eq!(render(&messages, size), ["        │", "        │"]);
+    }
+
+    #[test]
+    fn messages_stream_wraps_runs() {
+        let mut messages = messages(
+            Framing::Whole,
+            vec![
+                MessageOrigin::Remote(vec![0x01, 0x02]),
+                MessageOrigin::Remote(vec![0x03, 0x04, 0x05]),
+                MessageOrigin::Local(vec![0xff]),
+            ],
+        );
+        messages.toggle_stream();
+        // Room for three bytes per line after the gutter.
+        assert_eq!(
+            render(
+                &messages,
+                Size {
+                    width: 21,
+                    height: 4,
+                }
+            ),
+            [
+                " REMOTE │ 01 02 03",
+                "      ↳ │ 04 05",
+                "  LOCAL │ ff",
+                "        │",
+            ]
+        );
+    }
+
+    #[test]
+    fn input_groups_bytes_as_they_are_typed() {
+        let mut input = Input::new(gutter(), false);
+        input.set_auto_group(true);
+        for c in "dea d".chars() {
+            input.handle_key(Key::Char(c));
+        }
+        let size = Size {
+            width: 40,
+            height: 3,
+        };
+        assert_eq!(render(&input, size)[2], "   Hex: │ de a d");
+        input.handle_key(Key::Backspace);
+        input.handle_key(Key::Backspace);
+        input.handle_key(Key::Backspace);
+        assert_eq!(render(&input, size)[2], "   Hex: │ de");
+        input.handle_key(Key::Backspace);
+        assert_eq!(render(&input, size)[2], "   Hex: │ d");
+        for c in "e beef".chars() {
+            input.handle_key(Key::Char(c));
+        }
+        assert_eq!(render(&input, size)[2], "   Hex: │ de be ef");
+        assert_eq!(input.drain_user_message(), Some(vec![0xde, 0xbe, 0xef]));
+    }
+
+    #[test]
+    fn input_ignores_control_characters() {
+        let mut input = Input::new(gutter(), true);
+        assert!(!input.handle_key(Key::Char('\0')));
+        assert!(!input.handle_key(Key::Null));
+        input.handle_key(Key::Char(COMMAND_PREFIX));
+        assert!(!input.handle_key(Key::Char('\0')));
+        assert!(!input.handle_key(Key::Char('\u{1b}')));
+        let size = Size {
+            width: 40,
+            height: 3,
+        };
+        assert_eq!(
+            render(&input, size)[1..],
+            [" Bytes: │ (command)", "   Hex: │ :"]
+        );
+    }
+
+    #[test]
+    fn input_detects_its_encoding() {
+        let mut input = Input::new(gutter(), true);
+        input.set_detect(Some(vec![Guess::Hex, Guess::Decimal, Guess::Text]));
+        let size = Size {
+            width: 40,
+            height: 3,
+        };
+        let typed = |input: &mut Input, text: &str| {
+            for c in text.chars() {
+                input.handle_key(Key::Char(c));
+            }
+        };
+        typed(&mut input, "10 ff");
+        assert_eq!(
+            render(&input, size)[1..],
+            [" Bytes: │ (2 bytes, as hex) 10 ff", "  Auto: │ 10 ff"]
+        );
+        assert_eq!(input.drain_user_message(), Some(vec![0x10, 0xff]));
+        typed(&mut input, "10 255");
+        assert_eq!(input.drain_user_message(), Some(vec![10, 255]));
+        typed(&mut input, "10 256");
+        assert_eq!(input.drain_user_message(), Some(b"10 256".to_vec()));
+
+        // The order decides between readings that both fit, and nothing might fit at all.
+        input.set_detect(Some(vec![Guess::Decimal, Guess::Hex]));
+        typed(&mut input, "10 20");
+        assert_eq!(input.drain_user_message(), Some(vec![10, 20]));
+        typed(&mut input, "1f 300");
+        assert_eq!(input.user_message_len(), None);
+        assert_eq!(input.drain_user_message(), None);
+    }
+
+    #[test]
+    fn input_detects_multibyte_text() {
+        let mut input = Input::new(gutter(), true);
+        input.set_detect(Some(vec![Guess::Hex, Guess::Text]));
+        let size = Size {
+            width: 50,
+            height: 3,
+        };
+        for c in "café".chars() {
+            input.handle_key(Key::Char(c));
+        }
+        assert_eq!(
+            render(&input, size)[1..],
+            [
+                " Bytes: │ (5 bytes, as text) 63 61 66 c3 a9",
+                "  Auto: │ café"
+            ]
+        );
+        assert_eq!(input.drain_user_message(), Some("café".as_bytes().to_vec()));
+        // Hex digits alongside them don't make it hex.
+        for c in "ab 🦀".chars() {
+            input.handle_key(Key::Char(c));
+        }
+        assert_eq!(
+            input.drain_user_message(),
+            Some(vec![0x61, 0x62, 0x20, 0xf0, 0x9f, 0xa6, 0x80])
+        );
+    }
+
+    #[test]
+    fn input_trims_trailing_whitespace_from_text() {
+        let mut input = Input::new(gutter(), false);
+        input.set_detect(Some(vec![Guess::Hex, Guess::Text]));
+        let typed = |input: &mut Input, text: &str| {
+            for c in text.chars() {
+                input.handle_key(Key::Char(c));
+            }
+            input.drain_user_message()
+        };
+        assert_eq!(typed(&mut input, " hi  "), Some(b" hi".to_vec()));
+        assert_eq!(typed(&mut input, "de ad "), Some(vec![0xde, 0xad]));
+        input.set_keep_trailing_space(true);
+        assert_eq!(typed(&mut input, " hi  "), Some(b" hi  ".to_vec()));
+    }
+
+    #[test]
+    fn input_in_strict_mode_only_takes_what_is_detected() {
+        let mut input = Input::new(gutter(), true);
+        input.set_detect(Some(vec![Guess::Hex, Guess::Decimal]));
+        let size = Size {
+            width: 60,
+            height: 3,
+        };
+        for c in "1x".chars() {
+            input.handle_key(Key::Char(c));
+        }
+        assert_eq!(
+            render(&input, size)[1..],
+            [
+                " Bytes: │ Ignored \"x\": not valid hex or decimal input.",
+                "  Auto: │ 1"
+            ]
+        );
+        // Anything goes once text is one of the ways it's read.
+        input.set_detect(Some(vec![Guess::Hex, Guess::Text]));
+        input.handle_key(Key::Char('x'));
+        assert_eq!(input.drain_user_message(), Some(b"1x".to_vec()));
+    }
+
+    #[test]
+    fn input_edits_an_inspected_message() {
+        let mut messages = messages(
+            Framing::Whole,
+            vec![
+                MessageOrigin::Remote(vec![0xde, 0xad]),
+                MessageOrigin::Secret(2),
+            ],
+        );
+        assert!(messages.inspected().is_err());
+        messages.inspect_up();
+        assert!(messages.inspected().is_err());
+        messages.inspect_up();
+
+        let mut input = Input::new(gutter(), false);
+        input.handle_key(Key::Char('1'));
+        input.set_message(messages.inspected().unwrap());
+        input.handle_key(Key::Backspace);
+        input.handle_key(Key::Char('f'));
+        assert_eq!(input.drain_user_message(), Some(vec![0xde, 0xaf]));
+
+        input.set_radix(Radix::Octal);
+        input.set_message(messages.inspected().unwrap());
+        assert_eq!(input.drain_user_message(), Some(vec![0xde, 0xad]));
+    }
+
+    #[test]
+    fn histogram_counts_each_way() {
+        let mut messages = messages(
+            Framing::Whole,
+            vec![
+                MessageOrigin::Remote(vec![0x00, 0x00, 0x41]),
+                MessageOrigin::Local(vec![0x41, 0x0a]),
+                MessageOrigin::Secret(3),
+            ],
+        );
+        let size = Size {
+            width: 20,
+            height: 5,
+        };
+        // The most common first, with whatever was received at the start of each bar.
+        assert_eq!(
+            render(&messages.histogram(), size)[1..],
+            [
+                "00 ↑0 ↓2 ███████████",
+                "41 ↑1 ↓1 ███████████",
+                "0a ↑1 ↓0 █████",
+                "",
+            ]
+        );
+        let bar = &messages.histogram().paint(size).unwrap()[2];
+        assert_eq!(bar[9].style, Style::Remote);
+        assert_eq!(bar[19].style, Style::Local);
+
+        let wide = Size {
+            width: 70,
+            height: 1,
+        };
+        assert_eq!(
+            render(&messages.histogram(), wide),
+            ["Byte frequencies of all messages: 5 bytes, 3 of 256 values."]
+        );
+        messages.inspect_up();
+        assert_eq!(
+            render(&messages.histogram(), wide),
+            ["Byte frequencies of the inspected message: nothing to count yet."]
+        );
+    }
+
+    #[test]
+    fn input_wraps_onto_spare_rows() {
+        let mut input = Input::new(gutter(), false);
+        for c in "de ad be ef 01".chars() {
+            input.handle_key(Key::Char(c));
+        }
+        let size = Size {
+            width: 20,
+            height: 5,
+        };
+        let rendered = render(&input, size);
+        assert_eq!(rendered[2..], ["   Hex: │ de ad be", "        │ ef 01", ""]);
+        assert_eq!(input.get_cursor_position(size), (15, 3));
+    }
+
+    #[test]
+    fn input_wraps_wide_characters_by_their_width() {
+        let mut input = Input::new(gutter(), false);
+        input.set_detect(Some(vec![Guess::Text]));
+        for c in "é🦀🦀中".chars() {
+            input.handle_key(Key::Char(c));
+        }
+        let size = Size {
+            width: 16,
+            height: 5,
+        };
+        let rendered = render(&input, size);
+        assert_eq!(rendered[2..], ["  Auto: │ é🦀🦀", "        │ 中", ""]);
+        assert_eq!(input.get_cursor_position(size), (12, 3));
+
+        // Only the newest rows fit.
+        let size = Size {
+            width: 16,
+            height: 3,
+        };
+        assert_eq!(render(&input, size)[2], "  Auto: │ 中");
+        assert_eq!(input.get_cursor_position(size), (12, 2));
+    }
+
+    #[test]
+    fn input_previews_bytes() {
+        let mut input = Input::new(gutter(), false);
+        for c in "de ad b".chars() {
+            input.handle_key(Key::Char(c));
+        }
+        let size = Size {
+            width: 40,
+            height: 3,
+        };
+        assert_eq!(
+            render(&input, size),
+            [
+                "────────┼───────────────────────────────",
+                " Bytes: │ (2 bytes) de ad b?",
+                "   Hex: │ de ad b",
+            ]
+        );
+        assert_eq!(input.get_cursor_position(size), (17, 2));
+
+        input.set_notice("Something happened.".to_string());
+        assert_eq!(render(&input, size)[1], " Bytes: │ Something happened.");
+
+        input.set_radix(Radix::Octal);
+        for c in "336 9 400".chars() {
+            input.handle_key(Key::Char(c));
+        }
+        assert_eq!(
+            render(&input, size)[1..],
+            [" Bytes: │ (1 byte) 336 400?", "   Oct: │ 336  400"]
+        );
+        assert_eq!(input.drain_user_message(), None);
+
+        input.set_radix(Radix::Binary);
+        for c in "1010 0101 11".chars() {
+            input.handle_key(Key::Char(c));
+        }
+        assert_eq!(
+            render(&input, size)[1..],
+            [" Bytes: │ (1 byte) 10100101 11?", "   Bin: │ 1010 0101 11"]
+        );
+
+        input.set_radix(Radix::Hex);
+        input.set_space_delimits(true);
+        for c in "1 2 abc".chars() {
+            input.handle_key(Key::Char(c));
+        }
+        assert_eq!(render(&input, size)[1], " Bytes: │ (4 bytes) 01 02 0a bc");
+        assert_eq!(
+            input.drain_user_message(),
+            Some(vec![0x01, 0x02, 0x0a, 0xbc])
+        );
     }
 }
@@ -0,0 +1,583 @@
+use crate::clock::{Clock, SystemClock};
+use crate::connection::{self, Incoming, Stream, Transport};
+use crate::error::{AppError, InitError};
+use crate::framing::Framing;
+use crate::socks::Socks5;
+use crate::{Message, MessageOrigin, Status, TcpMessage};
+use error_stack::{IntoReport, Result, ResultExt};
+use std::cmp::{max, min};
+use std::collections::VecDeque;
+use std::io::ErrorKind;
+use std::net::SocketAddr;
+use std::sync::mpsc::{self, Receiver, Sender, TryRecvError};
+use std::thread;
+
+type Filtered = std::result::Result<TcpMessage, String>;
+
+// Filters messages one at a time on a thread of its own, handing each result to `done` along with its tag. It stops
+// once the sender it returns is dropped.
+pub fn spawn_filter<T: Send + 'static>(
+    filter: impl Fn(&[u8]) -> Filtered + Send + 'static,
+    done: impl Fn(T, Filtered) + Send + 'static,
+) -> Sender<(T, TcpMessage)> {
+    let (sink, messages) = mpsc::channel::<(T, TcpMessage)>();
+    thread::spawn(move || {
+        for (tag, message) in messages {
+            done(tag, filter(&message));
+        }
+    });
+    sink
+}
+
+pub struct Session {
+    messages: Vec<Message>,
+    sequence: usize,
+    connection: Box<dyn Transport>,
+    flush: bool,
+    framing: Framing,
+    pending: Vec<u8>,
+    max_frame: usize,
+    strip_header: usize,
+    strip_footer: usize,
+    // Frames wait in `unfiltered` until the filter sends them back, by sequence number.
+    filter: Option<Sender<(usize, TcpMessage)>>,
+    filtered_sink: Sender<(usize, Filtered)>,
+    filtered: Receiver<(usize, Filtered)>,
+    unfiltered: Vec<Message>,
+    notice: Option<String>,
+    incoming: Option<Receiver<Incoming>>,
+    closed: bool,
+    reset: bool,
+    sent: usize,
+    received: usize,
+    queue: VecDeque<Outgoing>,
+    clock: Box<dyn Clock>,
+}
+
+struct Outgoing {
+    message: Message,
+    secret: Option<TcpMessage>,
+    written: usize,
+}
+impl Outgoing {
+    fn bytes(&self) -> &[u8] {
+        match (&self.secret, &self.message.origin) {
+            (Some(secret), _) => secret,
+            (None, MessageOrigin::Local(message)) => message,
+            (None, _) => &[],
+        }
+    }
+}
+impl Session {
+    pub fn new(
+        connection: Box<dyn Transport>,
+        flush: bool,
+        framing: Framing,
+        max_frame: usize,
+    ) -> Self {
+        let (filtered_sink, filtered) = mpsc::channel();
+        Self {
+            messages: Vec::new(),
+            sequence: 0,
+            connection,
+            flush,
+            framing,
+            pending: Vec::new(),
+            max_frame,
+            strip_header: 0,
+            strip_footer: 0,
+            filter: None,
+            filtered_sink,
+            filtered,
+            unfiltered: Vec::new(),
+            notice: None,
+            incoming: None,
+            closed: false,
+            reset: false,
+            sent: 0,
+            received: 0,
+            queue: VecDeque::new(),
+            clock: Box::new(SystemClock),
+        }
+    }
+
+    pub fn connect(
+        target: SocketAddr,
+        flush: bool,
+        framing: Framing,
+        max_frame: usize,
+        proxy: Option<&Socks5>,
+    ) -> Result<Self, InitError> {
+        let connection = connection::connect(target, flush, proxy)?;
+        Self::over(connection.into(), flush, framing, max_frame)
+    }
+
+    pub fn over(
+        connection: Stream,
+        flush: bool,
+        framing: Framing,
+        max_frame: usize,
+    ) -> Result<Self, InitError> {
+        let (sink, incoming) = mpsc::channel();
+        connection::spawn_listener(&connection, move |read| _ = sink.send(read))?;
+        let mut session = Self::new(Box::new(connection), flush, framing, max_frame);
+        session.incoming = Some(incoming);
+        Ok(session)
+    }
+
+    pub fn poll(&mut self) -> Vec<Message> {
+        let start = self.messages.len();
+        while let Some(incoming) = &self.incoming {
+            match incoming.try_recv() {
+                Ok(Incoming::Data(chunk)) => _ = self.receive(&chunk),
+                end
+                @ (Ok(Incoming::Closed | Incoming::Reset) | Err(TryRecvError::Disconnected)) => {
+                    self.closed = true;
+                    self.reset = matches!(end, Ok(Incoming::Reset));
+                    self.incoming = None;
+                }
+                Err(TryRecvError::Empty) => break,
+            }
+        }
+        let mut completed = self.messages[start..].to_vec();
+        completed.extend(self.filtered());
+        completed.sort_by_key(|message| message.sequence);
+        completed
+    }
+
+    pub fn is_closed(&self) -> bool {
+        self.closed && !self.is_filtering()
+    }
+
+    pub fn was_reset(&self) -> bool {
+        self.reset
+    }
+
+    pub fn messages(&self) -> &[Message] {
+        &self.messages
+    }
+
+    pub fn sent(&self) -> usize {
+        self.sent
+    }
+
+    pub fn received(&self) -> usize {
+        self.received
+    }
+
+    pub fn pending(&self) -> &[u8] {
+        &self.pending
+    }
+
+    pub fn framing(&self) -> &Framing {
+        &self.framing
+    }
+
+    // Anything partway in was being framed the old way, with no telling where it ends now, so it's dropped.
+    pub fn set_framing(&mut self, framing: Framing, max_frame: usize) {
+        self.framing = framing;
+        self.max_frame = max_frame;
+        self.pending.clear();
+    }
+
+    pub fn set_strip(&mut self, header: usize, footer: usize) {
+        self.strip_header = header;
+        self.strip_footer = footer;
+    }
+
+    pub fn set_clock(&mut self, clock: impl Clock + 'static) {
+        self.clock = Box::new(clock);
+    }
+
+    pub fn clock(&self) -> &dyn Clock {
+        self.clock.as_ref()
+    }
+
+    // A frame the filter fails on is kept as it was, and why is left as a notice.
+    pub fn set_filter(&mut self, filter: impl Fn(&[u8]) -> Filtered + Send + 'static) {
+        let sink = self.filtered_sink.clone();
+        self.filter = Some(spawn_filter(filter, move |sequence, filtered| {
+            _ = sink.send((sequence, filtered));
+        }));
+    }
+
+    pub fn take_filtered(&mut self) -> usize {
+        self.filtered().len()
+    }
+
+    fn filtered(&mut self) -> Vec<Message> {
+        let mut taken = Vec::new();
+        while let Ok((sequence, filtered)) = self.filtered.try_recv() {
+            // Anything cleared away in the meantime stays that way.
+            let Some(index) = self
+                .unfiltered
+                .iter()
+                .position(|message| message.sequence == sequence)
+            else {
+                continue;
+            };
+            let mut message = self.unfiltered.remove(index);
+            match filtered {
+                Ok(frame) => message.origin = MessageOrigin::Remote(frame),
+                Err(notice) => self.notice = Some(format!("{notice} Kept unfiltered.")),
+            }
+            let index = self
+                .messages
+                .partition_point(|earlier| earlier.sequence < message.sequence);
+            self.messages.insert(index, message.clone());
+            taken.push(message);
+        }
+        taken
+    }
+
+    pub fn is_filtering(&self) -> bool {
+        !self.unfiltered.is_empty()
+    }
+
+    pub fn clear_filter(&mut self) {
+        self.filter = None;
+    }
+
+    // Messages only count as sent once all of them has been written. Until then (if the connection won't take them
+    // right now) they wait in a queue, and anything sent after them waits behind them.
+    pub fn send(&mut self, message: &[u8]) -> Result<(), AppError> {
+        self.enqueue(message, false)
+    }
+
+    pub fn send_secret(&mut self, message: &[u8]) -> Result<(), AppError> {
+        self.enqueue(message, true)
+    }
+
+    fn enqueue(&mut self, message: &[u8], secret: bool) -> Result<(), AppError> {
+        let origin = match secret {
+            true => MessageOrigin::Secret(message.len()),
+            false => MessageOrigin::Local(message.to_vec()),
+        };
+        // Given out when it's queued, so it's the same while it waits as once it's gone.
+        let outgoing = Outgoing {
+            message: Message {
+                origin,
+                timestamp: self.clock.time(),
+                sequence: self.next_sequence(),
+                status: Status::Queued,
+            },
+            secret: secret.then(|| message.to_vec()),
+            written: 0,
+        };
+        self.queue.push_back(outgoing);
+        self.retry().map(|_| ())
+    }
+
+    pub fn queued(&self) -> impl Iterator<Item = &Message> + '_ {
+        self.queue.iter().map(|outgoing| &outgoing.message)
+    }
+
+    // Returns whether any of it went out; a connection that fails for any other reason than being busy is given up on.
+    pub fn retry(&mut self) -> Result<bool, AppError> {
+        let mut progressed = false;
+        while let Some(outgoing) = self.queue.front_mut() {
+            // An empty message has nothing to write (and writing nothing would look like the connection refusing it).
+            let length = outgoing.bytes().len();
+            if outgoing.written < length {
+                match self.connection.write(&outgoing.bytes()[outgoing.written..]) {
+                    Ok(0) => Err(std::io::Error::from(ErrorKind::WriteZero))
+                        .into_report()
+                        .attach_printable("The remote server stopped accepting data.")
+                        .change_context(AppError::StreamWrite)?,
+                    Ok(written) => {
+                        outgoing.written += written;
+                        self.sent += written;
+                        progressed = true;
+                    }
+                    Err(error) if is_transient(&error) => break,
+                    Err(error) => Err(error)
+                        .into_report()
+                        .attach_printable("Could not send message to remote server.")
+                        .change_context(AppError::StreamWrite)?,
+                }
+            }
+            if outgoing.written == length {
+                let mut message = self
+                    .queue
+                    .pop_front()
+                    .expect("the front of the queue was just looked at")
+                    .message;
+                progressed = true;
+                message.status = Status::Complete;
+                // Anything received while it waited came after it; it goes back in where it was said.
+                let index = self
+                    .messages
+                    .partition_point(|earlier| earlier.sequence < message.sequence);
+                self.messages.insert(index, message);
+            }
+        }
+        if progressed && self.flush {
+            self.connection
+                .flush()
+                .into_report()
+                .attach_printable("Could not flush message to remote server.")
+                .change_context(AppError::StreamWrite)?;
+        }
+        Ok(progressed)
+    }
+
+    pub fn receive(&mut self, chunk: &[u8]) -> usize {
+        self.received += chunk.len();
+        self.pending.extend_from_slice(chunk);
+        let frames = self.framing.take_frames(&mut self.pending);
+        let count = frames.len();
+        let (header, footer) = (self.strip_header, self.strip_footer);
+        for mut frame in frames {
+            if header + footer > 0 {
+                // A length header is what the frame was read by, so it's kept; the stripping starts after it. A frame
+                // too short to have anything left in between is kept as an empty one.
+                let prefix = self
+                    .framing
+                    .header(&frame)
+                    .map_or(0, |(width, _)| min(width, frame.len()));
+                let end = max(prefix, frame.len().saturating_sub(footer));
+                let start = min(prefix + header, end);
+                frame.truncate(end);
+                frame.drain(prefix..start);
+            }
+            let message = self.stamp(MessageOrigin::Remote(frame.clone()), Status::Complete);
+            match &self.filter {
+                Some(filter) if filter.send((message.sequence, frame)).is_ok() => {
+                    self.unfiltered.push(message);
+                }
+                _ => self.messages.push(message),
+            }
+        }
+        // A peer that never sends a frame boundary (or announces a huge frame) shouldn't be able to exhaust memory.
+        if self.pending.len() > self.max_frame {
+            self.notice = Some(format!(
+                "Dropped {} bytes: frame exceeded the maximum of {} bytes.",
+                self.pending.len(),
+                self.max_frame
+            ));
+            self.pending = Vec::new();
+        }
+        count
+    }
+
+    pub fn record(&mut self, message: MessageOrigin) {
+        self.push(message, Status::Recorded);
+    }
+
+    // Keeps its own sequence number; anything added after it is numbered on from there.
+    pub fn restore(&mut self, message: Message) {
+        self.sequence = max(self.sequence, message.sequence + 1);
+        self.messages.push(Message {
+            status: Status::Recorded,
+            ..message
+        });
+    }
+
+    fn push(&mut self, origin: MessageOrigin, status: Status) {
+        let message = self.stamp(origin, status);
+        self.messages.push(message);
+    }
+
+    fn stamp(&mut self, origin: MessageOrigin, status: Status) -> Message {
+        Message {
+            origin,
+            timestamp: self.clock.time(),
+            sequence: self.next_sequence(),
+            status,
+        }
+    }
+
+    fn next_sequence(&mut self) -> usize {
+        self.sequence += 1;
+        self.sequence - 1
+    }
+
+    pub fn take_notice(&mut self) -> Option<String> {
+        self.notice.take()
+    }
+
+    // Unlike `clear`, a frame that's still arriving is kept.
+    pub fn take_messages(&mut self) -> Vec<Message> {
+        std::mem::take(&mut self.messages)
+    }
+
+    pub fn clear(&mut self) {
+        self.messages.clear();
+        self.pending.clear();
+        self.unfiltered.clear();
+    }
+
+    pub fn discard_queued(&mut self) {
+        self.queue.clear();
+    }
+
+    pub fn close(&self) {
+        self.connection.shutdown();
+    }
+
+    pub fn close_write(&self) {
+        self.connection.shutdown_write();
+    }
+
+    pub fn replace_connection(&mut self, connection: Box<dyn Transport>) -> Box<dyn Transport> {
+        self.sent = 0;
+        self.received = 0;
+        std::mem::replace(&mut self.connection, connection)
+    }
+
+    pub fn dump(&self, include_local: bool) -> Vec<u8> {
+        self.messages
+            .iter()
+            .filter_map(|message| match &message.origin {
+                MessageOrigin::Remote(message) => Some(message),
+                MessageOrigin::Local(message) if include_local => Some(message),
+                MessageOrigin::Local(_) | MessageOrigin::Secret(_) => None,
+            })
+            .flatten()
+            .copied()
+            .collect()
+    }
+}
+
+fn is_transient(error: &std::io::Error) -> bool {
+    matches!(
+        error.kind(),
+        ErrorKind::WouldBlock | ErrorKind::Interrupted | ErrorKind::TimedOut
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Read;
+    use std::net::TcpListener;
+    use std::thread;
+    use std::time::{Duration, Instant};
+
+    struct Sink;
+    impl std::io::Write for Sink {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+    impl Transport for Sink {
+        fn shutdown(&self) {}
+    }
+
+    fn remote(session: &Session) -> Vec<TcpMessage> {
+        session
+            .messages()
+            .iter()
+            .filter_map(|message| match &message.origin {
+                MessageOrigin::Remote(frame) => Some(frame.clone()),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn filters_see_whole_frames() {
+        let mut session = Session::new(Box::new(Sink), true, Framing::Delimiter(vec![b';']), 64);
+        session.set_strip(0, 1);
+        session.set_filter(|frame| match frame {
+            b"bad" => Err("Filter failed.".to_string()),
+            frame => Ok(frame.to_ascii_uppercase()),
+        });
+        assert_eq!(session.receive(b"ab"), 0);
+        assert_eq!(session.receive(b"c;de;ba"), 2);
+        assert_eq!(take_filtered(&mut session), 2);
+        assert_eq!(session.take_notice(), None);
+        assert_eq!(session.receive(b"d;"), 1);
+        assert_eq!(take_filtered(&mut session), 1);
+        assert_eq!(
+            session.take_notice(),
+            Some("Filter failed. Kept unfiltered.".to_string())
+        );
+        assert_eq!(remote(&session), [&b"ABC"[..], b"DE", b"bad"]);
+    }
+
+    fn take_filtered(session: &mut Session) -> usize {
+        let mut count = 0;
+        while session.is_filtering() {
+            count += session.take_filtered();
+            std::thread::sleep(Duration::from_millis(1));
+        }
+        count
+    }
+
+    #[test]
+    fn slow_filters_hold_nothing_else_up() {
+        let mut session = Session::new(Box::new(Sink), true, Framing::Delimiter(vec![b';']), 64);
+        session.send(b"out").unwrap();
+        session.set_filter(|frame| {
+            std::thread::sleep(Duration::from_millis(50));
+            Ok(frame.to_vec())
+        });
+        session.receive(b"in;");
+        // Sent before the frame arrived, so the frame takes its place after it once it's filtered.
+        session.send(b"later").unwrap();
+        assert!(session.is_filtering());
+        assert_eq!(session.messages().len(), 2);
+        assert_eq!(take_filtered(&mut session), 1);
+        assert_eq!(remote(&session), [&b"in;"[..]]);
+        assert!(matches!(
+            session.messages()[1].origin,
+            MessageOrigin::Remote(_)
+        ));
+    }
+
+    #[test]
+    fn traffic_is_counted_per_connection() {
+        let mut session = Session::new(Box::new(Sink), true, Framing::Whole, 64);
+        session.send(&[0x01, 0x02]).unwrap();
+        session.receive(&[0x03; 3]);
+        assert_eq!((session.sent(), session.received()), (2, 3));
+        session.replace_connection(Box::new(Sink));
+        assert_eq!((session.sent(), session.received()), (0, 0));
+        // The history itself is kept.
+        assert_eq!(session.messages().len(), 2);
+    }
+
+    #[test]
+    fn sends_queue_up_behind_a_peer_that_stops_reading() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let connection = connection::connect(listener.local_addr().unwrap(), true, None).unwrap();
+        let (mut peer, _) = listener.accept().unwrap();
+        let mut session =
+            Session::over(connection.into(), true, Framing::Whole, usize::MAX).unwrap();
+
+        // Nothing is read on the other end yet, so sooner or later the socket's buffers are full.
+        let message = vec![0x55; 1 << 20];
+        let mut sends = 0;
+        while session.queued().next().is_none() {
+            assert!(sends < 256, "the connection never pushed back");
+            let started = Instant::now();
+            session.send(&message).unwrap();
+            assert!(started.elapsed() < Duration::from_secs(1));
+            sends += 1;
+        }
+        assert!(session.sent() < sends * message.len());
+
+        let total = sends * message.len();
+        let reader = thread::spawn(move || {
+            let mut buffer = vec![0; 1 << 16];
+            let mut read = 0;
+            while read < total {
+                read += peer.read(&mut buffer).unwrap();
+            }
+            read
+        });
+        let deadline = Instant::now() + Duration::from_secs(30);
+        while session.queued().next().is_some() {
+            assert!(Instant::now() < deadline, "the queue never drained");
+            session.retry().unwrap();
+        }
+        assert_eq!(reader.join().unwrap(), total);
+        assert_eq!(session.sent(), total);
+        assert_eq!(session.messages().len(), sends);
+    }
+}
@@ -0,0 +1,270 @@
+use std::io::{self, ErrorKind, Read, Write};
+use std::net::SocketAddr;
+
+const VERSION: u8 = 5;
+const NO_AUTHENTICATION: u8 = 0;
+const USERNAME_PASSWORD: u8 = 2;
+const NO_ACCEPTABLE_METHODS: u8 = 0xff;
+const CONNECT: u8 = 1;
+const IPV4: u8 = 1;
+const DOMAIN_NAME: u8 = 3;
+const IPV6: u8 = 4;
+const AUTH_VERSION: u8 = 1;
+
+#[derive(Clone)]
+pub struct Socks5 {
+    pub addr: SocketAddr,
+    pub credentials: Option<(String, String)>,
+}
+impl Socks5 {
+    pub fn handshake(
+        &self,
+        stream: &mut (impl Read + Write),
+        target: SocketAddr,
+    ) -> io::Result<()> {
+        // A proxy that doesn't need them can still go without, even when there are credentials to offer.
+        let methods: &[u8] = match self.credentials {
+            Some(_) => &[NO_AUTHENTICATION, USERNAME_PASSWORD],
+            None => &[NO_AUTHENTICATION],
+        };
+        let mut greeting = vec![VERSION, methods.len() as u8];
+        greeting.extend_from_slice(methods);
+        stream.write_all(&greeting)?;
+
+        let [version, method] = read_array(stream)?;
+        check_version(VERSION, version)?;
+        match (method, &self.credentials) {
+            (NO_AUTHENTICATION, _) => (),
+            (USERNAME_PASSWORD, Some((username, password))) => {
+                authenticate(stream, username, password)?
+            }
+            (NO_ACCEPTABLE_METHODS, None) => {
+                return Err(io::Error::other(
+                    "the SOCKS5 proxy wants a username and password",
+                ))
+            }
+            _ => {
+                return Err(io::Error::other(
+                    "the SOCKS5 proxy didn't accept any way of authenticating that was offered",
+                ))
+            }
+        }
+
+        let mut request = vec![VERSION, CONNECT, 0];
+        match target {
+            SocketAddr::V4(addr) => {
+                request.push(IPV4);
+                request.extend_from_slice(&addr.ip().octets());
+            }
+            // There's no room for a scope id; the proxy has interfaces of its own anyway.
+            SocketAddr::V6(addr) => {
+                request.push(IPV6);
+                request.extend_from_slice(&addr.ip().octets());
+            }
+        }
+        request.extend_from_slice(&target.port().to_be_bytes());
+        stream.write_all(&request)?;
+
+        let [version, reply, _, address_type] = read_array(stream)?;
+        check_version(VERSION, version)?;
+        // Where the proxy connected from isn't any use here, but it still has to be read past.
+        let bound_length = match address_type {
+            IPV4 => 4,
+            IPV6 => 16,
+            DOMAIN_NAME => read_array::<1>(stream)?[0] as usize,
+            _ => {
+                return Err(io::Error::other(
+                    "the SOCKS5 proxy replied with an unknown type of address",
+                ))
+            }
+        };
+        stream.read_exact(&mut vec![0; bound_length + 2])?;
+        match reply {
+            0 => Ok(()),
+            2 => Err(io::Error::other(
+                "the SOCKS5 proxy isn't allowed to connect there",
+            )),
+            3 => Err(io::Error::from_raw_os_error(libc::ENETUNREACH)),
+            4 => Err(io::Error::from_raw_os_error(libc::EHOSTUNREACH)),
+            5 => Err(ErrorKind::ConnectionRefused.into()),
+            6 => Err(ErrorKind::TimedOut.into()),
+            _ => Err(io::Error::other(format!(
+                "the SOCKS5 proxy could not connect (reply {reply})"
+            ))),
+        }
+    }
+}
+
+fn authenticate(
+    stream: &mut (impl Read + Write),
+    username: &str,
+    password: &str,
+) -> io::Result<()> {
+    let mut request = vec![AUTH_VERSION];
+    for field in [username, password] {
+        let length = u8::try_from(field.len()).map_err(|_| {
+            io::Error::other("SOCKS5 usernames and passwords can't be longer than 255 bytes")
+        })?;
+        request.push(length);
+        request.extend_from_slice(field.as_bytes());
+    }
+    stream.write_all(&request)?;
+    let [version, status] = read_array(stream)?;
+    check_version(AUTH_VERSION, version)?;
+    match status {
+        0 => Ok(()),
+        _ => Err(io::Error::other(
+            "the SOCKS5 proxy didn't accept the username and password",
+        )),
+    }
+}
+
+fn read_array<const N: usize>(stream: &mut impl Read) -> io::Result<[u8; N]> {
+    let mut buffer = [0; N];
+    stream.read_exact(&mut buffer)?;
+    Ok(buffer)
+}
+
+fn check_version(expected: u8, version: u8) -> io::Result<()> {
+    match version == expected {
+        true => Ok(()),
+        false => Err(io::Error::other("the proxy doesn't speak SOCKS5")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    struct Proxy {
+        replies: Cursor<Vec<u8>>,
+        sent: Vec<u8>,
+    }
+    impl Proxy {
+        fn new(replies: &[&[u8]]) -> Self {
+            Self {
+                replies: Cursor::new(replies.concat()),
+                sent: Vec::new(),
+            }
+        }
+    }
+    impl Read for Proxy {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            self.replies.read(buf)
+        }
+    }
+    impl Write for Proxy {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.sent.write(buf)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    const CONNECTED: &[u8] = &[5, 0, 0, IPV4, 10, 0, 0, 1, 0x1f, 0x90];
+
+    fn target() -> SocketAddr {
+        "192.0.2.7:8080".parse().unwrap()
+    }
+
+    fn socks5(credentials: Option<(&str, &str)>) -> Socks5 {
+        Socks5 {
+            addr: "127.0.0.1:1080".parse().unwrap(),
+            credentials: credentials
+                .map(|(username, password)| (username.to_string(), password.to_string())),
+        }
+    }
+
+    #[test]
+    fn socks5_connects_without_authenticating() {
+        let mut proxy = Proxy::new(&[&[5, NO_AUTHENTICATION], CONNECTED]);
+        socks5(None).handshake(&mut proxy, target()).unwrap();
+        assert_eq!(
+            proxy.sent,
+            [
+                &[5, 1, NO_AUTHENTICATION][..],
+                &[5, CONNECT, 0, IPV4, 192, 0, 2, 7, 0x1f, 0x90]
+            ]
+            .concat()
+        );
+    }
+
+    #[test]
+    fn socks5_authenticates_when_asked_to() {
+        let mut proxy = Proxy::new(&[&[5, USERNAME_PASSWORD], &[AUTH_VERSION, 0], CONNECTED]);
+        let target = "[2001:db8::1]:443".parse().unwrap();
+        socks5(Some(("me", "pw")))
+            .handshake(&mut proxy, target)
+            .unwrap();
+        let mut request = vec![5, CONNECT, 0, IPV6, 0x20, 0x01, 0x0d, 0xb8];
+        request.extend([0; 11]);
+        request.extend([1, 0x01, 0xbb]);
+        assert_eq!(
+            proxy.sent,
+            [
+                &[5, 2, NO_AUTHENTICATION, USERNAME_PASSWORD][..],
+                &[AUTH_VERSION, 2, b'm', b'e', 2, b'p', b'w'],
+                &request
+            ]
+            .concat()
+        );
+    }
+
+    #[test]
+    fn socks5_reads_past_a_bound_domain_name() {
+        let mut proxy = Proxy::new(&[
+            &[5, NO_AUTHENTICATION],
+            &[5, 0, 0, DOMAIN_NAME, 3, b'a', b'.', b'b', 0, 80],
+            b"data",
+        ]);
+        socks5(None).handshake(&mut proxy, target()).unwrap();
+        assert_eq!(read_array(&mut proxy).unwrap(), *b"data");
+    }
+
+    #[test]
+    fn socks5_failures_are_described() {
+        let failure = |credentials, replies: &[&[u8]]| {
+            socks5(credentials)
+                .handshake(&mut Proxy::new(replies), target())
+                .unwrap_err()
+        };
+        let reply = |code| [5, code, 0, IPV4, 0, 0, 0, 0, 0, 0];
+        let accepted: &[u8] = &[5, NO_AUTHENTICATION];
+
+        assert_eq!(
+            failure(None, &[accepted, &reply(5)]).kind(),
+            ErrorKind::ConnectionRefused
+        );
+        assert_eq!(
+            failure(None, &[accepted, &reply(4)]).raw_os_error(),
+            Some(libc::EHOSTUNREACH)
+        );
+        assert_eq!(
+            failure(None, &[accepted, &reply(2)]).to_string(),
+            "the SOCKS5 proxy isn't allowed to connect there"
+        );
+        assert_eq!(
+            failure(None, &[&[5, NO_ACCEPTABLE_METHODS]]).to_string(),
+            "the SOCKS5 proxy wants a username and password"
+        );
+        assert_eq!(
+            failure(
+                Some(("me", "wrong")),
+                &[&[5, USERNAME_PASSWORD], &[AUTH_VERSION, 1]]
+            )
+            .to_string(),
+            "the SOCKS5 proxy didn't accept the username and password"
+        );
+        assert_eq!(
+            failure(None, &[&[4, 90]]).to_string(),
+            "the proxy doesn't speak SOCKS5"
+        );
+        assert_eq!(
+            failure(None, &[accepted, &[5, 0, 0]]).kind(),
+            ErrorKind::UnexpectedEof
+        );
+    }
+}
@@ -1,11 +1,18 @@
 use crate::error::AppError;
+use crate::{Event, RESIZE_POLL_INTERVAL};
 use error_stack::{IntoReport, Result, ResultExt};
+use std::fs::File;
 use std::io;
-use std::io::Write;
-use termion::event::Key;
-use termion::input::TermRead;
+use std::io::{Read, Write};
+use std::os::fd::AsFd;
+use std::sync::mpsc::Sender;
+use std::thread;
+use termion::event::{self, Key};
 use termion::raw::{IntoRawMode, RawTerminal};
 
+const ESCAPE: u8 = 0x1b;
+const MOUSE_MOTION: usize = 32;
+
 #[derive(Default, Clone, Copy)]
 pub struct Position {
     pub x: usize,
@@ -20,6 +27,8 @@ pub struct Size {
 pub struct Terminal {
     _stdout: RawTerminal<io::Stdout>,
     cursor: Position,
+    reporting_focus: bool,
+    reporting_mouse: bool,
 }
 
 impl Terminal {
@@ -31,9 +40,21 @@ impl Terminal {
                 .attach_printable("Could not enter RAW mode.")
                 .change_context(AppError::TerminalError)?,
             cursor: Position::default(),
+            reporting_focus: false,
+            reporting_mouse: false,
         })
     }
 
+    pub fn report_focus(&mut self) {
+        print!("\x1b[?1004h");
+        self.reporting_focus = true;
+    }
+
+    pub fn report_mouse(&mut self) {
+        print!("\x1b[?1003h\x1b[?1006h");
+        self.reporting_mouse = true;
+    }
+
     pub fn size() -> Result<Size, AppError> {
         let (width, height) = termion::terminal_size()
             .into_report()
@@ -45,6 +66,21 @@ impl Terminal {
         })
     }
 
+    pub(crate) fn watch_size(sink: Sender<Event>) -> Result<(), AppError> {
+        let mut current_size = Self::size()?;
+        loop {
+            thread::sleep(RESIZE_POLL_INTERVAL);
+            let new_size = Self::size()?;
+            if new_size != current_size {
+                current_size = new_size;
+                sink.send(Event::Resize(new_size))
+                    .into_report()
+                    .attach_printable("Could not communicate terminal size to main thread.")
+                    .change_context(AppError::ChannelBroken)?;
+            }
+        }
+    }
+
     pub fn clear_screen() {
         print!("{}", termion::clear::All);
     }
@@ -63,34 +99,148 @@ impl Terminal {
         );
     }
 
-    pub fn cursor_hide() {
-        print!("{}", termion::cursor::Hide);
+    pub fn bell() {
+        print!("\x07");
     }
 
-    pub fn cursor_show() {
-        print!("{}", termion::cursor::Show);
+    pub fn write_screen(screen: &str) -> Result<(), AppError> {
+        let mut stdout = io::stdout().lock();
+        stdout
+            .write_all(screen.as_bytes())
+            .and_then(|()| stdout.flush())
+            .into_report()
+            .attach_printable("Could not write display buffer to TTY.")
+            .change_context(AppError::TerminalError)
+    }
+}
+impl Drop for Terminal {
+    fn drop(&mut self) {
+        // Otherwise the shell gets to see the focus reports once we're gone.
+        if self.reporting_focus {
+            print!("\x1b[?1004l");
+        }
+        if self.reporting_mouse {
+            print!("\x1b[?1006l\x1b[?1003l");
+        }
+        _ = io::stdout().flush();
     }
+}
 
-    pub fn flush() -> Result<(), AppError> {
-        io::stdout()
-            .flush()
+// Termion gives up on escape sequences it doesn't know, like focus reports and plain mouse movement, so those are
+// picked out first.
+#[derive(Default)]
+pub(crate) struct InputReader {
+    leftover: Option<u8>,
+}
+impl InputReader {
+    pub(crate) fn next(&mut self) -> Result<Option<Event>, AppError> {
+        // Unbuffered, so that whatever hasn't been read yet is still there to be polled for (see `more_waiting`).
+        let mut stdin = io::stdin()
+            .as_fd()
+            .try_clone_to_owned()
+            .map(File::from)
             .into_report()
-            .attach_printable("Could not flush display buffer to TTY.")
-            .change_context(AppError::TerminalError)?;
-        Ok(())
-    }
+            .attach_printable("Could not read user input.")
+            .change_context(AppError::UserInput)?;
+        loop {
+            // Reading two bytes at a time is how a lone Esc is told apart from the start of an escape sequence.
+            let mut buffer = [0u8; 2];
+            let (first, second) = match self.leftover.take() {
+                // With nothing after it, it was pressed on its own (like Esc twice, sent together over SSH).
+                Some(ESCAPE) if !more_waiting() => return Ok(Some(Event::Key(Key::Esc))),
+                Some(byte) => (byte, None),
+                None => match stdin.read(&mut buffer) {
+                    Ok(0) => return Ok(None),
+                    Ok(1) if buffer[0] == ESCAPE => return Ok(Some(Event::Key(Key::Esc))),
+                    Ok(1) => (buffer[0], None),
+                    Ok(_) if buffer == [ESCAPE, ESCAPE] && !more_waiting() => {
+                        self.leftover = Some(ESCAPE);
+                        return Ok(Some(Event::Key(Key::Esc)));
+                    }
+                    Ok(_) => (buffer[0], Some(buffer[1])),
+                    Err(error) if error.kind() == io::ErrorKind::Interrupted => continue,
+                    Err(error) => {
+                        return Err(error)
+                            .into_report()
+                            .attach_printable("Could not read user input.")
+                            .change_context(AppError::UserInput)
+                    }
+                },
+            };
 
-    pub fn read_key() -> Result<Option<Key>, AppError> {
-        if let Some(key) = io::stdin().lock().keys().next() {
-            match key {
-                Ok(key) => Ok(Some(key)),
-                Err(error) => Err(error)
-                    .into_report()
-                    .attach_printable("Could not determine user input.")
-                    .change_context(AppError::UserInput),
+            let mut rest = Vec::from_iter(second);
+            if first == ESCAPE && second == Some(b'[') {
+                let mut third = [0u8; 1];
+                if stdin.read(&mut third).is_ok_and(|read| read == 1) {
+                    match third[0] {
+                        b'I' => return Ok(Some(Event::Focus(true))),
+                        b'O' => return Ok(Some(Event::Focus(false))),
+                        b'<' => match read_mouse(&mut stdin) {
+                            Some(event) => return Ok(Some(event)),
+                            None => continue,
+                        },
+                        _ => {}
+                    }
+                    rest.push(third[0]);
+                }
+            }
+            let mut rest = rest.into_iter();
+            let parsed = {
+                let mut bytes = (&mut rest).map(Ok).chain(bytes(&mut stdin));
+                event::parse_event(first, &mut bytes)
+            };
+            self.leftover = rest.next();
+            match parsed {
+                Ok(event::Event::Key(key)) => return Ok(Some(Event::Key(key))),
+                Ok(_) => continue,
+                Err(error) => {
+                    return Err(error)
+                        .into_report()
+                        .attach_printable("Could not determine user input.")
+                        .change_context(AppError::UserInput)
+                }
             }
-        } else {
-            Ok(None)
         }
     }
 }
+
+// A byte at a time, since anything read ahead would be hidden from `more_waiting`.
+fn bytes(stdin: &mut File) -> impl Iterator<Item = io::Result<u8>> + '_ {
+    std::iter::from_fn(move || {
+        let mut byte = [0];
+        match stdin.read_exact(&mut byte) {
+            Ok(()) => Some(Ok(byte[0])),
+            Err(error) if error.kind() == io::ErrorKind::UnexpectedEof => None,
+            Err(error) => Some(Err(error)),
+        }
+    })
+}
+
+fn more_waiting() -> bool {
+    let mut stdin = libc::pollfd {
+        fd: libc::STDIN_FILENO,
+        events: libc::POLLIN,
+        revents: 0,
+    };
+    // SAFETY: `stdin` is a single, valid `pollfd` that outlives the call, and a zero timeout never blocks.
+    unsafe { libc::poll(&mut stdin, 1, 0) > 0 }
+}
+
+fn read_mouse(stdin: &mut File) -> Option<Event> {
+    let mut report = Vec::new();
+    for byte in bytes(stdin) {
+        match byte.ok()? {
+            b'M' | b'm' => break,
+            byte => report.push(byte),
+        }
+    }
+    let report = String::from_utf8(report).ok()?;
+    let mut numbers = report.split(';').map(|number| number.parse::<usize>().ok());
+    let (button, x, y) = (numbers.next()??, numbers.next()??, numbers.next()??);
+    (button & MOUSE_MOTION != 0).then(|| {
+        Event::Hover(Position {
+            x: x.saturating_sub(1),
+            y: y.saturating_sub(1),
+        })
+    })
+}
@@ -1,124 +1,540 @@
-use crate::error::AppError;
-use crate::paint::Painter;
+use crate::activity::Activity;
+use crate::alert::Alert;
+use crate::command::{Command, LogFormat};
+use crate::config::Config;
+use crate::connection::{Incoming, Stream, Transport};
+use crate::error::{AppError, InitError};
+use crate::framing::{Encoding, Framing};
+use crate::macros::{self, Recording};
+use crate::paint::{plain, PaintLine, Painter, Theme};
+use crate::radix::Radix;
+use crate::session::{self, Session};
+use crate::socks::Socks5;
 use crate::terminal::Position;
 use crate::terminal::Size;
 use crate::terminal::Terminal;
-use crate::{sections, MessageOrigin};
-use crate::{TcpMessage, THREAD_SLOW_DOWN};
-use error_stack::{IntoReport, Result, ResultExt};
-use std::net::TcpStream;
-use std::sync::mpsc::{Receiver, TryRecvError};
-use std::thread;
+use crate::{connection, sections, ConnectionId, Event, MessageOrigin, TcpMessage};
+use error_stack::{AttachmentKind, FrameKind, IntoReport, Report, Result, ResultExt};
+use std::collections::VecDeque;
+use std::fs;
+use std::io;
+use std::net::{SocketAddr, TcpStream};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{Receiver, RecvError, RecvTimeoutError, Sender};
+use std::time::{Duration, Instant};
 use termion::event::Key;
+use termion::{clear, cursor};
+
+pub(crate) const TITLE_HEIGHT: usize = 2;
+pub(crate) const INPUT_HEIGHT: usize = 3;
+const MIN_TERMINAL_SIZE: Size = Size {
+    width: 16,
+    height: TITLE_HEIGHT + INPUT_HEIGHT + 1,
+};
+const RETRY_INTERVAL: Duration = Duration::from_millis(100);
+const FILTER_INTERVAL: Duration = Duration::from_millis(10);
+const NO_MACROS: &str = "There's nowhere to keep macros (use \"--macros <path>\").";
 
 struct Sections {
     title: sections::Title,
     messages: sections::Messages,
     input: sections::Input,
+    passthrough: sections::Passthrough,
 }
 
 pub(crate) struct WindowReceiver {
-    message: Receiver<TcpMessage>,
-    input: Receiver<Key>,
+    events: Receiver<Event>,
+    sink: Sender<Event>,
 }
 impl WindowReceiver {
-    pub(crate) fn new(message: Receiver<TcpMessage>, input: Receiver<Key>) -> Self {
-        Self { message, input }
+    pub(crate) fn new(events: Receiver<Event>, sink: Sender<Event>) -> Self {
+        Self { events, sink }
+    }
+
+    pub(crate) fn sink(&self) -> Sender<Event> {
+        self.sink.clone()
+    }
+
+    pub(crate) fn listen(&self, connection: &Stream, id: ConnectionId) -> Result<(), InitError> {
+        let sink = self.sink();
+        connection::spawn_listener(connection, move |read| {
+            _ = sink.send(match read {
+                Incoming::Data(message) => Event::Remote(id, message),
+                Incoming::Closed => Event::Closed(id, false),
+                Incoming::Reset => Event::Closed(id, true),
+            });
+        })
     }
 }
 pub(crate) struct Window {
     terminal: Terminal,
+    focused: bool,
+    columns: Option<usize>,
+    theme: Theme,
+    title_height: usize,
+    input_height: usize,
     should_quit: bool,
+    // Set by a lone Esc, so a second one in a row quits.
+    quit_armed: bool,
+    passthrough: bool,
+    histogram: bool,
+    send_framing: Framing,
+    encoding: Encoding,
+    flush: bool,
+    proxy: Option<Socks5>,
+    args: Vec<String>,
+    reviewing: bool,
+    exit_on_close: bool,
+    monitor: bool,
+    keep_without_input: bool,
+    explicit_send: bool,
+    large_input: usize,
+    confirm_large: bool,
+    large_armed: bool,
+    connected_at: Option<Instant>,
+    idle_timeout: Option<Duration>,
+    active_at: Instant,
+    traffic: (usize, usize),
+    reset: bool,
+    paced: VecDeque<(Duration, TcpMessage)>,
+    paced_at: Instant,
+    macros: Option<PathBuf>,
+    recording: Option<Recording>,
+    throttle: Option<Duration>,
+    advanced_at: Instant,
+    activity: Option<Activity>,
+    radix: Radix,
+    connection_id: ConnectionId,
+    peer_addr: Option<SocketAddr>,
+    alert: Option<Alert>,
+    filter_out: Option<Sender<(bool, TcpMessage)>>,
     receiver: WindowReceiver,
     sections: Sections,
 }
 impl Window {
     pub(crate) fn new(
-        terminal: Terminal,
-        connection: TcpStream,
+        mut terminal: Terminal,
+        config: &Config,
+        connection: Option<Stream>,
         receiver: WindowReceiver,
     ) -> Result<Self, AppError> {
-        let addr = connection
-            .peer_addr()
-            .into_report()
-            .attach_printable("Could not determine address of remote connection.")
-            .change_context(AppError::StreamRead)?;
+        let mut labels = vec![
+            sections::LOCAL_LABEL,
+            sections::REMOTE_LABEL,
+            sections::PREVIEW_LABEL,
+        ];
+        labels.extend(Radix::ALL.map(Radix::label));
+        let gutter = match config.compact_labels {
+            true => sections::Gutter::compact(&labels),
+            false => sections::Gutter::new(&labels),
+        }
+        .with_separator(config.separator);
+
+        let mut title = sections::Title::new(gutter);
+        let mut peer_addr = (!config.unix).then_some(config.target);
+        let reviewing = connection.is_none();
+        let transport: Box<dyn Transport> = match connection {
+            Some(connection) => {
+                match &connection {
+                    Stream::Tcp(tcp) => {
+                        let (local_addr, connected_addr) =
+                            addrs(tcp, config.proxy.as_ref(), config.target)?;
+                        title.set_connected(local_addr, connected_addr);
+                        peer_addr = connected_addr.or(peer_addr);
+                    }
+                    Stream::Unix(_) => title.set_connected_unix(),
+                }
+                Box::new(connection)
+            }
+            None => Box::new(connection::Offline),
+        };
+        let session = Session::new(
+            transport,
+            config.flush,
+            config.recv_framing.clone(),
+            config.max_frame,
+        );
         let sections = Sections {
-            title: sections::Title::new(addr),
-            messages: sections::Messages::new(connection),
-            input: sections::Input::new(),
+            title,
+            messages: sections::Messages::new(session, gutter),
+            input: sections::Input::new(gutter, config.strict),
+            passthrough: sections::Passthrough::new(),
         };
+        if config.pause_unfocused {
+            terminal.report_focus();
+        }
+        if config.mouse {
+            terminal.report_mouse();
+        }
 
-        let window = Self {
+        let mut window = Self {
             should_quit: false,
+            quit_armed: false,
+            passthrough: false,
+            histogram: false,
+            send_framing: config.send_framing.clone(),
+            encoding: config.encoding,
+            flush: config.flush,
+            proxy: config.proxy.clone(),
+            reviewing,
+            exit_on_close: config.exit_on_close,
+            monitor: config.monitor,
+            keep_without_input: config.keep_without_input,
+            explicit_send: config.explicit_send,
+            large_input: config.large_input,
+            confirm_large: config.confirm_large,
+            large_armed: false,
+            connected_at: (!reviewing).then(Instant::now),
+            // A saved session that's only being looked at isn't going to be left behind by a script.
+            idle_timeout: config.idle_timeout.filter(|_| !reviewing),
+            active_at: Instant::now(),
+            traffic: (0, 0),
+            reset: false,
+            paced: VecDeque::new(),
+            paced_at: Instant::now(),
+            macros: config.macros.clone().or_else(macros::default_path),
+            args: config.args.clone(),
+            recording: None,
+            throttle: config.throttle,
+            advanced_at: Instant::now(),
+            activity: config.sparkline.then(Activity::new),
+            radix: config.radix,
+            connection_id: 0,
+            peer_addr,
+            alert: config.alert.clone(),
+            filter_out: None,
             terminal,
+            focused: true,
+            columns: config.columns,
+            title_height: config.title_height,
+            input_height: config.input_height,
+            theme: config.theme,
             sections,
             receiver,
         };
+        window.configure(config);
 
         Ok(window)
     }
 
+    fn configure(&mut self, config: &Config) {
+        let messages = &mut self.sections.messages;
+        messages.set_receiving(
+            config.recv_framing.clone(),
+            config.max_frame,
+            (config.strip_header, config.strip_footer),
+            config.filter_in.clone(),
+        );
+        messages.set_radix(config.radix);
+        messages.set_context(config.context);
+        messages.set_fixed_width(config.fixed_width);
+        messages.set_fixed_columns(config.local_columns, config.remote_columns);
+        messages.set_newest_first(config.newest_first);
+        messages.set_wrap_marker(&config.wrap_marker);
+        messages.set_placeholder(config.placeholder);
+        messages.set_unprintable(config.unprintable);
+        messages.set_controls(config.controls);
+        messages.set_show_empty(!config.hide_empty);
+        messages.set_diff(config.diff);
+        messages.set_highlight(config.highlight);
+        messages.set_checksum(config.recv_checksum, config.verify_checksum);
+        let input = &mut self.sections.input;
+        input.set_radix(config.radix);
+        input.set_strict(config.strict);
+        input.set_space_delimits(config.space_delimits);
+        input.set_auto_group(config.group_input);
+        input.set_detect(config.detect_input.clone());
+        input.set_keep_trailing_space(config.keep_trailing_space);
+
+        self.radix = config.radix;
+        self.send_framing = config.send_framing.clone();
+        self.encoding = config.encoding;
+        self.flush = config.flush;
+        self.explicit_send = config.explicit_send;
+        self.large_input = config.large_input;
+        self.confirm_large = config.confirm_large;
+        self.throttle = config.throttle;
+        self.alert = config.alert.clone();
+        self.filter_out = config.filter_out.clone().map(|filter| {
+            let sink = self.receiver.sink();
+            session::spawn_filter(
+                move |message| filter.apply(message),
+                move |secret, filtered| _ = sink.send(Event::Filtered(secret, filtered)),
+            )
+        });
+        self.theme = config.theme;
+    }
+
     pub(crate) fn run(&mut self) -> Result<(), AppError> {
         Terminal::clear_screen();
 
-        let mut should_draw = true;
-        let mut current_terminal_size = Terminal::size()?;
+        let mut current_terminal_size = self.layout_size(Terminal::size()?);
+        self.draw(&current_terminal_size)?;
 
         'main: loop {
-            if self.should_quit {
+            // Besides events, highlights ending, the connected time ticking over and anything queued to send each need
+            // a pass of their own.
+            let retried = self.sections.messages.retry_queued();
+            if self.unless_gone(retried)? && self.focused {
+                self.draw(&current_terminal_size)?;
+            }
+            if self.send_due()? && self.focused {
+                self.draw(&current_terminal_size)?;
+            }
+            let filtered = self.take_in(&current_terminal_size, |messages| {
+                Ok(messages.take_filtered())
+            });
+            if filtered? && self.focused {
+                self.draw(&current_terminal_size)?;
+            }
+            let traffic = self.sections.messages.traffic();
+            if traffic != self.traffic {
+                self.traffic = traffic;
+                self.active_at = Instant::now();
+            }
+            if self.idle_ends().is_some_and(|at| at <= Instant::now()) {
                 break 'main;
             }
-
-            match self.receiver.message.try_recv() {
-                Ok(message) => {
-                    self.sections
-                        .messages
-                        .handle_message(MessageOrigin::Remote(message));
-                    should_draw = true;
+            let retry_at = self
+                .sections
+                .messages
+                .has_queued()
+                .then(|| Instant::now() + RETRY_INTERVAL);
+            let filter_at = self
+                .sections
+                .messages
+                .is_filtering()
+                .then(|| Instant::now() + FILTER_INTERVAL);
+            let redraw_at = [
+                self.sections.messages.highlight_ends(),
+                self.next_second(),
+                self.held_until(),
+            ]
+            .into_iter()
+            .flatten()
+            .filter(|_| self.focused)
+            .chain(retry_at)
+            .chain(filter_at)
+            .chain(self.idle_ends())
+            .chain(self.paced.front().map(|(delay, _)| self.paced_at + *delay))
+            .min();
+            let event = match redraw_at {
+                Some(deadline) => {
+                    let timeout = deadline.saturating_duration_since(Instant::now());
+                    match self.receiver.events.recv_timeout(timeout) {
+                        Err(RecvTimeoutError::Timeout) => None,
+                        event => Some(event.map_err(|_| RecvError)),
+                    }
                 }
-                Err(err) if err == TryRecvError::Empty => (),
-                Err(err) => Err(err)
-                    .into_report()
-                    .attach_printable("TCP thread communication broke.")
-                    .change_context(AppError::ChannelBroken)?,
+                None => Some(self.receiver.events.recv()),
+            };
+            let Some(event) = event else {
+                if self.held_until().is_some_and(|at| at <= Instant::now()) {
+                    let size = self.messages_size(&current_terminal_size);
+                    self.sections.messages.advance(size);
+                    self.advanced_at = Instant::now();
+                }
+                if self.focused {
+                    self.draw(&current_terminal_size)?;
+                }
+                continue 'main;
+            };
+            let event = event
+                .into_report()
+                .attach_printable("Thread communication broke.")
+                .change_context(AppError::ChannelBroken)?;
+
+            let acknowledged = matches!(event, Event::Key(_))
+                && (self.sections.title.is_alerted() || self.sections.title.has_notice());
+            if acknowledged {
+                self.sections.title.set_alerted(false);
+                self.sections.title.set_notice(None);
             }
 
-            match self.receiver.input.try_recv() {
-                Ok(key) => {
-                    match key {
-                        Key::Ctrl('c') => {
-                            self.should_quit = true;
+            // Pressing Esc twice always quits, for terminals (or SSH and tmux setups) that swallow Ctrl+C.
+            let quit_armed = matches!(event, Event::Key(_)) && std::mem::take(&mut self.quit_armed);
+            let large_armed =
+                matches!(event, Event::Key(_)) && std::mem::take(&mut self.large_armed);
+
+            let should_draw = match event {
+                Event::Remote(id, message) if id == self.connection_id => {
+                    if let Some(alert) = &mut self.alert {
+                        if alert.matches(&message) {
+                            Terminal::bell();
+                            self.sections.title.set_alerted(true);
                         }
-                        Key::Char('\n') => {
-                            if let Some(message) = self.sections.input.drain_user_message() {
-                                self.sections
-                                    .messages
-                                    .handle_message(MessageOrigin::Local(message));
-                                should_draw = true;
-                            }
+                    }
+                    self.sections.passthrough.handle_message(&message);
+                    self.take_in(&current_terminal_size, |messages| {
+                        messages.handle_message(MessageOrigin::Remote(message))?;
+                        Ok(true)
+                    })?
+                }
+                Event::Remote(..) => false,
+                Event::Closed(id, reset) if id == self.connection_id && self.exit_on_close => {
+                    self.reset = reset;
+                    self.should_quit = true;
+                    false
+                }
+                Event::Closed(id, reset) if id == self.connection_id => {
+                    self.reset = reset;
+                    self.disconnected(match reset {
+                        true => "The connection to the remote server was reset.",
+                        false => "The remote server closed the connection.",
+                    });
+                    true
+                }
+                Event::Closed(..) => false,
+                Event::Key(Key::Ctrl('c')) => {
+                    self.should_quit = true;
+                    false
+                }
+                Event::Key(Key::Ctrl('o')) => {
+                    self.execute(Command::Connect {
+                        target: None,
+                        clear: false,
+                    })?;
+                    true
+                }
+                // While monitoring, keys only get to look around (or quit) and never anything that would end up sent.
+                Event::Key(key) if self.monitor && !looks_around(key) => false,
+                Event::Key(Key::Ctrl('r')) => {
+                    self.passthrough = !self.passthrough;
+                    Terminal::clear_screen();
+                    true
+                }
+                Event::Key(Key::Esc) if self.passthrough && quit_armed => {
+                    self.should_quit = true;
+                    false
+                }
+                Event::Key(key) if self.passthrough => {
+                    // A lone Esc still goes out, since interactive services often need one; only a second straight
+                    // after it quits.
+                    self.quit_armed = key == Key::Esc;
+                    if let Some(message) = sections::Passthrough::key_to_bytes(key) {
+                        self.transmit(message)?;
+                    }
+                    false
+                }
+                Event::Key(key) => match key {
+                    Key::Ctrl('x') => {
+                        self.sections.messages.toggle_diff();
+                        true
+                    }
+                    Key::Ctrl('f') => {
+                        self.histogram = !self.histogram;
+                        true
+                    }
+                    Key::Ctrl('e') => {
+                        self.sections.messages.toggle_local_echo();
+                        true
+                    }
+                    Key::Ctrl('t') => {
+                        self.sections.messages.toggle_stream();
+                        true
+                    }
+                    Key::Ctrl('g') => {
+                        self.sections.messages.toggle_grouped();
+                        true
+                    }
+                    Key::Ctrl('y') => {
+                        match self.sections.messages.inspected() {
+                            Ok(message) => self.sections.input.set_message(message),
+                            Err(notice) => self.notify(notice),
                         }
-                        _ => should_draw = self.sections.input.handle_key(key),
+                        true
+                    }
+                    Key::PageUp | Key::PageDown => {
+                        let size = self.messages_size(&current_terminal_size);
+                        let scrolled = if key == Key::PageUp {
+                            self.sections.messages.page_up(size)
+                        } else {
+                            self.sections.messages.page_down(size)
+                        };
+                        if let Err(notice) = scrolled {
+                            self.notify(notice);
+                        }
+                        true
+                    }
+                    Key::Left => self.sections.messages.scroll_left(),
+                    Key::Right => self.sections.messages.scroll_right(),
+                    Key::Up => {
+                        self.sections.messages.inspect_up();
+                        true
+                    }
+                    Key::Down => {
+                        self.sections.messages.inspect_down();
+                        true
+                    }
+                    Key::Esc if self.sections.messages.stop_inspecting() => true,
+                    Key::Esc if quit_armed => {
+                        self.should_quit = true;
+                        false
+                    }
+                    Key::Esc => {
+                        self.quit_armed = true;
+                        self.notify("Press Esc again to quit.".to_string());
+                        true
+                    }
+                    Key::Ctrl('s') => {
+                        self.sections.input.toggle_secret();
+                        true
+                    }
+                    Key::Ctrl('b') => {
+                        self.radix = self.radix.next();
+                        self.sections.messages.set_radix(self.radix);
+                        self.sections.input.set_radix(self.radix);
+                        true
+                    }
+                    Key::Char('\n') if self.explicit_send && !self.sections.input.is_command() => {
+                        self.sections.input.handle_key(Key::Char(' '))
+                    }
+                    Key::Char('\n') | Key::Ctrl('d') => self.submit(large_armed)?,
+                    _ => self.sections.input.handle_key(key),
+                },
+                Event::Resize(size) => {
+                    current_terminal_size = self.layout_size(size);
+                    true
+                }
+                Event::Hover(position) => {
+                    let (title_height, _) = self.heights(&current_terminal_size);
+                    let byte = match position.y.checked_sub(title_height) {
+                        Some(y) if !self.passthrough => self.sections.messages.byte_at(
+                            self.messages_size(&current_terminal_size),
+                            Position { y, ..position },
+                        )?,
+                        _ => None,
                     };
+                    match self.monitor {
+                        true => self.sections.title.set_hover(byte),
+                        false => self.sections.input.set_hover(byte),
+                    }
                 }
-                Err(err) if err == TryRecvError::Empty => (),
-                Err(err) => Err(err)
-                    .into_report()
-                    .attach_printable("User Input thread communication broke.")
-                    .change_context(AppError::ChannelBroken)?,
-            }
+                Event::Focus(focused) => {
+                    self.focused = focused;
+                    focused
+                }
+                Event::Filtered(secret, filtered) => {
+                    self.filtered(secret, filtered)?;
+                    true
+                }
+                // Without a terminal there's usually nobody left to see the window, or to quit it.
+                Event::InputClosed if self.keep_without_input => {
+                    self.notify("Stdin was closed; nothing more can be typed.".to_string());
+                    true
+                }
+                Event::InputClosed => {
+                    self.should_quit = true;
+                    false
+                }
+            };
 
-            let new_terminal_size = Terminal::size()?;
-            if current_terminal_size != new_terminal_size {
-                should_draw = true;
-                current_terminal_size = new_terminal_size;
+            if self.should_quit {
+                break 'main;
             }
-
-            if should_draw {
+            if (should_draw || acknowledged) && self.focused {
                 self.draw(&current_terminal_size)?;
-                should_draw = false;
             }
-            thread::sleep(THREAD_SLOW_DOWN);
         }
 
         Terminal::clear_screen();
@@ -126,53 +542,611 @@ impl Window {
         Ok(())
     }
 
+    fn execute(&mut self, command: Command) -> Result<(), AppError> {
+        match command {
+            Command::Quit => self.should_quit = true,
+            Command::Clear => self.sections.messages.clear(),
+            Command::Connect { target, clear } => match target.or(self.peer_addr) {
+                Some(target) => self.reconnect(target, clear)?,
+                None => self.notify(
+                    "A Unix socket that was passed down can't be reconnected to; give an address and port."
+                        .to_string(),
+                ),
+            },
+            Command::Save(path, format) => match fs::write(&path, self.export(format)) {
+                Ok(()) => self.notify(format!("Saved to {}.", path.display())),
+                Err(error) => self.notify(format!("Could not save to {}: {error}", path.display())),
+            },
+            Command::Record(name) => {
+                let notice = match &self.recording {
+                    Some(recording) => format!("Already recording macro \"{}\".", recording.name),
+                    None => format!("Recording macro \"{name}\" (\":macro stop\" saves it)."),
+                };
+                self.recording.get_or_insert_with(|| Recording::new(name));
+                self.notify(notice);
+            }
+            Command::StopRecording => {
+                let notice = match (self.recording.take(), &self.macros) {
+                    (None, _) => "Not recording a macro.".to_string(),
+                    (Some(recording), _) if recording.steps.is_empty() => format!(
+                        "Nothing was sent, so macro \"{}\" wasn't saved.",
+                        recording.name
+                    ),
+                    (Some(_), None) => NO_MACROS.to_string(),
+                    (Some(recording), Some(path)) => {
+                        match macros::save(path, &recording.name, &recording.steps) {
+                            Ok(()) => format!(
+                                "Saved macro \"{}\" ({} message{}) to {}.",
+                                recording.name,
+                                recording.steps.len(),
+                                if recording.steps.len() == 1 { "" } else { "s" },
+                                path.display()
+                            ),
+                            Err(notice) => notice,
+                        }
+                    }
+                };
+                self.notify(notice);
+            }
+            Command::Run(name) => {
+                let loaded = match &self.macros {
+                    Some(path) => macros::load(path, &name),
+                    None => Err(NO_MACROS.to_string()),
+                };
+                match loaded {
+                    Ok(steps) => self.send_paced(steps),
+                    Err(notice) => self.notify(notice),
+                }
+            }
+            Command::Load(path) => match fs::read(&path) {
+                Ok(bytes) => {
+                    for message in self.send_framing.split(&bytes) {
+                        self.send(message)?;
+                    }
+                }
+                Err(error) => self.notify(format!("Could not load {}: {error}", path.display())),
+            },
+        }
+        Ok(())
+    }
+
+    fn export(&self, format: LogFormat) -> String {
+        match format {
+            LogFormat::Plain => self.sections.messages.to_log(),
+            LogFormat::Ansi => self
+                .sections
+                .messages
+                .painted_log()
+                .iter()
+                .map(|line| format!("{}\n", self.theme.ansi(line)))
+                .collect(),
+            LogFormat::Html => self.theme.html(&self.sections.messages.painted_log()),
+        }
+    }
+
+    fn reconnect(&mut self, target: SocketAddr, clear: bool) -> Result<(), AppError> {
+        let config = match Config::for_target(&self.args, target)
+            .and_then(|config| config.check_family(target).map(|()| config))
+        {
+            Ok(config) => config,
+            Err(report) => {
+                self.notify(reason(&report));
+                return Ok(());
+            }
+        };
+        let connection = match connection::connect(target, config.flush, self.proxy.as_ref()) {
+            Ok(connection) => connection,
+            Err(report) => {
+                // Through a proxy, it might be the proxy that couldn't be reached.
+                let failed_addr = report.downcast_ref::<SocketAddr>().unwrap_or(&target);
+                let reason = match report.downcast_ref::<io::Error>() {
+                    Some(error) => connection::describe_connect_error(error, *failed_addr),
+                    None => format!(
+                        "Could not connect to {} on port {}.",
+                        target.ip(),
+                        target.port()
+                    ),
+                };
+                self.notify(reason);
+                return Ok(());
+            }
+        };
+        let (local_addr, peer_addr) = addrs(&connection, self.proxy.as_ref(), target)?;
+
+        self.connection_id += 1;
+        let connection = Stream::Tcp(connection);
+        self.receiver
+            .listen(&connection, self.connection_id)
+            .change_context(AppError::StreamRead)?;
+        // Anything still waiting to go out was meant for the old peer.
+        self.sections.messages.discard_queued();
+        self.paced.clear();
+        let old_connection = self
+            .sections
+            .messages
+            .replace_connection(Box::new(connection));
+        // Shutting the old socket down unblocks its listener thread, which then exits by itself.
+        old_connection.shutdown();
+        self.traffic = (0, 0);
+
+        self.peer_addr = Some(peer_addr.unwrap_or(target));
+        self.reviewing = false;
+        self.reset = false;
+        self.connected_at = Some(Instant::now());
+        self.active_at = Instant::now();
+        self.sections.title.set_connected(local_addr, peer_addr);
+        self.configure(&config);
+        if clear {
+            self.sections.messages.clear();
+            self.sections.passthrough.clear();
+        }
+        Ok(())
+    }
+
+    fn submit(&mut self, confirmed: bool) -> Result<bool, AppError> {
+        let length = match self.sections.input.is_command() {
+            true => None,
+            false => self.sections.input.user_message_len(),
+        };
+        let large = Large::of(length, self.large_input, self.confirm_large, confirmed);
+        if let Large::Confirm(length) = large {
+            self.large_armed = true;
+            let key = if self.explicit_send {
+                "Ctrl+D"
+            } else {
+                "Enter"
+            };
+            self.notify(format!(
+                "This message is {length} bytes. Press {key} again to send it."
+            ));
+            return Ok(true);
+        }
+        if let Some(command) = self.sections.input.drain_command() {
+            match Command::parse(&command) {
+                Ok(command) => self.execute(command)?,
+                Err(notice) => self.notify(notice),
+            }
+            Ok(true)
+        } else if let Some(message) = self.sections.input.drain_user_message() {
+            if let Large::Warn(length) = large {
+                self.notify(format!(
+                    "Sending a large message ({length} bytes, over the {} byte limit).",
+                    self.large_input
+                ));
+            }
+            if self.sections.input.take_secret() {
+                self.send_secret(message)?;
+            } else {
+                self.send(message)?;
+            }
+            Ok(true)
+        } else {
+            Ok(false)
+        }
+    }
+
+    fn disconnected(&mut self, notice: &str) {
+        let old_connection = self
+            .sections
+            .messages
+            .replace_connection(Box::new(connection::Offline));
+        old_connection.shutdown();
+        self.sections.messages.discard_queued();
+        self.paced.clear();
+        self.reviewing = true;
+        self.connected_at = None;
+        self.sections.title.set_disconnected(self.peer_addr);
+        self.notify(notice.to_string());
+    }
+
+    fn unless_gone<T: Default>(&mut self, result: Result<T, AppError>) -> Result<T, AppError> {
+        match result {
+            Err(report) if report.downcast_ref().is_some_and(connection::is_gone) => {
+                self.disconnected("Not sent: the remote server is no longer accepting data.");
+                Ok(T::default())
+            }
+            result => result,
+        }
+    }
+
+    pub(crate) fn review(&mut self, path: &Path, log: &str) -> std::result::Result<(), String> {
+        self.sections.messages.load_log(log)?;
+        self.sections.title.set_reviewing(path);
+        Ok(())
+    }
+
+    pub(crate) fn send(&mut self, message: TcpMessage) -> Result<(), AppError> {
+        if let Some(recording) = &mut self.recording {
+            recording.record(&message);
+        }
+        self.transmit(message)
+    }
+
+    // Unlike `send`, nothing goes into a macro being recorded.
+    fn transmit(&mut self, message: TcpMessage) -> Result<(), AppError> {
+        match self.frame(message) {
+            Some(message) => self.filter_or_send(false, message),
+            None => Ok(()),
+        }
+    }
+
+    pub(crate) fn send_paced(
+        &mut self,
+        messages: impl IntoIterator<Item = (Duration, TcpMessage)>,
+    ) {
+        self.paced.extend(messages);
+    }
+
+    fn send_due(&mut self) -> Result<bool, AppError> {
+        let mut sent = false;
+        while let Some((delay, _)) = self.paced.front() {
+            if self.paced_at + *delay > Instant::now() {
+                break;
+            }
+            let (_, message) = self
+                .paced
+                .pop_front()
+                .expect("the front was just looked at");
+            self.send(message)?;
+            self.paced_at = Instant::now();
+            sent = true;
+        }
+        Ok(sent)
+    }
+
+    pub(crate) fn send_secret(&mut self, message: TcpMessage) -> Result<(), AppError> {
+        match self.frame(message) {
+            Some(message) => self.filter_or_send(true, message),
+            None => Ok(()),
+        }
+    }
+
+    fn filter_or_send(&mut self, secret: bool, message: TcpMessage) -> Result<(), AppError> {
+        // Keystrokes in passthrough mode aren't filtered either.
+        match &self.filter_out {
+            Some(filter) if !self.passthrough => {
+                filter
+                    .send((secret, message))
+                    .into_report()
+                    .attach_printable("The outgoing filter stopped.")
+                    .change_context(AppError::ChannelBroken)?;
+                Ok(())
+            }
+            _ => self.filtered(secret, Ok(message)),
+        }
+    }
+
+    fn filtered(
+        &mut self,
+        secret: bool,
+        filtered: std::result::Result<TcpMessage, String>,
+    ) -> Result<(), AppError> {
+        let message = match filtered {
+            Ok(_) if self.reviewing => {
+                self.notify(
+                    "Not sent: not connected (use \":connect <addr> <port>\").".to_string(),
+                );
+                return Ok(());
+            }
+            Ok(message) => message,
+            Err(notice) => {
+                self.notify(format!("Not sent: {notice}"));
+                return Ok(());
+            }
+        };
+        let sent = match secret {
+            true => self.sections.messages.handle_secret(message),
+            false => self
+                .sections
+                .messages
+                .handle_message(MessageOrigin::Local(message)),
+        };
+        self.unless_gone(sent)
+    }
+
+    fn take_in(
+        &mut self,
+        terminal_size: &Size,
+        add: impl FnOnce(&mut sections::Messages) -> Result<bool, AppError>,
+    ) -> Result<bool, AppError> {
+        // Counted before it's added, so whatever it adds is what's held back.
+        let width = self.messages_size(terminal_size).width;
+        let held_from = self
+            .hold()
+            .then(|| self.sections.messages.line_count(width));
+        let added = add(&mut self.sections.messages)?;
+        if let Some(notice) = self.sections.messages.take_notice() {
+            self.notify(notice);
+        }
+        match held_from {
+            Some(rows) => {
+                let added = self
+                    .sections
+                    .messages
+                    .line_count(width)
+                    .saturating_sub(rows);
+                self.sections.messages.hold_back(added);
+                Ok(false)
+            }
+            None => Ok(added),
+        }
+    }
+
+    // Notices go in the input section, which isn't there while monitoring.
+    fn notify(&mut self, notice: String) {
+        match self.monitor {
+            true => self.sections.title.set_notice(Some(notice)),
+            false => self.sections.input.set_notice(notice),
+        }
+    }
+
+    fn frame(&mut self, message: TcpMessage) -> Option<TcpMessage> {
+        if self.reviewing {
+            self.notify("Not sent: not connected (use \":connect <addr> <port>\").".to_string());
+            return None;
+        }
+        // Keystrokes in passthrough mode are a stream of their own, not messages of the protocol being spoken.
+        if self.passthrough {
+            return Some(message);
+        }
+        match self.encoding.encode(message) {
+            Ok(message) => Some(message),
+            Err(notice) => {
+                self.notify(notice);
+                None
+            }
+        }
+    }
+
+    pub(crate) fn dump(&self, include_local: bool) -> Vec<u8> {
+        self.sections.messages.dump(include_local)
+    }
+
+    pub(crate) fn was_reset(&self) -> bool {
+        self.reset
+    }
+
+    fn next_second(&self) -> Option<Instant> {
+        self.connected_at
+            .map(|at| at + Duration::from_secs(at.elapsed().as_secs() + 1))
+    }
+
+    fn idle_ends(&self) -> Option<Instant> {
+        self.idle_timeout.map(|timeout| self.active_at + timeout)
+    }
+
+    fn hold(&mut self) -> bool {
+        let Some(throttle) = self.throttle else {
+            return false;
+        };
+        let hold = self.sections.messages.is_holding() || self.advanced_at.elapsed() < throttle;
+        if !hold {
+            self.advanced_at = Instant::now();
+        }
+        hold
+    }
+
+    fn held_until(&self) -> Option<Instant> {
+        self.throttle
+            .filter(|_| self.sections.messages.is_holding())
+            .map(|throttle| self.advanced_at + throttle)
+    }
+
+    fn heights(&self, terminal_size: &Size) -> (usize, usize) {
+        // Monitoring leaves nothing to type into, so the messages get the input's rows as well.
+        let input = |height| if self.monitor { 0 } else { height };
+        if self.title_height + input(self.input_height) < terminal_size.height {
+            (self.title_height, input(self.input_height))
+        } else {
+            (TITLE_HEIGHT, input(INPUT_HEIGHT))
+        }
+    }
+
+    fn messages_size(&self, terminal_size: &Size) -> Size {
+        let (title_height, input_height) = self.heights(terminal_size);
+        Size {
+            width: terminal_size.width,
+            height: terminal_size
+                .height
+                .saturating_sub(title_height + input_height),
+        }
+    }
+
+    fn layout_size(&self, terminal_size: Size) -> Size {
+        Size {
+            width: self.columns.unwrap_or(terminal_size.width),
+            ..terminal_size
+        }
+    }
+
     fn draw(&mut self, terminal_size: &Size) -> Result<(), AppError> {
-        Terminal::cursor_hide();
+        let screen = self.paint_screen(terminal_size)?;
+        Terminal::write_screen(&screen)
+    }
+
+    fn paint_screen(&mut self, terminal_size: &Size) -> Result<String, AppError> {
+        let mut screen = cursor::Hide.to_string();
 
+        if let Some(connected_at) = self.connected_at {
+            let (sent, received) = self.sections.messages.traffic();
+            let idle_left = self
+                .idle_ends()
+                .map(|at| at.saturating_duration_since(Instant::now()));
+            self.sections
+                .title
+                .set_status(connected_at.elapsed(), sent, received, idle_left);
+            if let Some(activity) = &mut self.activity {
+                activity.record(sent + received);
+                self.sections.title.set_activity(Some(activity.sparkline()));
+            }
+        }
+
+        // Detached or pseudo terminals sometimes report a size of zero; there's no sensible layout that small.
+        if terminal_size.width < MIN_TERMINAL_SIZE.width
+            || terminal_size.height < MIN_TERMINAL_SIZE.height
+        {
+            screen.push_str(clear::All.as_ref());
+            if terminal_size.width > 0 && terminal_size.height > 0 {
+                let mut notice = plain("Terminal too small.");
+                notice.truncate(terminal_size.width);
+                self.print(&mut screen, &[notice], Position { x: 0, y: 0 });
+            }
+            return Ok(screen);
+        }
+
+        let (title_height, input_height) = self.heights(terminal_size);
         self.print(
+            &mut screen,
             &self.sections.title.paint(Size {
                 width: terminal_size.width,
-                height: 2,
+                height: title_height,
             })?,
             Position { x: 0, y: 0 },
         );
 
-        self.print(
-            &self.sections.messages.paint(Size {
+        if self.passthrough {
+            let passthrough_size = Size {
                 width: terminal_size.width,
-                height: terminal_size.height - 3,
-            })?,
-            Position { x: 0, y: 2 },
-        );
+                height: terminal_size.height - title_height,
+            };
+            self.print(
+                &mut screen,
+                &self.sections.passthrough.paint(passthrough_size)?,
+                Position {
+                    x: 0,
+                    y: title_height,
+                },
+            );
+            let (x, y) = self
+                .sections
+                .passthrough
+                .get_cursor_position(passthrough_size);
+            push_cursor(&mut screen, x, y + title_height as u16);
+            screen.push_str(cursor::Show.as_ref());
+            return Ok(screen);
+        }
 
+        let messages_size = self.messages_size(terminal_size);
+        let messages = match self.histogram {
+            true => self.sections.messages.histogram().paint(messages_size)?,
+            false => self.sections.messages.paint(messages_size)?,
+        };
         self.print(
-            &self.sections.input.paint(Size {
-                width: terminal_size.width,
-                height: 2,
-            })?,
+            &mut screen,
+            &messages,
             Position {
                 x: 0,
-                y: terminal_size.height - 2,
+                y: title_height,
             },
         );
 
-        self.terminal.move_cursor(
-            self.sections
-                .input
-                .get_cursor_x_position(terminal_size.width),
-            (terminal_size.height - 1) as u16,
+        if self.monitor {
+            return Ok(screen);
+        }
+        let input_size = Size {
+            width: terminal_size.width,
+            height: input_height,
+        };
+        let input_top = terminal_size.height - input_height;
+        self.print(
+            &mut screen,
+            &self.sections.input.paint(input_size)?,
+            Position { x: 0, y: input_top },
         );
 
-        Terminal::cursor_show();
-        Terminal::flush()?;
-        Ok(())
+        let (x, y) = self.sections.input.get_cursor_position(input_size);
+        push_cursor(&mut screen, x, y + input_top as u16);
+        screen.push_str(cursor::Show.as_ref());
+        Ok(screen)
     }
 
-    fn print(&mut self, content: &[Vec<char>], position: Position) {
+    fn print(&self, screen: &mut String, content: &[PaintLine], position: Position) {
         content.iter().enumerate().for_each(|(index, line)| {
-            self.terminal
-                .move_cursor(position.x as u16, (position.y + index) as u16);
-            print!("{}", line.iter().collect::<String>());
+            push_cursor(screen, position.x as u16, (position.y + index) as u16);
+            screen.push_str(&self.theme.ansi(line));
         });
     }
 }
+
+#[derive(Debug, PartialEq)]
+enum Large {
+    Fine,
+    Warn(usize),
+    Confirm(usize),
+}
+impl Large {
+    fn of(length: Option<usize>, limit: usize, confirm: bool, confirmed: bool) -> Self {
+        match length.filter(|length| *length > limit) {
+            None => Self::Fine,
+            Some(_) if confirmed => Self::Fine,
+            Some(length) if confirm => Self::Confirm(length),
+            Some(length) => Self::Warn(length),
+        }
+    }
+}
+
+fn looks_around(key: Key) -> bool {
+    matches!(
+        key,
+        Key::Ctrl('e' | 't' | 'g' | 'b' | 'f' | 'x')
+            | Key::PageUp
+            | Key::PageDown
+            | Key::Left
+            | Key::Right
+            | Key::Up
+            | Key::Down
+            | Key::Esc
+    )
+}
+
+// Positions are counted from zero, but the terminal counts from one.
+fn push_cursor(screen: &mut String, x: u16, y: u16) {
+    screen.push_str(&cursor::Goto(x.saturating_add(1), y.saturating_add(1)).to_string());
+}
+
+fn reason(report: &Report<InitError>) -> String {
+    report
+        .frames()
+        .filter_map(|frame| match frame.kind() {
+            FrameKind::Attachment(AttachmentKind::Printable(printable)) => {
+                Some(printable.to_string())
+            }
+            _ => None,
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+// Not knowing the peer address isn't a reason to give up on a working connection.
+fn addrs(
+    connection: &TcpStream,
+    proxy: Option<&Socks5>,
+    target: SocketAddr,
+) -> Result<(SocketAddr, Option<SocketAddr>), AppError> {
+    let local_addr = connection
+        .local_addr()
+        .into_report()
+        .attach_printable("Could not determine address of local connection.")
+        .change_context(AppError::StreamRead)?;
+    let peer_addr = match proxy {
+        Some(_) => Some(target),
+        None => connection.peer_addr().ok(),
+    };
+    Ok((local_addr, peer_addr))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn large_messages_are_warned_about_or_confirmed() {
+        assert_eq!(Large::of(Some(4), 4, false, false), Large::Fine);
+        assert_eq!(Large::of(Some(5), 4, false, false), Large::Warn(5));
+        assert_eq!(Large::of(Some(5), 4, true, false), Large::Confirm(5));
+        // Pressing Enter again sends it, without asking a second time.
+        assert_eq!(Large::of(Some(5), 4, true, true), Large::Fine);
+        // Commands (and input that isn't a message yet) aren't sized.
+        assert_eq!(Large::of(None, 4, true, false), Large::Fine);
+    }
+}
@@ -0,0 +1,94 @@
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::process::{Child, Command, Stdio};
+
+// Stopped however the test ends, so a failing one doesn't leave it listening.
+struct Server(Child);
+impl Drop for Server {
+    fn drop(&mut self) {
+        _ = self.0.kill();
+        _ = self.0.wait();
+    }
+}
+
+fn hexcat() -> Command {
+    Command::new(env!("CARGO_BIN_EXE_hexcat"))
+}
+
+// Port 0 has it listen wherever's free, which it says on stderr.
+fn echo_server() -> (Server, SocketAddr) {
+    let mut child = hexcat()
+        .args(["--echo-server", "127.0.0.1", "0"])
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    let mut line = String::new();
+    BufReader::new(child.stderr.take().unwrap())
+        .read_line(&mut line)
+        .unwrap();
+    let addr = line
+        .trim_end()
+        .strip_prefix("Echoing on ")
+        .and_then(|addr| addr.strip_suffix('.'))
+        .unwrap_or_else(|| panic!("unexpected output: {line:?}"))
+        .parse()
+        .unwrap();
+    (Server(child), addr)
+}
+
+#[test]
+fn echoes_each_connection_back_to_itself() {
+    let (_server, addr) = echo_server();
+    let mut first = TcpStream::connect(addr).unwrap();
+    let mut second = TcpStream::connect(addr).unwrap();
+    second.write_all(b"second").unwrap();
+    first.write_all(b"first").unwrap();
+    let mut buffer = [0; 6];
+    second.read_exact(&mut buffer).unwrap();
+    assert_eq!(&buffer, b"second");
+    let mut buffer = [0; 5];
+    first.read_exact(&mut buffer).unwrap();
+    assert_eq!(&buffer, b"first");
+}
+
+#[test]
+fn another_hexcat_gets_its_messages_back() {
+    let (_server, addr) = echo_server();
+    let mut client = hexcat()
+        .args(["--pipe", &addr.ip().to_string(), &addr.port().to_string()])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .unwrap();
+    // Stdin stays open until the reply is in, or the pipe would hang up before it arrived.
+    let mut stdin = client.stdin.take().unwrap();
+    stdin.write_all(b"\xde\xad\xbe\xef").unwrap();
+    let mut reply = [0; 4];
+    client
+        .stdout
+        .take()
+        .unwrap()
+        .read_exact(&mut reply)
+        .unwrap();
+    assert_eq!(reply, [0xde, 0xad, 0xbe, 0xef]);
+    drop(stdin);
+    assert!(client.wait().unwrap().success());
+}
+
+#[test]
+fn says_when_the_port_is_already_in_use() {
+    let taken = TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = taken.local_addr().unwrap().port();
+    let output = hexcat()
+        .args(["--echo-server", "127.0.0.1", &port.to_string()])
+        .env("RUST_BACKTRACE", "0")
+        .env("RUST_LIB_BACKTRACE", "0")
+        .output()
+        .unwrap();
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        stderr.contains(&format!("Port {port} is already in use")),
+        "{stderr}"
+    );
+}